@@ -0,0 +1,5 @@
+function Update()
+    if get_current_anim() == "idle" and math.random(0, 100) < 1 then
+        set_current_anim("quacking")
+    end
+end
@@ -0,0 +1,6 @@
+-- Optional logic shared by every state. It's loaded before the states,
+-- so they can use the functions defined here.
+
+-- Called every global_tick_delay, whatever state the pet is in.
+-- function Tick()
+-- end
@@ -25,3 +25,10 @@ function Update()
     end
 end
 
+
+-- Optional sequential logic, run as a coroutine. wait(ms) pauses it.
+-- function Main()
+--     set_current_anim("quacking")
+--     wait(2000)
+--     set_current_anim("blink")
+-- end
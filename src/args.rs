@@ -1,10 +1,244 @@
-use clap::{ArgAction, Parser};
+use std::{path::PathBuf, str::FromStr};
+
+use a_duk::pet::DEFAULT_MAX_FRAME_BYTES;
+use clap::{ArgAction, Parser, Subcommand, ValueEnum};
+
+use crate::exit::EXIT_CODES_HELP;
 
 #[derive(Parser, Debug)]
-#[command(version = "1.0.0", about = "A duk.")]
+#[command(version = "1.0.0", about = "A duk.", after_help = EXIT_CODES_HELP)]
 pub struct Args {
-    #[arg(short, long, default_value("duk"))]
-    pub pet: String,
-    #[arg(short, long, action(ArgAction::SetTrue), default_value("false"))]
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    // Running without a subcommand is the same as `run`
+    #[command(flatten)]
+    pub run: RunArgs,
+    /// Write a debug log to a_duk.log in the configuration directory
+    #[arg(short, long, global = true, action(ArgAction::SetTrue), default_value("false"))]
     pub debug: bool,
+    /// The directory pets are looked up in, defaults to the pets folder of the configuration directory
+    #[arg(long, global = true, env = "A_DUK_PET_DIR")]
+    pub pet_dir: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Run a pet (the default)
+    Run(RunArgs),
+    /// Create a new pet in the configuration directory
+    New {
+        name: String,
+    },
+    /// List the installed pets
+    List,
+    /// Save the names and descriptions of the installed pets so list and the picker don't have to read every pet
+    Index,
+    /// Check that a pet loads without running it
+    Validate {
+        pet: String,
+        /// Treat gaps in frame numbers as errors
+        #[arg(long, action(ArgAction::SetTrue), default_value("false"))]
+        strict: bool,
+    },
+    /// Show how many frames, states and bytes of frame text and Lua a pet loads
+    Audit {
+        pet: String,
+    },
+    /// Export an animation to a file that plays it outside of the terminal
+    Export {
+        pet: String,
+        animation: String,
+        #[arg(long, value_enum, default_value_t = ExportFormat::Html)]
+        format: ExportFormat,
+        /// Where to write it, <pet>-<animation>.html in the current directory by default
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum ExportFormat {
+    /// A web page that replays the animation with CSS
+    Html,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct RunArgs {
+    /// The pet to run, repeat to run several pets side by side. Without it, the pet is picked from a list if several are installed, otherwise it's duk
+    #[arg(short, long)]
+    pub pet: Vec<String>,
+    /// Show the first frame of every animation in a grid instead of running the pet
+    #[arg(long, action(ArgAction::SetTrue), default_value("false"))]
+    pub preview: bool,
+    /// Where to draw the pet
+    #[arg(long, value_enum, default_value_t = Output::Stdout)]
+    pub output: Output,
+    /// Record what's drawn to an asciinema v2 .cast file, until the pet exits or --duration is over
+    #[arg(long, value_name = "FILE")]
+    pub record_cast: Option<PathBuf>,
+    /// Refuse to load animations with gaps in their frame numbers, like 0, 1, 3, instead of warning about them
+    #[arg(long, action(ArgAction::SetTrue), default_value("false"))]
+    pub strict: bool,
+    /// Ask before quitting with Esc, pets can turn this on with confirm_quit in their metadata
+    #[arg(long, action(ArgAction::SetTrue), default_value("false"))]
+    pub confirm_quit: bool,
+    /// Refuse to load pets whose frames add up to more than this many MiB
+    #[arg(long, value_name = "MB", default_value_t = DEFAULT_MAX_FRAME_BYTES / (1024 * 1024))]
+    pub max_assets_mb: u64,
+    /// Pause the pet while the terminal isn't focused
+    #[arg(long, action(ArgAction::SetTrue), default_value("false"))]
+    pub pause_on_blur: bool,
+    /// Speed up or slow down the pet, from 0.1 to 10
+    #[arg(long, default_value_t = 1.0)]
+    pub speed: f64,
+    /// Exit after running for this many seconds, 0 runs until quit
+    #[arg(long, value_name = "SECS", default_value_t = 0)]
+    pub duration: u64,
+    /// Seed the random number generators, for repeatable runs
+    #[arg(long)]
+    pub seed: Option<u64>,
+    /// Run on a virtual clock that advances by the tick delay every loop, with a fixed seed (0 unless --seed is given)
+    #[arg(long, action(ArgAction::SetTrue), default_value("false"))]
+    pub deterministic: bool,
+    /// How many colors to draw with, detected from COLORTERM and TERM by default
+    #[arg(long, value_enum, default_value_t = ColorChoice::Auto)]
+    pub color: ColorChoice,
+    /// Log and disable failing Lua handlers instead of exiting, F5 turns them back on
+    #[arg(long, action(ArgAction::SetTrue), default_value("false"))]
+    pub keep_alive: bool,
+    /// Step through the frames of an animation with the arrow keys instead of running the pet
+    #[arg(long, value_name = "ANIMATION", conflicts_with = "preview")]
+    pub inspect: Option<String>,
+    /// Print every frame of an animation side by side and exit, for documentation
+    #[arg(long, value_name = "ANIMATION", conflicts_with_all = ["preview", "inspect"])]
+    pub strip: Option<String>,
+    /// Tour all the states on a timer for demos, ignoring input other than Esc
+    #[arg(long, action(ArgAction::SetTrue), default_value("false"), conflicts_with_all = ["preview", "inspect"])]
+    pub attract: bool,
+    /// How long --attract stays in each state
+    #[arg(long, value_name = "SECS", default_value_t = 5.0)]
+    pub attract_interval: f64,
+    /// Draw into a fixed-size region instead of the whole terminal, frames sticking out of it are cut off
+    #[arg(long, value_name = "COLSxROWS")]
+    pub viewport: Option<Viewport>,
+    /// Pan frames too big for the terminal back and forth instead of only showing their top left
+    #[arg(long, action(ArgAction::SetTrue), default_value("false"))]
+    pub scroll: bool,
+    /// Only draw in this rectangle and leave the rest of the terminal as it is, like a viewport at a fixed place
+    #[arg(long, value_name = "X,Y,W,H", conflicts_with = "viewport")]
+    pub region: Option<Region>,
+    /// Where the viewport is in the terminal
+    #[arg(long, value_enum, default_value_t = ViewportAnchor::Center)]
+    pub viewport_anchor: ViewportAnchor,
+    /// How lines are ended when frames are written out, for terminals that don't expect \r\n in raw mode
+    #[arg(long, value_enum, default_value_t = LineEndingChoice::Crlf)]
+    pub line_ending: LineEndingChoice,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Viewport {
+    pub cols: u16,
+    pub rows: u16,
+}
+
+impl FromStr for Viewport {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("'{s}' isn't a size like 80x24");
+
+        let (cols, rows) = s.split_once(['x', 'X']).ok_or_else(invalid)?;
+        let cols: u16 = cols.parse().map_err(|_| invalid())?;
+        let rows: u16 = rows.parse().map_err(|_| invalid())?;
+
+        if cols == 0 || rows == 0 {
+            return Err("The viewport can't be empty".to_string());
+        }
+
+        Ok(Self { cols, rows })
+    }
+}
+
+impl Viewport {
+    // The viewport's origin and size in a terminal of `size`, cut down if
+    // the terminal is smaller
+    pub fn area(&self, size: (u16, u16), anchor: ViewportAnchor) -> ((u16, u16), (u16, u16)) {
+        let area_size = (self.cols.min(size.0), self.rows.min(size.1));
+
+        let origin = match anchor {
+            ViewportAnchor::TopLeft => (0, 0),
+            ViewportAnchor::Center => ((size.0 - area_size.0) / 2, (size.1 - area_size.1) / 2),
+        };
+
+        (origin, area_size)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Region {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl FromStr for Region {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("'{s}' isn't a rectangle like 60,20,20,8");
+
+        let values = s.split(',')
+            .map(|value| value.trim().parse::<u16>().map_err(|_| invalid()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let [x, y, width, height] = values[..] else {
+            return Err(invalid());
+        };
+
+        if width == 0 || height == 0 {
+            return Err("The region can't be empty".to_string());
+        }
+
+        Ok(Self { x, y, width, height })
+    }
+}
+
+impl Region {
+    // The part of the region that's on a terminal of `size`. Empty if the
+    // terminal doesn't reach it.
+    pub fn area(&self, size: (u16, u16)) -> ((u16, u16), (u16, u16)) {
+        let origin = (self.x.min(size.0), self.y.min(size.1));
+        let area_size = (self.width.min(size.0 - origin.0), self.height.min(size.1 - origin.1));
+
+        (origin, area_size)
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum ViewportAnchor {
+    TopLeft,
+    Center,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum Output {
+    Stdout,
+    Stderr,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum ColorChoice {
+    Auto,
+    Truecolor,
+    #[value(name = "256")]
+    Ansi256,
+    #[value(name = "16")]
+    Ansi16,
+    None,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum LineEndingChoice {
+    Lf,
+    Crlf,
 }
@@ -0,0 +1,77 @@
+use std::{collections::BTreeMap, fs, path::{Path, PathBuf}};
+
+use crate::pet::{Error, Pet};
+
+// How much a pet loads, for keeping pets small
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Audit {
+    pub frames: usize,
+    // Of the frame text as loaded, after tabs are expanded
+    pub frame_bytes: usize,
+    // The animation with the most frame text
+    pub largest_animation: Option<AnimationSize>,
+    pub states: usize,
+    // Every Lua file the pet can run and its size, relative to the pet's
+    // directory where they're in it
+    pub scripts: BTreeMap<PathBuf, u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnimationSize {
+    pub name: String,
+    pub frames: usize,
+    pub bytes: usize,
+}
+
+impl Audit {
+    pub fn script_bytes(&self) -> u64 {
+        self.scripts.values().sum()
+    }
+}
+
+pub fn audit_pet(pet: &Pet) -> Result<Audit, Error> {
+    let sizes: Vec<AnimationSize> = pet.animations.values()
+        .map(|anim| AnimationSize {
+            name: anim.name.clone(),
+            frames: anim.frames.len(),
+            bytes: anim.frames.iter().map(String::len).sum(),
+        })
+        .collect();
+
+    // Modules loaded with require count as well as the state scripts, and
+    // states inherited with `extends` live outside the pet's directory
+    let root = pet.path.canonicalize().map_err(Error::IO)?;
+    let mut script_paths = Vec::new();
+    lua_files(&root, &mut script_paths)?;
+    script_paths.extend(pet.states.values().map(|state| state.path.join("state.lua")));
+
+    let mut scripts = BTreeMap::new();
+    for path in script_paths {
+        let path = path.canonicalize().map_err(Error::IO)?;
+        let size = fs::metadata(&path).map_err(Error::IO)?.len();
+        let path = path.strip_prefix(&root).map(Path::to_path_buf).unwrap_or(path);
+        scripts.insert(path, size);
+    }
+
+    Ok(Audit {
+        frames: sizes.iter().map(|size| size.frames).sum(),
+        frame_bytes: sizes.iter().map(|size| size.bytes).sum(),
+        largest_animation: sizes.into_iter().max_by(|a, b| a.bytes.cmp(&b.bytes).then_with(|| b.name.cmp(&a.name))),
+        states: pet.states.len(),
+        scripts,
+    })
+}
+
+fn lua_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), Error> {
+    for entry in fs::read_dir(dir).map_err(Error::IO)? {
+        let path = entry.map_err(Error::IO)?.path();
+
+        if path.is_dir() {
+            lua_files(&path, files)?;
+        } else if path.extension().is_some_and(|ext| ext == "lua") {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,87 @@
+use std::collections::{HashSet, VecDeque};
+
+use mlua::{Lua, Value};
+
+// A Lua value that can be passed between pets. Every pet has its own Lua
+// state, so values are copied out of the sender and into the receiver.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Payload {
+    Nil,
+    Bool(bool),
+    Integer(i64),
+    Number(f64),
+    String(String),
+    Table(Vec<(Payload, Payload)>),
+}
+
+impl Payload {
+    pub fn from_lua(value: Value) -> mlua::Result<Self> {
+        Ok(match value {
+            Value::Nil => Self::Nil,
+            Value::Boolean(b) => Self::Bool(b),
+            Value::Integer(i) => Self::Integer(i),
+            Value::Number(n) => Self::Number(n),
+            Value::String(s) => Self::String(s.to_str()?.to_string()),
+            Value::Table(t) => Self::Table(t.pairs::<Value, Value>()
+                .map(|pair| {
+                    let (k, v) = pair?;
+                    Ok((Self::from_lua(k)?, Self::from_lua(v)?))
+                })
+                .collect::<mlua::Result<_>>()?),
+            other => return Err(mlua::Error::RuntimeError(format!(
+                "a message payload can't contain a {}", other.type_name()))),
+        })
+    }
+
+    pub fn into_lua(self, lua: &Lua) -> mlua::Result<Value<'_>> {
+        Ok(match self {
+            Self::Nil => Value::Nil,
+            Self::Bool(b) => Value::Boolean(b),
+            Self::Integer(i) => Value::Integer(i),
+            Self::Number(n) => Value::Number(n),
+            Self::String(s) => Value::String(lua.create_string(s)?),
+            Self::Table(pairs) => {
+                let table = lua.create_table()?;
+                for (k, v) in pairs {
+                    table.raw_set(k.into_lua(lua)?, v.into_lua(lua)?)?;
+                }
+                Value::Table(table)
+            },
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    pub sender: String,
+    pub target: String,
+    pub event: String,
+    pub payload: Payload,
+}
+
+// Queues messages between the pets running in the same process
+#[derive(Debug, Default)]
+pub struct MessageBus {
+    pets: HashSet<String>,
+    queue: VecDeque<Message>,
+}
+
+impl MessageBus {
+    pub fn register(&mut self, pet: &str) {
+        self.pets.insert(pet.to_string());
+    }
+
+    pub fn has_pet(&self, pet: &str) -> bool {
+        self.pets.contains(pet)
+    }
+
+    pub fn send(&mut self, message: Message) {
+        self.queue.push_back(message);
+    }
+
+    // Takes every queued message, messages sent while handling these are
+    // delivered by the next call
+    pub fn drain(&mut self) -> Vec<Message> {
+        self.queue.drain(..).collect()
+    }
+}
@@ -0,0 +1,163 @@
+use std::io::{self, Write};
+
+use crossterm::{
+    cursor::MoveTo,
+    queue,
+    style::{Color, Print, ResetColor, SetForegroundColor},
+};
+
+use crate::text::{grapheme_width, graphemes};
+
+// The right half of a wide grapheme
+const CONTINUATION: &str = "";
+
+// A grapheme rather than a char, so emoji and accented letters made of
+// several chars stay in one piece
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cell {
+    pub grapheme: String,
+    pub color: Option<Color>,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self { grapheme: " ".to_string(), color: None }
+    }
+}
+
+// A screen buffer the pets are composited into before it's written out in
+// one go, so overlapping pets don't flicker
+#[derive(Debug, Clone)]
+pub struct Canvas {
+    width: u16,
+    height: u16,
+    cells: Vec<Cell>,
+}
+
+impl Canvas {
+    pub fn new(size: (u16, u16)) -> Self {
+        Self {
+            width: size.0,
+            height: size.1,
+            cells: vec![Cell::default(); usize::from(size.0) * usize::from(size.1)],
+        }
+    }
+
+    pub fn size(&self) -> (u16, u16) {
+        (self.width, self.height)
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.fill(Cell::default());
+    }
+
+    fn index(&self, x: u16, y: u16) -> Option<usize> {
+        (x < self.width && y < self.height).then(|| usize::from(y) * usize::from(self.width) + usize::from(x))
+    }
+
+    // Writes a line starting at (x, y), clipped to the canvas. Characters
+    // equal to `transparent` leave the cell below untouched.
+    pub fn put_str(&mut self, x: u16, y: u16, line: &str, color: Option<Color>, transparent: Option<char>) {
+        let mut column = usize::from(x);
+
+        let mut buf = [0; 4];
+        let transparent = transparent.map(|c| &*c.encode_utf8(&mut buf));
+
+        for grapheme in graphemes(line) {
+            let width = grapheme_width(grapheme);
+            if width == 0 {
+                continue;
+            }
+
+            if Some(grapheme) != transparent {
+                if let Ok(column) = u16::try_from(column) {
+                    self.set(column, y, grapheme, width, color);
+                }
+            }
+
+            column += width;
+        }
+    }
+
+    // Blanks a rectangle, clipped to the canvas
+    pub fn clear_rect(&mut self, x: u16, y: u16, width: u16, height: u16) {
+        for row in y..y.saturating_add(height).min(self.height) {
+            for column in x..x.saturating_add(width).min(self.width) {
+                self.set(column, row, " ", 1, None);
+            }
+        }
+    }
+
+    fn set(&mut self, x: u16, y: u16, grapheme: &str, width: usize, color: Option<Color>) {
+        // A wide grapheme cut off by the right edge isn't drawn at all
+        let Some(i) = self.index(x.saturating_add(width as u16 - 1), y).and(self.index(x, y)) else {
+            return;
+        };
+
+        // Don't leave half of a wide grapheme behind
+        if self.cells[i].grapheme == CONTINUATION && x > 0 {
+            self.cells[i - 1].grapheme = " ".to_string();
+        }
+        let end = i + width;
+        if end < self.cells.len() && self.cells[end].grapheme == CONTINUATION && !end.is_multiple_of(usize::from(self.width)) {
+            self.cells[end].grapheme = " ".to_string();
+        }
+
+        self.cells[i] = Cell { grapheme: grapheme.to_string(), color };
+        for cell in &mut self.cells[i + 1..end] {
+            *cell = Cell { grapheme: CONTINUATION.to_string(), color };
+        }
+    }
+
+    pub fn row(&self, y: u16) -> String {
+        self.index(0, y)
+            .map(|start| self.cells[start..start + usize::from(self.width)].iter()
+                .map(|cell| cell.grapheme.as_str())
+                .collect())
+            .unwrap_or_default()
+    }
+
+    // Every row is written in full, which also erases what was there before
+    pub fn write_to(&self, out: &mut impl Write) -> io::Result<()> {
+        self.write_at(out, (0, 0))
+    }
+
+    // Like `write_to`, with the top left corner at `origin` on the screen
+    pub fn write_at(&self, out: &mut impl Write, origin: (u16, u16)) -> io::Result<()> {
+        let mut current_color = None;
+
+        for y in 0..self.height {
+            queue!(out, MoveTo(origin.0, origin.1.saturating_add(y)))?;
+
+            let start = usize::from(y) * usize::from(self.width);
+            let mut run = String::new();
+
+            for cell in &self.cells[start..start + usize::from(self.width)] {
+                if cell.grapheme == CONTINUATION {
+                    continue;
+                }
+
+                if cell.color != current_color {
+                    queue!(out, Print(&run))?;
+                    run.clear();
+
+                    match cell.color {
+                        Some(color) => queue!(out, SetForegroundColor(color))?,
+                        None => queue!(out, ResetColor)?,
+                    }
+                    current_color = cell.color;
+                }
+
+                run.push_str(&cell.grapheme);
+            }
+
+            queue!(out, Print(&run))?;
+        }
+
+        if current_color.is_some() {
+            queue!(out, ResetColor)?;
+        }
+
+        out.flush()
+    }
+}
@@ -0,0 +1,57 @@
+use std::{
+    io::{self, Write},
+    time::Instant,
+};
+
+use serde_json::json;
+
+use crate::runtime::unix_time;
+
+// Passes what's drawn on to `out` and records it in `cast` as an asciinema
+// v2 recording: a header line, then one event line per flush with the time
+// since the recording started.
+pub struct CastRecorder<W: Write, C: Write> {
+    out: W,
+    cast: C,
+    started: Instant,
+    // Written since the last flush
+    pending: Vec<u8>,
+}
+
+impl<W: Write, C: Write> CastRecorder<W, C> {
+    pub fn new(out: W, mut cast: C, size: (u16, u16)) -> io::Result<Self> {
+        let header = json!({
+            "version": 2,
+            "width": size.0,
+            "height": size.1,
+            "timestamp": unix_time(),
+        });
+        writeln!(cast, "{header}")?;
+
+        Ok(Self { out, cast, started: Instant::now(), pending: Vec::new() })
+    }
+}
+
+impl<W: Write, C: Write> Write for CastRecorder<W, C> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.out.write(buf)?;
+        self.pending.extend_from_slice(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()?;
+
+        if !self.pending.is_empty() {
+            let event = json!([
+                self.started.elapsed().as_secs_f64(),
+                "o",
+                String::from_utf8_lossy(&self.pending),
+            ]);
+            writeln!(self.cast, "{event}")?;
+            self.pending.clear();
+        }
+
+        self.cast.flush()
+    }
+}
@@ -0,0 +1,25 @@
+// The system clipboard for get_clipboard and set_clipboard. Without the
+// clipboard feature, or without a clipboard to talk to (like over SSH),
+// there's nothing to read and writing fails.
+
+#[cfg(feature = "clipboard")]
+pub fn get() -> Option<String> {
+    arboard::Clipboard::new().ok()?.get_text().ok()
+}
+
+#[cfg(feature = "clipboard")]
+pub fn set(text: String) -> Result<(), String> {
+    arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.set_text(text))
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(not(feature = "clipboard"))]
+pub fn get() -> Option<String> {
+    None
+}
+
+#[cfg(not(feature = "clipboard"))]
+pub fn set(_text: String) -> Result<(), String> {
+    Err("a_duk was built without the clipboard feature".to_string())
+}
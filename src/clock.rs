@@ -0,0 +1,54 @@
+use std::time::{Duration, Instant};
+
+// Where the main loop gets the time it passes to `PetRuntime::tick`
+pub trait Clock {
+    fn now(&self) -> Instant;
+
+    // Called once per loop iteration with the time the loop slept for
+    fn advance(&mut self, by: Duration);
+}
+
+// The wall clock, which moves on its own
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn advance(&mut self, _by: Duration) {}
+}
+
+// A clock that only moves when it's advanced, so the time doesn't depend on
+// how long anything took. Used by tests and --deterministic.
+#[derive(Debug, Clone, Copy)]
+pub struct MockClock {
+    now: Instant,
+}
+
+impl MockClock {
+    pub fn new() -> Self {
+        Self::starting_at(Instant::now())
+    }
+
+    pub fn starting_at(now: Instant) -> Self {
+        Self { now }
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.now
+    }
+
+    fn advance(&mut self, by: Duration) {
+        self.now += by;
+    }
+}
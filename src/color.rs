@@ -0,0 +1,128 @@
+use crossterm::style::Color;
+
+// How many colors the terminal can show
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorDepth {
+    TrueColor,
+    Ansi256,
+    Ansi16,
+    None,
+}
+
+impl ColorDepth {
+    pub fn detect() -> Self {
+        if std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+            return Self::None;
+        }
+
+        Self::from_env(
+            std::env::var("COLORTERM").ok().as_deref(),
+            std::env::var("TERM").ok().as_deref(),
+        )
+    }
+
+    // Guesses from the values of COLORTERM and TERM
+    pub fn from_env(colorterm: Option<&str>, term: Option<&str>) -> Self {
+        if matches!(colorterm, Some("truecolor" | "24bit")) {
+            return Self::TrueColor;
+        }
+
+        match term {
+            None | Some("" | "dumb") => Self::None,
+            Some(term) if term.contains("truecolor") || term.contains("24bit") || term.contains("direct") => Self::TrueColor,
+            Some(term) if term.contains("256color") => Self::Ansi256,
+            _ => Self::Ansi16,
+        }
+    }
+
+    // As it's given to --color
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::TrueColor => "truecolor",
+            Self::Ansi256 => "256",
+            Self::Ansi16 => "16",
+            Self::None => "none",
+        }
+    }
+
+    // The closest color the terminal can show, `None` if it can't show any
+    pub fn convert(self, rgb: (u8, u8, u8)) -> Option<Color> {
+        let (r, g, b) = rgb;
+
+        match self {
+            Self::TrueColor => Some(Color::Rgb { r, g, b }),
+            Self::Ansi256 => Some(Color::AnsiValue(to_ansi256(rgb))),
+            Self::Ansi16 => Some(to_ansi16(rgb)),
+            Self::None => None,
+        }
+    }
+}
+
+// Parses "#rrggbb"
+pub fn parse_hex(hex: &str) -> Option<(u8, u8, u8)> {
+    let digits = hex.strip_prefix('#')?;
+    if digits.len() != 6 || !digits.is_ascii() {
+        return None;
+    }
+
+    let channel = |i: usize| u8::from_str_radix(&digits[i..i + 2], 16).ok();
+    Some((channel(0)?, channel(2)?, channel(4)?))
+}
+
+// The levels of the 6x6x6 color cube
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+fn nearest_level(channel: u8) -> usize {
+    (0..CUBE_LEVELS.len())
+        .min_by_key(|&i| CUBE_LEVELS[i].abs_diff(channel))
+        .unwrap_or(0)
+}
+
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let d = |x: u8, y: u8| u32::from(x.abs_diff(y)).pow(2);
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+// Picks between the closest cube color and the closest gray
+pub fn to_ansi256(rgb: (u8, u8, u8)) -> u8 {
+    let (r, g, b) = (nearest_level(rgb.0), nearest_level(rgb.1), nearest_level(rgb.2));
+    let cube = (CUBE_LEVELS[r], CUBE_LEVELS[g], CUBE_LEVELS[b]);
+    let cube_index = 16 + 36 * r + 6 * g + b;
+
+    // Grays 232-255 go from 8 to 238 in steps of 10
+    let average = (u16::from(rgb.0) + u16::from(rgb.1) + u16::from(rgb.2)) / 3;
+    let gray_step = (average.saturating_sub(3) / 10).min(23) as u8;
+    let gray_level = 8 + gray_step * 10;
+
+    if distance(rgb, (gray_level, gray_level, gray_level)) < distance(rgb, cube) {
+        232 + gray_step
+    } else {
+        cube_index as u8
+    }
+}
+
+const ANSI16: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::DarkRed, (128, 0, 0)),
+    (Color::DarkGreen, (0, 128, 0)),
+    (Color::DarkYellow, (128, 128, 0)),
+    (Color::DarkBlue, (0, 0, 128)),
+    (Color::DarkMagenta, (128, 0, 128)),
+    (Color::DarkCyan, (0, 128, 128)),
+    (Color::Grey, (192, 192, 192)),
+    (Color::DarkGrey, (128, 128, 128)),
+    (Color::Red, (255, 0, 0)),
+    (Color::Green, (0, 255, 0)),
+    (Color::Yellow, (255, 255, 0)),
+    (Color::Blue, (0, 0, 255)),
+    (Color::Magenta, (255, 0, 255)),
+    (Color::Cyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+pub fn to_ansi16(rgb: (u8, u8, u8)) -> Color {
+    ANSI16.iter()
+        .min_by_key(|(_, value)| distance(rgb, *value))
+        .map(|(color, _)| *color)
+        .unwrap_or(Color::Reset)
+}
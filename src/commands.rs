@@ -0,0 +1,226 @@
+use std::{fs, path::{Path, PathBuf}};
+
+use a_duk::{audit, embedded, export, index::{indexed_pets, PetIndex}, lint::lint_pet, pet::{is_inside, is_valid_pet_name, LoadOptions, Pet}};
+use directories::BaseDirs;
+use mlua::Lua;
+
+use crate::{args::ExportFormat, exit::{ExitCode, Failure}};
+
+fn check_pet_name(name: &str) -> Result<(), Failure> {
+    if !is_valid_pet_name(name) {
+        return Err(Failure::new(ExitCode::Other,
+            format!("'{name}' isn't a valid pet name, it has to be the name of a folder in the pets directory")));
+    }
+
+    Ok(())
+}
+
+pub fn pet_dir(pets_dir: &Path, name: &str) -> Result<PathBuf, Failure> {
+    check_pet_name(name)?;
+    let path = pets_dir.join(name);
+
+    if !path.is_dir() {
+        return Err(Failure::new(ExitCode::PetNotFound,
+            format!("The pet '{}' doesn't exist in {}", name, pets_dir.display())));
+    }
+
+    if !is_inside(pets_dir, &path) {
+        return Err(Failure::new(ExitCode::Other,
+            format!("The pet '{}' is outside of {}", name, pets_dir.display())));
+    }
+
+    Ok(path)
+}
+
+// Like `pet_dir`, but a missing duk is replaced with the built-in one
+pub fn pet_dir_or_embedded(pets_dir: &Path, name: &str) -> Result<PathBuf, Failure> {
+    match pet_dir(pets_dir, name) {
+        Err(failure) if name == embedded::NAME => {
+            // Not the temp dir, other users could put their own scripts there
+            let embedded_dir = BaseDirs::new()
+                .map(|dirs| dirs.cache_dir().join("a_duk").join("embedded"))
+                .ok_or_else(|| Failure::new(ExitCode::Other, "BaseDirs couldn't be instantiated"))?;
+            let path = embedded::extract(&embedded_dir)
+                .map_err(|e| Failure::new(ExitCode::Other, format!("The built-in pet couldn't be extracted: {e}")))?;
+
+            eprintln!("The pet '{name}' isn't installed in {}, using the built-in one", pets_dir.display());
+            log::info!("{}", failure.message);
+            Ok(path)
+        },
+        result => result,
+    }
+}
+
+// The files of a freshly created pet, relative to its directory
+const SCAFFOLD: [(&str, &str); 5] = [
+    ("anim/idle/meta.toml", "\
+# Delay between frames in milliseconds
+delay = 500
+"),
+    ("anim/idle/0.txt", "\
+ ('v')
+ /| |\\
+"),
+    ("anim/idle/1.txt", "\
+ ('-')
+ /| |\\
+"),
+    ("state/idle/meta.toml", "\
+# The default animation
+animation = \"idle\"
+# The delay between updates in milliseconds
+update_delay = 100
+"),
+    ("state/idle/state.lua", "\
+-- The pet has entered the state.
+function Init()
+end
+
+-- A tick in the state, return the name of a state to go to it.
+function Update()
+end
+"),
+];
+
+fn pet_metadata(name: &str) -> String {
+    format!("\
+# The name of the pet
+name = {name:?}
+# The description of the pet
+description = \"A new pet.\"
+# The state the pet will be in at the start
+default_state = \"idle\"
+# The delay in the main loop in milliseconds
+global_tick_delay = 50
+")
+}
+
+pub fn new_pet(pets_dir: &Path, name: &str) -> Result<(), Failure> {
+    let io_failure = |e: std::io::Error| Failure::new(ExitCode::Other,
+        format!("Creating the pet failed: {e}"));
+
+    check_pet_name(name)?;
+    fs::create_dir_all(pets_dir).map_err(io_failure)?;
+
+    let path = pets_dir.join(name);
+    if path.exists() {
+        return Err(Failure::new(ExitCode::Other,
+            format!("The pet '{}' already exists in {}", name, pets_dir.display())));
+    }
+
+    fs::create_dir(&path).map_err(io_failure)?;
+    fs::write(path.join("meta.toml"), pet_metadata(name)).map_err(io_failure)?;
+
+    for (file, contents) in SCAFFOLD {
+        let file_path = path.join(file);
+        if let Some(parent) = file_path.parent() {
+            fs::create_dir_all(parent).map_err(io_failure)?;
+        }
+
+        fs::write(file_path, contents).map_err(io_failure)?;
+    }
+
+    println!("Created {}", path.display());
+    println!("Run it with: a_duk --pet {name}");
+
+    Ok(())
+}
+
+pub fn list_pets(pets_dir: &Path, index_path: &Path) -> Result<(), Failure> {
+    let pets = indexed_pets(pets_dir, load_index(index_path).as_ref());
+    if pets.is_empty() {
+        println!("No pets installed in {}", pets_dir.display());
+        return Ok(());
+    }
+
+    for (dir_name, summary) in pets {
+        match summary {
+            Ok(summary) => println!("{dir_name}: {} - {}", summary.name, summary.description),
+            Err(e) => println!("{dir_name}: (invalid: {e})"),
+        }
+    }
+
+    Ok(())
+}
+
+pub fn index_pets(pets_dir: &Path, index_path: &Path) -> Result<(), Failure> {
+    let index = PetIndex::build(pets_dir);
+    index.write(index_path)
+        .map_err(|e| Failure::new(ExitCode::Other, format!("Writing {} failed: {e}", index_path.display())))?;
+
+    println!("Indexed {} pets in {}", index.pets.len(), pets_dir.display());
+
+    Ok(())
+}
+
+// A broken index is only a missed shortcut, the pets are read instead
+pub fn load_index(index_path: &Path) -> Option<PetIndex> {
+    PetIndex::load(index_path)
+        .inspect_err(|e| log::warn!("Ignoring the pet index {}: {e}", index_path.display()))
+        .ok()
+        .flatten()
+}
+
+pub fn validate_pet(pets_dir: &Path, name: &str, strict: bool) -> Result<(), Failure> {
+    let path = pet_dir(pets_dir, name)?;
+    let lua = Lua::new();
+
+    // Including the states that only --debug loads
+    let pet = Pet::load_with_options(&lua, &path, LoadOptions { debug: true, strict, ..LoadOptions::default() }, |_, _| {})
+        .map_err(|e| Failure::pet(e, "The pet is invalid"))?;
+
+    let warnings = lint_pet(&pet);
+    for warning in &warnings {
+        eprintln!("warning: {warning}");
+    }
+
+    println!("{} is valid: {} animations, {} states, {} warnings",
+        name, pet.animations.len(), pet.states.len(), warnings.len());
+
+    Ok(())
+}
+
+pub fn audit_pet(pets_dir: &Path, name: &str) -> Result<(), Failure> {
+    let path = pet_dir_or_embedded(pets_dir, name)?;
+    let lua = Lua::new();
+
+    let pet = Pet::load(&lua, &path)
+        .map_err(|e| Failure::pet(e, &format!("Loading the pet '{name}' failed")))?;
+    let audit = audit::audit_pet(&pet)
+        .map_err(|e| Failure::pet(e, "Reading the pet's scripts failed"))?;
+
+    println!("{:<20}{}", "frames", audit.frames);
+    println!("{:<20}{} bytes", "frame text", audit.frame_bytes);
+    if let Some(largest) = &audit.largest_animation {
+        println!("{:<20}{}, {} frames, {} bytes", "largest animation", largest.name, largest.frames, largest.bytes);
+    }
+    println!("{:<20}{}", "states", audit.states);
+    println!("{:<20}{} bytes in {} files", "lua scripts", audit.script_bytes(), audit.scripts.len());
+    for (script, size) in &audit.scripts {
+        println!("  {}  {size} bytes", script.display());
+    }
+
+    Ok(())
+}
+
+pub fn export_animation(pets_dir: &Path, name: &str, anim_name: &str, format: ExportFormat, output: Option<PathBuf>) -> Result<(), Failure> {
+    let path = pet_dir_or_embedded(pets_dir, name)?;
+    let lua = Lua::new();
+
+    let pet = Pet::load(&lua, &path)
+        .map_err(|e| Failure::pet(e, &format!("Loading the pet '{name}' failed")))?;
+    let anim = pet.animations.get(anim_name)
+        .ok_or_else(|| Failure::new(ExitCode::InvalidPet, format!("The animation '{anim_name}' doesn't exist")))?;
+
+    let (contents, extension) = match format {
+        ExportFormat::Html => (export::animation_html(anim, &format!("{} - {anim_name}", pet.metadata.name)), "html"),
+    };
+
+    let output = output.unwrap_or_else(|| PathBuf::from(format!("{name}-{anim_name}.{extension}")));
+    fs::write(&output, contents)
+        .map_err(|e| Failure::new(ExitCode::Other, format!("Writing {} failed: {e}", output.display())))?;
+
+    println!("Exported {anim_name} to {}", output.display());
+
+    Ok(())
+}
@@ -0,0 +1,57 @@
+use crossterm::cursor::SetCursorStyle;
+
+// The terminal's own cursor, which scripts can show as part of the pet. It's
+// in the same coordinates as the overlay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PetCursor {
+    pub x: u16,
+    pub y: u16,
+    pub shape: CursorShape,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CursorShape {
+    #[default]
+    SteadyBlock,
+    BlinkingBlock,
+    SteadyUnderline,
+    BlinkingUnderline,
+    SteadyBar,
+    BlinkingBar,
+}
+
+impl CursorShape {
+    const NAMES: [(&'static str, Self); 6] = [
+        ("steady_block", Self::SteadyBlock),
+        ("blinking_block", Self::BlinkingBlock),
+        ("steady_underline", Self::SteadyUnderline),
+        ("blinking_underline", Self::BlinkingUnderline),
+        ("steady_bar", Self::SteadyBar),
+        ("blinking_bar", Self::BlinkingBar),
+    ];
+
+    // Names like "blinking_bar", a bare "bar" is steady
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = match name {
+            "block" | "underline" | "bar" => format!("steady_{name}"),
+            name => name.to_string(),
+        };
+
+        Self::NAMES.iter().find(|(n, _)| *n == name).map(|(_, shape)| *shape)
+    }
+
+    pub fn name(self) -> &'static str {
+        Self::NAMES.iter().find(|(_, shape)| *shape == self).map_or("steady_block", |(name, _)| name)
+    }
+
+    pub fn command(self) -> SetCursorStyle {
+        match self {
+            Self::SteadyBlock => SetCursorStyle::SteadyBlock,
+            Self::BlinkingBlock => SetCursorStyle::BlinkingBlock,
+            Self::SteadyUnderline => SetCursorStyle::SteadyUnderScore,
+            Self::BlinkingUnderline => SetCursorStyle::BlinkingUnderScore,
+            Self::SteadyBar => SetCursorStyle::SteadyBar,
+            Self::BlinkingBar => SetCursorStyle::BlinkingBar,
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use std::{cell::RefCell, path::Path};
+
+use mlua::Lua;
+
+use crate::pet::{Error, LoadOptions, Pet};
+
+// A pet loaded by a script while another pet runs, with a Lua state of its
+// own. The Lua functions that load it can only keep 'static values, so the
+// state is leaked for the pet to borrow and freed again once the pet is
+// dropped, or by `DetachedLuas` once the pet is attached.
+#[derive(Debug)]
+pub struct DetachedPet {
+    // Only taken by `DetachedLuas::attach`
+    pet: Option<Pet<'static>>,
+}
+
+impl DetachedPet {
+    pub fn load(path: &Path, options: LoadOptions) -> Result<Self, Error> {
+        let lua = Lua::new().into_static();
+
+        match Pet::load_with_options(lua, path, options, |_, _| {}) {
+            Ok(pet) => Ok(Self { pet: Some(pet) }),
+            Err(e) => {
+                // SAFETY: the pet failed to load, so nothing borrows the state
+                drop(unsafe { Lua::from_static(lua) });
+                Err(e)
+            },
+        }
+    }
+
+    pub fn pet(&self) -> &Pet<'_> {
+        self.pet.as_ref().expect("a detached pet is only emptied by attaching it")
+    }
+}
+
+impl Drop for DetachedPet {
+    fn drop(&mut self) {
+        if let Some(pet) = self.pet.take() {
+            let lua = pet.lua;
+            drop(pet);
+            // SAFETY: the pet was the only thing borrowing the state, and
+            // `pet()` doesn't lend anything out for longer than `self`
+            drop(unsafe { Lua::from_static(lua) });
+        }
+    }
+}
+
+// Keeps the Lua states of the detached pets that were started, freeing them
+// when dropped. The attached pets borrow it, so they can't outlive it.
+#[derive(Debug, Default)]
+pub struct DetachedLuas {
+    luas: RefCell<Vec<&'static Lua>>,
+}
+
+impl DetachedLuas {
+    pub fn attach(&self, mut detached: DetachedPet) -> Pet<'_> {
+        let pet = detached.pet.take().expect("a detached pet is only emptied by attaching it");
+        self.luas.borrow_mut().push(pet.lua);
+        pet
+    }
+}
+
+impl Drop for DetachedLuas {
+    fn drop(&mut self) {
+        for lua in self.luas.get_mut().drain(..) {
+            // SAFETY: everything borrowing the state came from `attach`,
+            // which ties it to `self`, so it's gone by now
+            drop(unsafe { Lua::from_static(lua) });
+        }
+    }
+}
@@ -0,0 +1,37 @@
+use std::{
+    fs,
+    io,
+    path::{Path, PathBuf},
+};
+
+// The name of the pet that's built into the binary
+pub const NAME: &str = "duk";
+
+// Its files, relative to the pet directory
+const FILES: [(&str, &str); 8] = [
+    ("meta.toml", include_str!("../assets/pets/duk/meta.toml")),
+    ("anim/idle/meta.toml", include_str!("../assets/pets/duk/anim/idle/meta.toml")),
+    ("anim/idle/0.txt", include_str!("../assets/pets/duk/anim/idle/0.txt")),
+    ("anim/quacking/meta.toml", include_str!("../assets/pets/duk/anim/quacking/meta.toml")),
+    ("anim/quacking/0.txt", include_str!("../assets/pets/duk/anim/quacking/0.txt")),
+    ("anim/quacking/1.txt", include_str!("../assets/pets/duk/anim/quacking/1.txt")),
+    ("state/idle/meta.toml", include_str!("../assets/pets/duk/state/idle/meta.toml")),
+    ("state/idle/state.lua", include_str!("../assets/pets/duk/state/idle/state.lua")),
+];
+
+// Writes the built-in pet to `dir`/duk so it loads like any other pet.
+// Returns the pet's directory.
+pub fn extract(dir: &Path) -> io::Result<PathBuf> {
+    let pet_dir = dir.join(NAME);
+
+    for (file, contents) in FILES {
+        let path = pet_dir.join(file);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::write(path, contents)?;
+    }
+
+    Ok(pet_dir)
+}
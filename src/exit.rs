@@ -0,0 +1,61 @@
+use std::{fmt::Display, io, process};
+
+use a_duk::pet;
+
+// Keep in sync with EXIT_CODES_HELP
+#[derive(Debug, Clone, Copy)]
+pub enum ExitCode {
+    Other = 1,
+    PetNotFound = 2,
+    InvalidPet = 3,
+    Lua = 4,
+    Terminal = 5,
+}
+
+pub const EXIT_CODES_HELP: &str = "\
+Exit codes:
+  0  Success
+  1  Other error
+  2  The pet couldn't be found
+  3  The pet's files are invalid
+  4  The pet's Lua code failed
+  5  The terminal couldn't be set up or written to";
+
+pub struct Failure {
+    pub code: ExitCode,
+    pub message: String,
+}
+
+impl Failure {
+    pub fn new(code: ExitCode, message: impl Display) -> Self {
+        Self { code, message: message.to_string() }
+    }
+
+    pub fn terminal(e: io::Error) -> Self {
+        Self::new(ExitCode::Terminal, format!("Terminal error: {e}"))
+    }
+
+    pub fn pet(e: pet::Error, context: &str) -> Self {
+        let code = match &e {
+            pet::Error::IO(_) => ExitCode::Other,
+            pet::Error::TomlSerializer(_) => ExitCode::Other,
+            pet::Error::TomlDeserializer(_)
+                | pet::Error::JsonDeserializer(_)
+                | pet::Error::Utf8(_)
+                | pet::Error::InvalidFileName
+                | pet::Error::InvalidObject(_)
+                | pet::Error::MissingMetadata(..)
+                | pet::Error::NoFrames(..)
+                | pet::Error::FrameGap(..)
+                | pet::Error::TooManyFrameBytes(..) => ExitCode::InvalidPet,
+            pet::Error::Lua(_) => ExitCode::Lua,
+        };
+
+        Self::new(code, format!("{context}: {e}"))
+    }
+
+    pub fn exit(self) -> ! {
+        eprintln!("Error: {}", self.message);
+        process::exit(self.code as i32)
+    }
+}
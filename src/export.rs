@@ -0,0 +1,66 @@
+use std::fmt::Write;
+
+use crate::pet::Animation;
+
+// Frames are drawn in the animation's color on a dark page, like in most
+// terminals
+const DEFAULT_COLOR: &str = "#dddddd";
+
+// Turns an animation into a standalone HTML page that plays it. Every frame
+// is a <pre> stacked on the others, and one CSS animation shows them in turn:
+// each is visible for its share of the cycle, started that far into it.
+pub fn animation_html(anim: &Animation, title: &str) -> String {
+    let count = anim.frames.len();
+    // A zero delay would stop the CSS animation altogether
+    let delay = anim.metadata.delay.max(1);
+    let cycle = delay * count as u64;
+    let color = anim.color
+        .map(|(r, g, b)| format!("#{r:02x}{g:02x}{b:02x}"))
+        .unwrap_or_else(|| DEFAULT_COLOR.to_string());
+
+    let mut html = format!("\
+<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>{title}</title>
+<style>
+body {{ background: #111111; color: {color}; }}
+.pet {{ display: grid; }}
+.pet pre {{ grid-area: 1 / 1; margin: 0; font-family: monospace; visibility: hidden; animation: frame {cycle}ms step-end infinite; }}
+@keyframes frame {{ 0% {{ visibility: visible; }} {share:.4}% {{ visibility: hidden; }} }}
+</style>
+</head>
+<body>
+<div class=\"pet\">
+",
+        title = escape_html(title),
+        share = 100.0 / count as f64,
+    );
+
+    for (i, frame) in anim.frames.iter().enumerate() {
+        // Negative, so every frame is in its place of the cycle from the start
+        let start = (delay * i as u64) as i64 - cycle as i64;
+        let _ = writeln!(html, "<pre style=\"animation-delay: {start}ms\">{}</pre>", escape_html(frame));
+    }
+
+    html.push_str("</div>\n</body>\n</html>\n");
+    html
+}
+
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
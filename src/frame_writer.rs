@@ -0,0 +1,32 @@
+use std::io::{self, Write};
+
+// In raw mode a bare \n moves down without going back to the first column,
+// so frames come out as a staircase unless lines end in \r\n
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineEnding {
+    Lf,
+    #[default]
+    CrLf,
+}
+
+impl LineEnding {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Lf => "\n",
+            Self::CrLf => "\r\n",
+        }
+    }
+}
+
+// Writes lines starting where the cursor is. The last line isn't ended, so
+// a frame on the bottom row doesn't scroll the screen.
+pub fn write_lines<S: AsRef<str>>(out: &mut impl Write, lines: impl IntoIterator<Item = S>, ending: LineEnding) -> io::Result<()> {
+    for (i, line) in lines.into_iter().enumerate() {
+        if i > 0 {
+            out.write_all(ending.as_str().as_bytes())?;
+        }
+        out.write_all(line.as_ref().as_bytes())?;
+    }
+
+    Ok(())
+}
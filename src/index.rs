@@ -0,0 +1,108 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::pet::{metadata_path, pet_dirs, Error, PetMetadata};
+
+// What listing a pet shows
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PetSummary {
+    pub name: String,
+    pub description: String,
+}
+
+impl From<PetMetadata> for PetSummary {
+    fn from(metadata: PetMetadata) -> Self {
+        Self { name: metadata.name, description: metadata.description }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct IndexEntry {
+    #[serde(flatten)]
+    pub summary: PetSummary,
+    // When the pet's metadata file was last changed, in milliseconds since
+    // the Unix epoch
+    pub modified: u64,
+}
+
+// A cache of the installed pets' summaries, so listing them doesn't parse
+// every pet's metadata. Written by `a_duk index`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PetIndex {
+    // The index is only used for the directory it was made from
+    pub pets_dir: PathBuf,
+    #[serde(default)]
+    pub pets: BTreeMap<String, IndexEntry>,
+}
+
+impl PetIndex {
+    // Pets that can't be read are left out, they're read again every time
+    pub fn build(pets_dir: &Path) -> Self {
+        let pets = pet_dirs(pets_dir)
+            .into_iter()
+            .filter_map(|(dir_name, path)| {
+                let metadata_file = metadata_path(&path);
+                let modified = modified(&metadata_file)?;
+                let summary = PetMetadata::load(metadata_file).ok()?.into();
+
+                Some((dir_name, IndexEntry { summary, modified }))
+            })
+            .collect();
+
+        Self { pets_dir: absolute(pets_dir), pets }
+    }
+
+    // None if there's no index yet
+    pub fn load(path: &Path) -> Result<Option<Self>, Error> {
+        match fs::read_to_string(path) {
+            Ok(toml_string) => toml::de::from_str(&toml_string).map(Some).map_err(Error::TomlDeserializer),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(Error::IO(e)),
+        }
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), Error> {
+        let toml_string = toml::to_string(self).map_err(Error::TomlSerializer)?;
+        fs::write(path, toml_string).map_err(Error::IO)
+    }
+}
+
+// Like `installed_pets`, but taking what it can from `index`. A pet whose
+// metadata changed since the index was made is read again, as are pets the
+// index doesn't know about.
+pub fn indexed_pets(pets_dir: &Path, index: Option<&PetIndex>) -> Vec<(String, Result<PetSummary, Error>)> {
+    let index = index.filter(|index| index.pets_dir == absolute(pets_dir));
+
+    pet_dirs(pets_dir)
+        .into_iter()
+        .map(|(dir_name, path)| {
+            let metadata_file = metadata_path(&path);
+            let cached = index
+                .and_then(|index| index.pets.get(&dir_name))
+                .filter(|entry| modified(&metadata_file) == Some(entry.modified));
+
+            let summary = match cached {
+                Some(entry) => Ok(entry.summary.clone()),
+                None => PetMetadata::load(metadata_file).map(PetSummary::from),
+            };
+            (dir_name, summary)
+        })
+        .collect()
+}
+
+// So the same directory given relative to another working directory matches
+fn absolute(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn modified(path: &Path) -> Option<u64> {
+    let modified = fs::metadata(path).and_then(|metadata| metadata.modified()).ok()?;
+    modified.duration_since(UNIX_EPOCH).ok().map(|since| since.as_millis() as u64)
+}
@@ -0,0 +1,27 @@
+pub mod audit;
+pub mod bus;
+pub mod canvas;
+pub mod cast;
+mod clipboard;
+pub mod clock;
+pub mod color;
+pub mod cursor;
+pub mod detached;
+pub mod embedded;
+pub mod export;
+pub mod frame_writer;
+pub mod index;
+pub mod lint;
+mod lua_api;
+pub mod overlay;
+pub mod pet;
+pub mod preview;
+mod require;
+pub mod runtime;
+pub mod save;
+pub mod sound;
+pub mod stats;
+pub mod text;
+pub mod tween;
+
+pub use runtime::PetRuntime;
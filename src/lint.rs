@@ -0,0 +1,99 @@
+use std::{fmt::Display, fs, path::PathBuf};
+
+use crate::pet::Pet;
+
+// Mistakes in a pet that don't stop it from loading but will show up once
+// it runs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    pub file: PathBuf,
+    // Counted from 1, None for the file as a whole
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{line}: {}", self.file.display(), self.message),
+            None => write!(f, "{}: {}", self.file.display(), self.message),
+        }
+    }
+}
+
+// Goes through the scripts of every state looking for calls with a literal
+// state or animation name that doesn't exist. It only reads the source, so
+// names built at runtime aren't checked.
+pub fn lint_pet(pet: &Pet) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+
+    let mut states: Vec<_> = pet.states.iter().collect();
+    states.sort_by_key(|(name, _)| *name);
+
+    for (_, state) in states {
+        let script_path = state.path.join("state.lua");
+
+        if state.metadata.update_delay > 0 && state.event_handlers.update.is_none() {
+            warnings.push(Warning {
+                file: state.path.join("meta.toml"),
+                line: None,
+                message: "update_delay is set but the state has no Update function".to_string(),
+            });
+        }
+
+        let Ok(script) = fs::read_to_string(&script_path) else {
+            continue;
+        };
+
+        for (i, line) in script.lines().enumerate() {
+            for name in literal_arguments(line, "set_current_state") {
+                if !pet.states.contains_key(name) {
+                    warnings.push(Warning {
+                        file: script_path.clone(),
+                        line: Some(i + 1),
+                        message: format!("set_current_state: unknown state '{name}'"),
+                    });
+                }
+            }
+
+            for name in literal_arguments(line, "set_current_anim") {
+                if !pet.animations.contains_key(name) {
+                    warnings.push(Warning {
+                        file: script_path.clone(),
+                        line: Some(i + 1),
+                        message: format!("set_current_anim: unknown animation '{name}'"),
+                    });
+                }
+            }
+        }
+    }
+
+    warnings
+}
+
+// The string literals `function` is called with on a line, as in
+// `function("name")`, `function 'name'` and so on. Comments are skipped.
+fn literal_arguments<'a>(line: &'a str, function: &str) -> Vec<&'a str> {
+    let code = line.split("--").next().unwrap_or_default();
+    let mut names = Vec::new();
+
+    for (start, _) in code.match_indices(function) {
+        // Part of a longer name
+        let before = code[..start].chars().next_back();
+        if before.is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '.' || c == ':') {
+            continue;
+        }
+
+        let rest = code[start + function.len()..].trim_start();
+        let rest = rest.strip_prefix('(').unwrap_or(rest).trim_start();
+
+        let Some(quote) = rest.chars().next().filter(|c| *c == '"' || *c == '\'') else {
+            continue;
+        };
+        if let Some(end) = rest[1..].find(quote) {
+            names.push(&rest[1..1 + end]);
+        }
+    }
+
+    names
+}
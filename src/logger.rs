@@ -0,0 +1,45 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::Path,
+    sync::Mutex,
+};
+
+use log::{LevelFilter, Log, Metadata, Record};
+
+// Appends log records to a file, the terminal is taken by the pet
+struct FileLogger {
+    file: Mutex<File>,
+}
+
+impl Log for FileLogger {
+    fn enabled(&self, _: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        if let Ok(mut file) = self.file.lock() {
+            let _ = writeln!(file, "[{}] {}: {}", record.level(), record.target(), record.args());
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut file) = self.file.lock() {
+            let _ = file.flush();
+        }
+    }
+}
+
+pub fn init(path: &Path) -> Result<(), String> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| e.to_string())?;
+
+    log::set_boxed_logger(Box::new(FileLogger { file: Mutex::new(file) }))
+        .map_err(|e| e.to_string())?;
+    log::set_max_level(LevelFilter::Debug);
+
+    Ok(())
+}
@@ -0,0 +1,935 @@
+use std::{cell::{Ref, RefCell, RefMut}, rc::Rc, time::Duration};
+
+use crossterm::terminal;
+use mlua::{AnyUserData, FromLuaMulti, Function, IntoLuaMulti, Lua, MultiValue, Table, UserData, UserDataMethods, Value};
+use rand::{distributions::WeightedIndex, Rng};
+use rand_distr::{Distribution, Normal};
+
+use crate::{bus::{Message, Payload}, clipboard, cursor::{CursorShape, PetCursor}, detached::DetachedPet, overlay::Overlay, pet::{is_inside, is_valid_pet_name, Anchor}, runtime::{clamp_speed, unix_time, RuntimeState, Speech, Track, KEY_HOLD_TIMEOUT, SPEECH_DURATION, SPEECH_QUEUE_SIZE}, save::StoreValue, sound::Sounds, stats::{Crossing, Stat, Thresholds}, tween::{Easing, Tween}};
+
+// A reference to one of the pet's animations, looked up on use
+struct AnimationHandle {
+    name: String,
+    shared: Rc<RefCell<RuntimeState>>,
+}
+
+impl UserData for AnimationHandle {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("name", |_, this, ()| Ok(this.name.clone()));
+
+        methods.add_method("frame_count", |_, this, ()| {
+            Ok(this.shared.borrow().animations.get(&this.name).map(|a| a.frames.len()))
+        });
+
+        methods.add_method("delay", |_, this, ()| {
+            Ok(this.shared.borrow().animations.get(&this.name).map(|a| a.metadata.delay))
+        });
+
+        // The frame being shown, nil if this isn't the current animation
+        methods.add_method("current_frame", |_, this, ()| {
+            let shared = this.shared.borrow();
+            Ok((shared.current_anim == this.name).then_some(shared.current_frame))
+        });
+
+        methods.add_method("is_current", |_, this, ()| {
+            Ok(this.shared.borrow().current_anim == this.name)
+        });
+    }
+}
+
+// A tween started by `tween`, it moves along with the pet's clock
+struct TweenHandle {
+    tween: Tween,
+    shared: Rc<RefCell<RuntimeState>>,
+}
+
+impl TweenHandle {
+    fn value(&self) -> f64 {
+        self.tween.value(self.shared.borrow().elapsed)
+    }
+
+    fn is_done(&self) -> bool {
+        self.tween.is_done(self.shared.borrow().elapsed)
+    }
+}
+
+impl UserData for TweenHandle {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("value", |_, this, ()| Ok(this.value()));
+        methods.add_method("is_done", |_, this, ()| Ok(this.is_done()));
+    }
+}
+
+// The tween handle passed to tween_value and tween_done
+fn tween_handle<'a>(name: &str, handle: &'a AnyUserData) -> mlua::Result<Ref<'a, TweenHandle>> {
+    handle.borrow::<TweenHandle>()
+        .map_err(|_| mlua::Error::RuntimeError(format!("{name} expected a tween, got another userdata")))
+}
+
+// An argument of a Lua function: its Lua type and what it's for. The type
+// is "string", "number", "boolean", "table", "userdata" or "any", optional
+// ones end in '?'.
+type Param = (&'static str, &'static str);
+
+// Like `Lua::create_function`, but arguments of the wrong type are reported
+// as e.g. "set_current_anim expected a string animation name, got number"
+// instead of mlua's generic conversion errors
+fn checked_function<'lua, A, R, F>(lua: &'lua Lua, name: &'static str, params: &'static [Param], f: F) -> mlua::Result<Function<'lua>>
+where
+    A: FromLuaMulti<'lua>,
+    R: IntoLuaMulti<'lua>,
+    F: Fn(&'lua Lua, A) -> mlua::Result<R> + 'static,
+{
+    lua.create_function(move |lua, args: MultiValue<'lua>| {
+        check_args(name, params, &args)?;
+        f(lua, A::from_lua_multi(args, lua)?)
+    })
+}
+
+fn check_args(name: &str, params: &[Param], args: &MultiValue) -> mlua::Result<()> {
+    for (i, (expected, description)) in params.iter().enumerate() {
+        let (expected, optional) = match expected.strip_suffix('?') {
+            Some(expected) => (expected, true),
+            None => (*expected, false),
+        };
+
+        let got = match args.iter().nth(i) {
+            None | Some(Value::Nil) => "nil",
+            Some(Value::Integer(_)) => "number",
+            Some(value) => value.type_name(),
+        };
+
+        if expected == "any" || got == expected || (optional && got == "nil") {
+            continue;
+        }
+
+        let article = if expected.starts_with(['a', 'e', 'i', 'o', 'u']) { "an" } else { "a" };
+        let position = if params.len() > 1 { format!(" as argument {}", i + 1) } else { String::new() };
+
+        return Err(mlua::Error::RuntimeError(
+            format!("{name} expected {article} {expected} {description}{position}, got {got}")));
+    }
+
+    Ok(())
+}
+
+// The terminal as it is right now, cut down to the viewport if there is one
+fn screen_size(shared: &RuntimeState) -> Option<(u16, u16)> {
+    let size = terminal::size().ok();
+
+    match (shared.viewport, size) {
+        (Some(viewport), Some(size)) => Some((viewport.0.min(size.0), viewport.1.min(size.1))),
+        (viewport, size) => viewport.or(size),
+    }
+}
+
+// The overlay, clipped to the screen as it is right now
+fn overlay(shared: &Rc<RefCell<RuntimeState>>) -> RefMut<'_, Overlay> {
+    let size = screen_size(&shared.borrow());
+    let mut overlay = RefMut::map(shared.borrow_mut(), |shared| &mut shared.overlay);
+    overlay.set_bounds(size);
+    overlay
+}
+
+// The draw_* functions take the first character of a string
+fn draw_char(name: &str, s: &str) -> mlua::Result<char> {
+    s.chars().next().ok_or_else(|| mlua::Error::RuntimeError(format!("{name}: the character can't be empty")))
+}
+
+// An empty table that reads from `values` and raises an error on writes
+fn read_only<'lua>(lua: &'lua Lua, values: Table<'lua>, function: &'static str) -> mlua::Result<Table<'lua>> {
+    let meta = lua.create_table()?;
+    meta.set("__index", values)?;
+    meta.set("__newindex", lua.create_function(move |_, _: MultiValue| -> mlua::Result<()> {
+        Err(mlua::Error::RuntimeError(format!("{function}: the table is read-only")))
+    })?)?;
+    // pairs() would only see the empty proxy otherwise
+    meta.set("__pairs", lua.create_function(move |lua, proxy: Table| {
+        let values: Table = proxy.get_metatable()
+            .ok_or_else(|| mlua::Error::RuntimeError(format!("{function}: the table lost its metatable")))?
+            .raw_get("__index")?;
+        Ok((lua.globals().get::<_, Function>("next")?, values, Value::Nil))
+    })?)?;
+    meta.set("__metatable", false)?;
+
+    let proxy = lua.create_table()?;
+    proxy.set_metatable(Some(meta));
+    Ok(proxy)
+}
+
+// Installs the engine's functions into the Lua globals
+pub(crate) fn register(lua: &Lua, shared: &Rc<RefCell<RuntimeState>>, sounds: &Rc<Sounds>) -> mlua::Result<()> {
+    let globals = lua.globals();
+
+    let shared_closure = shared.clone();
+    globals.set(
+        "get_current_anim",
+        lua.create_function(
+            move |_, ()| Ok(shared_closure.borrow().current_anim.clone())
+        )?
+    )?;
+
+    // Whether a key (named like Key_down gets it) is held down right now
+    let shared_closure = shared.clone();
+    globals.set(
+        "is_key_down",
+        checked_function(lua, "is_key_down", &[("string", "key name")], move |_, name: String| {
+            let shared = shared_closure.borrow();
+
+            Ok(shared.held_keys.get(&name).is_some_and(|pressed|
+                shared.reports_releases || shared.elapsed.saturating_sub(*pressed) < KEY_HOLD_TIMEOUT))
+        })?
+    )?;
+
+    // Polling alternatives to Key_down. Reading keys clears the buffer.
+    let shared_closure = shared.clone();
+    globals.set(
+        "get_last_key",
+        lua.create_function(move |_, ()| {
+            let mut shared = shared_closure.borrow_mut();
+            let key = shared.key_buffer.back().cloned();
+            shared.key_buffer.clear();
+            Ok(key)
+        })?
+    )?;
+
+    let shared_closure = shared.clone();
+    globals.set(
+        "peek_last_key",
+        lua.create_function(move |_, ()| Ok(shared_closure.borrow().key_buffer.back().cloned()))?
+    )?;
+
+    let shared_closure = shared.clone();
+    globals.set(
+        "get_keys",
+        lua.create_function(move |lua, ()| {
+            let keys: Vec<String> = shared_closure.borrow_mut().key_buffer.drain(..).collect();
+            lua.create_sequence_from(keys)
+        })?
+    )?;
+
+    let shared_closure = shared.clone();
+    globals.set(
+        "current_frame_has_tag",
+        checked_function(lua, "current_frame_has_tag", &[("string", "tag")], move |_, tag: String| {
+            let shared = shared_closure.borrow();
+
+            Ok(shared.animations.get(&shared.current_anim)
+                .is_some_and(|anim| anim.frame_has_tag(shared.current_frame, &tag)))
+        })?
+    )?;
+
+    // The named animation, or the current one without a name
+    let shared_closure = shared.clone();
+    globals.set(
+        "get_animation",
+        checked_function(lua, "get_animation", &[("string?", "animation name")], move |_, name: Option<String>| {
+            let name = name.unwrap_or_else(|| shared_closure.borrow().current_anim.clone());
+
+            if !shared_closure.borrow().animations.contains_key(&name) {
+                return Ok(None);
+            }
+
+            Ok(Some(AnimationHandle { name, shared: shared_closure.clone() }))
+        })?
+    )?;
+
+    let shared_closure = shared.clone();
+    globals.set(
+        "set_current_anim",
+        checked_function(lua, "set_current_anim", &[("string", "animation name")], move |_, anim_name: String| {
+            let mut shared = shared_closure.borrow_mut();
+            let start_frame = shared.animations.get(&anim_name)
+                .ok_or_else(|| mlua::Error::RuntimeError(
+                    format!("set_current_anim: unknown animation '{anim_name}'")))?
+                .metadata.start_frame;
+
+            shared.current_anim = anim_name;
+            shared.current_frame = start_frame;
+            Ok(())
+        })?
+    )?;
+
+    // Starting a track that's already playing switches its animation and
+    // keeps its place in the z-order
+    let shared_closure = shared.clone();
+    globals.set(
+        "play_track",
+        checked_function(lua, "play_track", &[("string", "track name"), ("string", "animation name")],
+            move |_, (track_name, anim_name): (String, String)| {
+                let mut shared = shared_closure.borrow_mut();
+                let start_frame = shared.animations.get(&anim_name)
+                    .ok_or_else(|| mlua::Error::RuntimeError(
+                        format!("play_track: unknown animation '{anim_name}'")))?
+                    .metadata.start_frame;
+
+                let track = Track { name: track_name, anim: anim_name, frame: start_frame, last_advance: None };
+                match shared.tracks.iter_mut().find(|t| t.name == track.name) {
+                    Some(existing) => *existing = track,
+                    None => shared.tracks.push(track),
+                }
+                shared.tracks_changed = true;
+                Ok(())
+            })?
+    )?;
+
+    // Stopping a track that isn't playing does nothing
+    let shared_closure = shared.clone();
+    globals.set(
+        "stop_track",
+        checked_function(lua, "stop_track", &[("string", "track name")], move |_, track_name: String| {
+            let mut shared = shared_closure.borrow_mut();
+            let count = shared.tracks.len();
+            shared.tracks.retain(|track| track.name != track_name);
+            shared.tracks_changed |= shared.tracks.len() != count;
+            Ok(())
+        })?
+    )?;
+
+    let shared_closure = shared.clone();
+    globals.set(
+        "get_color_support",
+        lua.create_function(move |_, ()| Ok(shared_closure.borrow().color_depth.name()))?
+    )?;
+
+    // cols and rows are nil when the terminal size can't be read
+    let shared_closure = shared.clone();
+    globals.set(
+        "get_terminal_caps",
+        lua.create_function(move |lua, ()| {
+            let shared = shared_closure.borrow();
+            let caps = lua.create_table()?;
+            let size = screen_size(&shared);
+
+            caps.set("colors", shared.color_depth.name())?;
+            caps.set("cols", size.map(|s| s.0))?;
+            caps.set("rows", size.map(|s| s.1))?;
+            caps.set("is_tty", shared.output_is_tty)?;
+            // Mouse events aren't passed to pets
+            caps.set("supports_mouse", false)?;
+
+            Ok(caps)
+        })?
+    )?;
+
+    // A fresh table every call, behind a proxy so scripts can read the
+    // arguments but not change them for the rest of the pet
+    let shared_closure = shared.clone();
+    globals.set(
+        "get_args",
+        lua.create_function(move |lua, ()| {
+            let values = lua.create_table()?;
+            for (name, value) in shared_closure.borrow().launch_args.iter().cloned() {
+                values.set(name, value.into_lua(lua)?)?;
+            }
+
+            read_only(lua, values, "get_args")
+        })?
+    )?;
+
+    let shared_closure = shared.clone();
+    globals.set(
+        "get_metadata",
+        lua.create_function(move |lua, ()| {
+            let shared = shared_closure.borrow();
+            let metadata = &shared.metadata;
+
+            let values = lua.create_table()?;
+            values.set("name", metadata.name.as_str())?;
+            values.set("description", metadata.description.as_str())?;
+            values.set("default_state", metadata.default_state.as_str())?;
+            values.set("global_tick_delay", metadata.global_tick_delay)?;
+            values.set("persist_state", metadata.persist_state)?;
+            values.set("extends", metadata.extends.as_deref())?;
+            values.set("anchor", match metadata.anchor {
+                Anchor::TopLeft => "top_left",
+                Anchor::Center => "center",
+            })?;
+            values.set("z", metadata.z)?;
+            values.set("flip", metadata.flip)?;
+            values.set("confirm_quit", metadata.confirm_quit)?;
+            values.set("mirror_pairs", lua.create_sequence_from(metadata.mirror_pairs.iter().map(String::as_str))?)?;
+
+            read_only(lua, values, "get_metadata")
+        })?
+    )?;
+
+    // nil when there's no clipboard or nothing text-like on it
+    globals.set(
+        "get_clipboard",
+        lua.create_function(|_, ()| Ok(clipboard::get()))?
+    )?;
+
+    globals.set(
+        "set_clipboard",
+        checked_function(lua, "set_clipboard", &[("string", "text")], |_, text: String| {
+            clipboard::set(text).map_err(|e| mlua::Error::RuntimeError(format!("set_clipboard: {e}")))
+        })?
+    )?;
+
+    // Milliseconds since the pet started
+    let shared_closure = shared.clone();
+    globals.set(
+        "get_time",
+        lua.create_function(move |_, ()| Ok(shared_closure.borrow().elapsed.as_millis() as u64))?
+    )?;
+
+    let shared_closure = shared.clone();
+    globals.set(
+        "set_visible",
+        checked_function(lua, "set_visible", &[("boolean", "visibility")], move |_, visible: bool| {
+            shared_closure.borrow_mut().visible = visible;
+            Ok(())
+        })?
+    )?;
+
+    let shared_closure = shared.clone();
+    globals.set(
+        "draw_line",
+        checked_function(lua, "draw_line",
+            &[("number", "x1"), ("number", "y1"), ("number", "x2"), ("number", "y2"), ("string", "character")],
+            move |_, (x1, y1, x2, y2, c): (f64, f64, f64, f64, String)| {
+                let c = draw_char("draw_line", &c)?;
+                overlay(&shared_closure).line((x1 as i64, y1 as i64), (x2 as i64, y2 as i64), c);
+                Ok(())
+            })?
+    )?;
+
+    let shared_closure = shared.clone();
+    globals.set(
+        "draw_rect",
+        checked_function(lua, "draw_rect",
+            &[("number", "x"), ("number", "y"), ("number", "width"), ("number", "height"), ("string", "character")],
+            move |_, (x, y, w, h, c): (f64, f64, f64, f64, String)| {
+                let c = draw_char("draw_rect", &c)?;
+                overlay(&shared_closure).rect(x as i64, y as i64, w as i64, h as i64, c);
+                Ok(())
+            })?
+    )?;
+
+    let shared_closure = shared.clone();
+    globals.set(
+        "draw_hline",
+        checked_function(lua, "draw_hline",
+            &[("number", "x"), ("number", "y"), ("number", "length"), ("string", "character")],
+            move |_, (x, y, length, c): (f64, f64, f64, String)| {
+                let c = draw_char("draw_hline", &c)?;
+                overlay(&shared_closure).hline(x as i64, y as i64, length as i64, c);
+                Ok(())
+            })?
+    )?;
+
+    let shared_closure = shared.clone();
+    globals.set(
+        "draw_vline",
+        checked_function(lua, "draw_vline",
+            &[("number", "x"), ("number", "y"), ("number", "length"), ("string", "character")],
+            move |_, (x, y, length, c): (f64, f64, f64, String)| {
+                let c = draw_char("draw_vline", &c)?;
+                overlay(&shared_closure).vline(x as i64, y as i64, length as i64, c);
+                Ok(())
+            })?
+    )?;
+
+    let shared_closure = shared.clone();
+    globals.set(
+        "clear_overlay",
+        lua.create_function(move |_, ()| {
+            shared_closure.borrow_mut().overlay.clear();
+            Ok(())
+        })?
+    )?;
+
+    // Shows the terminal's cursor at a position of the overlay, or hides it
+    // again. It's hidden whenever the state changes.
+    let shared_closure = shared.clone();
+    globals.set(
+        "set_cursor",
+        checked_function(lua, "set_cursor", &[("boolean", "visibility"), ("string?", "style"), ("number?", "x"), ("number?", "y")],
+            move |_, (visible, style, x, y): (bool, Option<String>, Option<i64>, Option<i64>)| {
+                let mut shared = shared_closure.borrow_mut();
+                if !visible {
+                    shared.cursor = None;
+                    return Ok(());
+                }
+
+                let shape = match style {
+                    Some(style) => CursorShape::from_name(&style).ok_or_else(|| mlua::Error::RuntimeError(format!(
+                        "set_cursor: '{style}' isn't a cursor style, they're block, underline or bar, optionally prefixed with blinking_ or steady_")))?,
+                    None => shared.cursor.map(|cursor| cursor.shape).unwrap_or_default(),
+                };
+                let (last_x, last_y) = shared.cursor.map_or((0, 0), |cursor| (cursor.x, cursor.y));
+                let coordinate = |value: Option<i64>, last| value.map_or(Ok(last), |value| u16::try_from(value)
+                    .map_err(|_| mlua::Error::RuntimeError(format!("set_cursor: {value} is off the screen"))));
+
+                shared.cursor = Some(PetCursor { x: coordinate(x, last_x)?, y: coordinate(y, last_y)?, shape });
+                Ok(())
+            })?
+    )?;
+
+    // Returns the visibility, style and position of the cursor
+    let shared_closure = shared.clone();
+    globals.set(
+        "get_cursor",
+        lua.create_function(move |_, ()| Ok(match shared_closure.borrow().cursor {
+            Some(cursor) => (true, Some(cursor.shape.name()), Some(cursor.x), Some(cursor.y)),
+            None => (false, None, None, None),
+        }))?
+    )?;
+
+    let shared_closure = shared.clone();
+    globals.set(
+        "is_visible",
+        lua.create_function(move |_, ()| Ok(shared_closure.borrow().visible))?
+    )?;
+
+    let shared_closure = shared.clone();
+    globals.set(
+        "set_flip",
+        checked_function(lua, "set_flip", &[("boolean", "horizontal flip")], move |_, horizontal: bool| {
+            shared_closure.borrow_mut().flip = horizontal;
+            Ok(())
+        })?
+    )?;
+
+    let shared_closure = shared.clone();
+    globals.set(
+        "get_flip",
+        lua.create_function(move |_, ()| Ok(shared_closure.borrow().flip))?
+    )?;
+
+    let shared_closure = shared.clone();
+    globals.set(
+        "get_current_state",
+        lua.create_function(move |_, ()| {
+            let shared = shared_closure.borrow();
+            Ok(shared.pending_state.clone().unwrap_or_else(|| shared.current_state.clone()))
+        })?
+    )?;
+
+    // The transition happens after the calling handler returns
+    let shared_closure = shared.clone();
+    globals.set(
+        "set_current_state",
+        checked_function(lua, "set_current_state", &[("string", "state name")], move |_, state_name: String| {
+            let mut shared = shared_closure.borrow_mut();
+
+            if !shared.state_names.contains(&state_name) {
+                return Err(mlua::Error::RuntimeError(
+                    format!("set_current_state: unknown state '{state_name}'")));
+            }
+
+            shared.pending_state = Some(state_name);
+            Ok(())
+        })?
+    )?;
+
+    // Like set_current_state, but the current animation gets to finish first
+    let shared_closure = shared.clone();
+    globals.set(
+        "transition_after_anim",
+        checked_function(lua, "transition_after_anim", &[("string", "state name")], move |_, state_name: String| {
+            let mut shared = shared_closure.borrow_mut();
+
+            if !shared.state_names.contains(&state_name) {
+                return Err(mlua::Error::RuntimeError(
+                    format!("transition_after_anim: unknown state '{state_name}'")));
+            }
+
+            shared.state_after_anim = Some(state_name);
+            Ok(())
+        })?
+    )?;
+
+    // Setting a key to nil removes it
+    let shared_closure = shared.clone();
+    globals.set(
+        "store_set",
+        checked_function(lua, "store_set", &[("string", "key"), ("any", "value")], move |_, (key, value): (String, Value)| {
+            let mut shared = shared_closure.borrow_mut();
+
+            match value {
+                Value::Nil => {
+                    shared.store.remove(&key);
+                },
+                value => {
+                    let value = StoreValue::from_lua(value)
+                        .map_err(|e| mlua::Error::RuntimeError(format!("store_set: {e}")))?;
+                    shared.store.insert(key, value);
+                },
+            }
+
+            Ok(())
+        })?
+    )?;
+
+    let shared_closure = shared.clone();
+    globals.set(
+        "store_get",
+        checked_function(lua, "store_get", &[("string", "key")], move |lua, key: String| {
+            match shared_closure.borrow().store.get(&key) {
+                Some(value) => value.clone().into_lua(lua),
+                None => Ok(Value::Nil),
+            }
+        })?
+    )?;
+
+    // Counters start at 0 and are saved with the pet. Returns the new count.
+    let shared_closure = shared.clone();
+    globals.set(
+        "increment_counter",
+        checked_function(lua, "increment_counter", &[("string", "counter name"), ("number?", "amount")],
+            move |_, (name, by): (String, Option<i64>)| {
+                let mut shared = shared_closure.borrow_mut();
+                let counter = shared.counters.entry(name).or_default();
+                *counter = counter.saturating_add(by.unwrap_or(1));
+                Ok(*counter)
+            })?
+    )?;
+
+    let shared_closure = shared.clone();
+    globals.set(
+        "get_counter",
+        checked_function(lua, "get_counter", &[("string", "counter name")], move |_, name: String| {
+            Ok(shared_closure.borrow().counters.get(&name).copied().unwrap_or(0))
+        })?
+    )?;
+
+    // Whole days since the pet was first run, by the wall clock
+    let shared_closure = shared.clone();
+    globals.set(
+        "days_alive",
+        lua.create_function(move |_, ()| {
+            Ok(unix_time().saturating_sub(shared_closure.borrow().first_run) / (24 * 60 * 60))
+        })?
+    )?;
+
+    let shared_closure = shared.clone();
+    globals.set(
+        "register_stat",
+        checked_function(lua, "register_stat",
+            &[("string", "stat name"), ("number", "initial value"), ("number", "rate per second"),
+                ("number", "minimum"), ("number", "maximum"), ("table?", "thresholds")],
+            move |lua, (name, initial, rate_per_sec, min, max, thresholds): (String, f64, f64, f64, f64, Option<Table>)| {
+                let invalid = |e: String| mlua::Error::RuntimeError(format!("register_stat '{name}': {e}"));
+                let mut stat = Stat::new(initial, rate_per_sec, min, max).map_err(invalid)?;
+
+                // { high = 80, on_high = function(name, value) ... end, low = 10, on_low = ..., hysteresis = 5 }
+                let mut callbacks = Vec::new();
+                if let Some(thresholds) = thresholds {
+                    stat = stat.with_thresholds(Thresholds {
+                        high: thresholds.get("high")?,
+                        low: thresholds.get("low")?,
+                        hysteresis: thresholds.get::<_, Option<f64>>("hysteresis")?.unwrap_or(0.0),
+                    }).map_err(invalid)?;
+
+                    for (crossing, key) in [(Crossing::High, "on_high"), (Crossing::Low, "on_low")] {
+                        if let Some(callback) = thresholds.get::<_, Option<Function>>(key)? {
+                            callbacks.push((crossing, lua.create_registry_value(callback)?));
+                        }
+                    }
+                }
+
+                let mut shared = shared_closure.borrow_mut();
+                shared.stat_callbacks.retain(|(stat_name, _), _| *stat_name != name);
+                for (crossing, callback) in callbacks {
+                    shared.stat_callbacks.insert((name.clone(), crossing), callback);
+                }
+                shared.stats.register(name, stat);
+                Ok(())
+            }
+        )?
+    )?;
+
+    let shared_closure = shared.clone();
+    globals.set(
+        "get_stat",
+        checked_function(lua, "get_stat", &[("string", "stat name")],
+            move |_, name: String| Ok(shared_closure.borrow().stats.get(&name))
+        )?
+    )?;
+
+    let shared_closure = shared.clone();
+    globals.set(
+        "adjust_stat",
+        checked_function(lua, "adjust_stat", &[("string", "stat name"), ("number", "delta")], move |_, (name, delta): (String, f64)| {
+            if shared_closure.borrow_mut().stats.adjust(&name, delta) {
+                Ok(())
+            } else {
+                Err(mlua::Error::RuntimeError(format!("adjust_stat: unknown stat '{name}'")))
+            }
+        })?
+    )?;
+
+    // Only usable from a state's `Main` coroutine, the engine resumes it
+    // once the time has passed
+    globals.set(
+        "wait",
+        lua.load(r#"
+            return function(ms)
+                if type(ms) ~= "number" then
+                    error("wait expected a number of milliseconds, got " .. type(ms), 2)
+                end
+                coroutine.yield(ms)
+            end
+        "#)
+            .set_name("wait")
+            .eval::<mlua::Function>()?
+    )?;
+
+    let shared_closure = shared.clone();
+    globals.set(
+        "send_message",
+        checked_function(lua, "send_message", &[("string", "pet name"), ("string", "event name"), ("any", "payload")], move |_, (target, event, payload): (String, String, mlua::Value)| {
+            let shared = shared_closure.borrow();
+            let mut bus = shared.bus.borrow_mut();
+
+            if !bus.has_pet(&target) {
+                return Err(mlua::Error::RuntimeError(format!("send_message: unknown pet '{target}'")));
+            }
+
+            bus.send(Message {
+                sender: shared.pet_id.clone(),
+                target,
+                event,
+                payload: Payload::from_lua(payload)?,
+            });
+            Ok(())
+        })?
+    )?;
+
+    let shared_closure = shared.clone();
+    globals.set(
+        "set_speed",
+        checked_function(lua, "set_speed", &[("number", "speed")], move |_, speed: f64| {
+            shared_closure.borrow_mut().speed = clamp_speed(speed);
+            Ok(())
+        })?
+    )?;
+
+    let shared_closure = shared.clone();
+    globals.set(
+        "get_speed",
+        lua.create_function(move |_, ()| Ok(shared_closure.borrow().speed))?
+    )?;
+
+    let shared_closure = shared.clone();
+    globals.set(
+        "random_normal",
+        checked_function(lua, "random_normal", &[("number?", "mean"), ("number?", "standard deviation")],
+            move |_, (mean, stddev): (Option<f64>, Option<f64>)| {
+                let stddev = stddev.unwrap_or(1.0);
+                if !(stddev >= 0.0 && stddev.is_finite()) {
+                    return Err(mlua::Error::RuntimeError(
+                        "random_normal: the standard deviation can't be negative".to_string()));
+                }
+
+                let normal = Normal::new(mean.unwrap_or(0.0), stddev)
+                    .map_err(|e| mlua::Error::RuntimeError(format!("random_normal: {e}")))?;
+
+                Ok(normal.sample(&mut shared_closure.borrow_mut().rng))
+            })?
+    )?;
+
+    // Picks an element of a list, by the weights in a second list if given
+    let shared_closure = shared.clone();
+    globals.set(
+        "random_choice",
+        checked_function(lua, "random_choice", &[("table", "list"), ("table?", "list of weights")],
+            move |_, (items, weights): (Table, Option<Table>)| {
+                let items: Vec<Value> = items.sequence_values().collect::<mlua::Result<_>>()?;
+                if items.is_empty() {
+                    return Ok(Value::Nil);
+                }
+
+                let mut shared = shared_closure.borrow_mut();
+                let i = match weights {
+                    Some(weights) => {
+                        let weights: Vec<f64> = weights.sequence_values().collect::<mlua::Result<_>>()?;
+                        if weights.len() != items.len() {
+                            return Err(mlua::Error::RuntimeError(
+                                "random_choice: there must be as many weights as elements".to_string()));
+                        }
+
+                        WeightedIndex::new(&weights)
+                            .map_err(|e| mlua::Error::RuntimeError(format!("random_choice: {e}")))?
+                            .sample(&mut shared.rng)
+                    },
+                    None => shared.rng.gen_range(0..items.len()),
+                };
+
+                Ok(items[i].clone())
+            })?
+    )?;
+
+    // Moves from one number to another over a number of milliseconds,
+    // scaled by the speed like wait
+    let shared_closure = shared.clone();
+    globals.set(
+        "tween",
+        checked_function(lua, "tween", &[("number", "start value"), ("number", "end value"), ("number", "duration in milliseconds"), ("string?", "easing")],
+            move |_, (from, to, ms, easing): (f64, f64, f64, Option<String>)| {
+                let easing = easing.as_deref()
+                    .map(str::parse)
+                    .transpose()
+                    .map_err(|e| mlua::Error::RuntimeError(format!("tween: {e}")))?
+                    .unwrap_or_default();
+
+                let shared = shared_closure.borrow();
+                let duration = Duration::try_from_secs_f64(ms.max(0.0) / 1000.0)
+                    .map_err(|_| mlua::Error::RuntimeError("tween: the duration is too long".to_string()))?
+                    .div_f64(shared.speed);
+                let tween = Tween { from, to, start: shared.elapsed, duration, easing };
+
+                Ok(TweenHandle { tween, shared: shared_closure.clone() })
+            })?
+    )?;
+
+    globals.set(
+        "tween_value",
+        checked_function(lua, "tween_value", &[("userdata", "tween")], |_, handle: AnyUserData| {
+            Ok(tween_handle("tween_value", &handle)?.value())
+        })?
+    )?;
+
+    globals.set(
+        "tween_done",
+        checked_function(lua, "tween_done", &[("userdata", "tween")], |_, handle: AnyUserData| {
+            Ok(tween_handle("tween_done", &handle)?.is_done())
+        })?
+    )?;
+
+    // Fills `{key}` placeholders in frames with a value, see `fill_template`
+    // for how it's fitted in. A nil value puts the placeholder back.
+    let shared_closure = shared.clone();
+    globals.set(
+        "set_template_var",
+        checked_function(lua, "set_template_var", &[("string", "key"), ("any", "value")],
+            move |_, (key, value): (String, Value)| {
+                if key.is_empty() || !key.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_') {
+                    return Err(mlua::Error::RuntimeError(format!(
+                        "set_template_var: '{key}' isn't a valid key, use letters, digits and underscores")));
+                }
+
+                let value = match value {
+                    Value::Nil => None,
+                    Value::String(s) => Some(s.to_str()?.to_string()),
+                    Value::Integer(i) => Some(i.to_string()),
+                    Value::Number(n) => Some(n.to_string()),
+                    Value::Boolean(b) => Some(b.to_string()),
+                    other => return Err(mlua::Error::RuntimeError(format!(
+                        "set_template_var: a {} can't be shown in a frame", other.type_name()))),
+                };
+
+                let mut shared = shared_closure.borrow_mut();
+                let changed = match value {
+                    Some(value) => shared.template_vars.insert(key, value.clone()) != Some(value),
+                    None => shared.template_vars.remove(&key).is_some(),
+                };
+                shared.template_changed |= changed;
+                Ok(())
+            })?
+    )?;
+
+    // Jolts the pet around its place by up to `intensity` cells, less and
+    // less until the duration is over. Shaking again starts over.
+    let shared_closure = shared.clone();
+    globals.set(
+        "shake",
+        checked_function(lua, "shake", &[("number", "intensity"), ("number", "duration in milliseconds")],
+            move |_, (intensity, ms): (f64, f64)| {
+                if !(intensity >= 0.0 && intensity.is_finite()) {
+                    return Err(mlua::Error::RuntimeError("shake: the intensity must be a positive number".to_string()));
+                }
+
+                let mut shared = shared_closure.borrow_mut();
+                let duration = Duration::try_from_secs_f64(ms.max(0.0) / 1000.0)
+                    .map_err(|_| mlua::Error::RuntimeError("shake: the duration is too long".to_string()))?
+                    .div_f64(shared.speed);
+                shared.shake = Some(Tween { from: intensity, to: 0.0, start: shared.elapsed, duration, easing: Easing::Linear });
+                Ok(())
+            })?
+    )?;
+
+    // Lines are shown one after another, each for its duration. Interrupting
+    // drops whatever is queued, including the line being shown. Returns
+    // whether the line was queued, it isn't when the queue is full.
+    let shared_closure = shared.clone();
+    globals.set(
+        "say",
+        checked_function(lua, "say", &[("string", "text"), ("number?", "duration in milliseconds"), ("boolean?", "interrupt")],
+            move |_, (text, ms, interrupt): (String, Option<f64>, Option<bool>)| {
+                let mut shared = shared_closure.borrow_mut();
+                let duration = match ms {
+                    Some(ms) => Duration::try_from_secs_f64(ms.max(0.0) / 1000.0)
+                        .map_err(|_| mlua::Error::RuntimeError("say: the duration is too long".to_string()))?,
+                    None => SPEECH_DURATION,
+                }.div_f64(shared.speed);
+
+                if interrupt.unwrap_or(false) {
+                    shared.speech.clear();
+                    shared.speech_until = None;
+                } else if shared.speech.len() == SPEECH_QUEUE_SIZE {
+                    return Ok(false);
+                }
+
+                shared.speech.push_back(Speech { text, duration });
+                shared.speech_changed = true;
+                Ok(true)
+            })?
+    )?;
+
+    let shared_closure = shared.clone();
+    globals.set(
+        "clear_speech",
+        lua.create_function(move |_, ()| {
+            let mut shared = shared_closure.borrow_mut();
+            shared.speech_changed |= !shared.speech.is_empty();
+            shared.speech.clear();
+            shared.speech_until = None;
+            Ok(())
+        })?
+    )?;
+
+    // The pet is loaded right away so that failing to load it is an error
+    // here, then it replaces this one after the tick
+    let shared_closure = shared.clone();
+    globals.set(
+        "load_pet",
+        checked_function(lua, "load_pet", &[("string", "pet name")], move |_, name: String| {
+            let (pets_dir, load_options) = {
+                let shared = shared_closure.borrow();
+                (shared.pets_dir.clone(), shared.load_options)
+            };
+            let pets_dir = pets_dir
+                .ok_or_else(|| mlua::Error::RuntimeError("load_pet: there's no pets directory to load from".to_string()))?;
+
+            if !is_valid_pet_name(&name) {
+                return Err(mlua::Error::RuntimeError(format!("load_pet: '{name}' isn't a valid pet name")));
+            }
+            let path = pets_dir.join(&name);
+            if !path.is_dir() {
+                return Err(mlua::Error::RuntimeError(format!("load_pet: the pet '{name}' doesn't exist")));
+            }
+            if !is_inside(&pets_dir, &path) {
+                return Err(mlua::Error::RuntimeError(format!("load_pet: the pet '{name}' is outside of the pets directory")));
+            }
+
+            let pet = DetachedPet::load(&path, load_options)
+                .map_err(|e| mlua::Error::RuntimeError(format!("load_pet: loading '{name}' failed: {e}")))?;
+            shared_closure.borrow_mut().loaded_pet = Some(pet);
+            Ok(())
+        })?
+    )?;
+
+    let sounds_closure = sounds.clone();
+    globals.set(
+        "play_sound",
+        checked_function(lua, "play_sound", &[("string", "sound name")], move |_, name: String| {
+            sounds_closure.play(&name)
+                .map_err(|e| mlua::Error::RuntimeError(format!("play_sound '{name}': {e}")))
+        })?
+    )?;
+
+    Ok(())
+}
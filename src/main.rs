@@ -1,160 +1,719 @@
 use std::{
-    fs, io::{stdout, Stdout, Write}, path::PathBuf, string::String, sync::{atomic::{AtomicBool, Ordering}, Arc}, thread::sleep, time::{Duration, Instant}
+    cell::RefCell, fs, io::{self, stderr, stdout, IsTerminal, Write}, panic::{self, AssertUnwindSafe}, path::{Path, PathBuf}, rc::Rc, string::String, sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex}, thread::sleep, time::{Duration, Instant}
 };
 
 use clap::Parser;
-use crossterm::{cursor::{self, MoveTo}, event::{self, KeyEvent}, execute, terminal::{self, disable_raw_mode, enable_raw_mode}, ExecutableCommand};
+use crossterm::{cursor::{self, MoveTo, SetCursorStyle}, event::{self, DisableFocusChange, EnableFocusChange, KeyEvent, KeyEventKind, KeyboardEnhancementFlags, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags}, execute, style::Print, terminal::{self, disable_raw_mode, enable_raw_mode}, ExecutableCommand};
 use directories::BaseDirs;
 use mlua::Lua;
 
-use pet::{Animation, Pet};
-use args::Args;
+use a_duk::{bus::{MessageBus, Payload}, canvas::Canvas, cast::CastRecorder, clock::{Clock, MockClock, SystemClock}, color::ColorDepth, detached::DetachedLuas, embedded, frame_writer::{write_lines, LineEnding}, index::indexed_pets, pet::{Animation, LoadOptions, Pet}, preview::{animation_strip, preview_pages}, runtime::RenderKey, save::SaveFile, text::{display_width, fit_to_width, frame_width}, PetRuntime};
+use args::{Args, ColorChoice, Command, LineEndingChoice, Output, Region, RunArgs, Viewport, ViewportAnchor};
+use exit::{ExitCode, Failure};
+use usage::ProcessUsage;
 
-mod pet;
 mod args;
+mod commands;
+mod exit;
+mod logger;
+mod picker;
+mod usage;
+
+fn clear(out: &mut impl Write) -> io::Result<()> {
+    execute!(out, terminal::Clear(terminal::ClearType::All))?;
+    execute!(out, MoveTo(0,0))
+}
+
+// How many transitions --debug shows under each pet
+const DEBUG_HISTORY_LINES: usize = 5;
+
+// How long --scroll stays on each column or row of a big frame
+const SCROLL_STEP: Duration = Duration::from_millis(200);
+
+// Shown where the pet speaks with --confirm-quit or confirm_quit
+const QUIT_PROMPT: &str = "Quit? (y/n)";
+
+// The origin and size of the part of the terminal that's drawn into
+type Area = ((u16, u16), (u16, u16));
+
+// What's on the screen, so pets that look the same as last time aren't
+// composited and written out again
+struct Screen {
+    canvas: Canvas,
+    viewport: Option<(Viewport, ViewportAnchor)>,
+    // Takes the viewport's place with --region
+    region: Option<Region>,
+    // Where the pets were drawn last and what they looked like
+    drawn: Option<(Area, Vec<RenderKey>)>,
+    // Shown with --debug
+    usage: Option<ProcessUsage>,
+    // When --scroll started panning
+    scroll_started: Option<Instant>,
+}
+
+// Every pet gets an equally wide column of the terminal, or of the viewport
+// or region if there is one. The pets are composited back to front by z
+// before anything is written.
+fn draw(out: &mut impl Write, runtimes: &mut [PetRuntime], screen: &mut Screen, debug: bool) -> io::Result<()> {
+    let (origin, size) = match (screen.region, screen.viewport) {
+        (Some(region), _) => region.area(terminal::size()?),
+        (None, Some((viewport, anchor))) => viewport.area(terminal::size()?, anchor),
+        (None, None) => ((0, 0), terminal::size()?),
+    };
+
+    let column_width = size.0 / runtimes.len().max(1) as u16;
+
+    if let Some(started) = screen.scroll_started {
+        let steps = (started.elapsed().as_millis() / SCROLL_STEP.as_millis()) as u64;
+        for runtime in runtimes.iter_mut() {
+            let (width, height) = runtime.animation_size();
+            runtime.set_pan((
+                bounce(steps, width.saturating_sub(column_width)),
+                bounce(steps, height.saturating_sub(size.1)),
+            ));
+        }
+    }
+    let origins: Vec<_> = runtimes.iter().enumerate()
+        .map(|(i, runtime)| runtime.anchored_origin((i as u16 * column_width, 0), (column_width, size.1)))
+        .collect();
+    let keys = runtimes.iter().zip(&origins)
+        .map(|(runtime, origin)| runtime.render_key(*origin))
+        .collect();
+
+    let last_area = screen.drawn.as_ref().map(|(area, _)| *area);
+    let drawn = Some(((origin, size), keys));
+    // --debug always redraws, the history can change while the pets don't
+    if screen.drawn == drawn && !debug {
+        return Ok(());
+    }
+    screen.drawn = drawn;
+
+    // Whatever was drawn outside of a moved viewport would stay there
+    if screen.viewport.is_some() && last_area != Some((origin, size)) {
+        clear(out)?;
+    }
+
+    let canvas = &mut screen.canvas;
+    if canvas.size() != size {
+        *canvas = Canvas::new(size);
+    } else if runtimes.iter().all(PetRuntime::needs_clear) {
+        canvas.clear();
+    }
+
+    let mut order: Vec<_> = runtimes.iter().zip(origins).collect();
+    order.sort_by_key(|(runtime, _)| runtime.z());
+
+    for (runtime, origin) in order {
+        runtime.render_into(canvas, origin);
+    }
+    canvas.write_at(out, origin)?;
+
+    if debug {
+        for (i, runtime) in runtimes.iter().enumerate() {
+            draw_history(out, runtime, (origin.0 + i as u16 * column_width, origin.1), (column_width, size.1))?;
+        }
+    }
+
+    // In the top right corner, out of the way of the history
+    if let Some(line) = screen.usage.as_mut().and_then(|usage| usage.line(Instant::now())) {
+        let line = fit_to_width(line, usize::from(size.0).min(display_width(line)));
+        let x = origin.0 + size.0.saturating_sub(display_width(&line) as u16);
+        execute!(out, MoveTo(x, origin.1), Print(line))?;
+    }
+
+    // Last, since drawing moves the cursor. The topmost pet showing the
+    // cursor gets it.
+    let shown = runtimes.iter()
+        .filter_map(|runtime| Some((runtime.z(), runtime.cursor()?)))
+        .filter(|(_, pet_cursor)| pet_cursor.x < size.0 && pet_cursor.y < size.1)
+        .max_by_key(|(z, _)| *z);
+    match shown {
+        Some((_, pet_cursor)) => execute!(out, pet_cursor.shape.command(),
+            MoveTo(origin.0 + pet_cursor.x, origin.1 + pet_cursor.y), cursor::Show)?,
+        None => execute!(out, cursor::Hide)?,
+    }
+
+    Ok(())
+}
+
+// Goes from 0 up to `max` and back down again, a step at a time
+fn bounce(steps: u64, max: u16) -> u16 {
+    if max == 0 {
+        return 0;
+    }
 
-fn clear(stdout: &mut Stdout) {
-    execute!(stdout, terminal::Clear(terminal::ClearType::All)).unwrap();
-    execute!(stdout, MoveTo(0,0)).unwrap();
+    let position = steps % (2 * u64::from(max));
+    position.min(2 * u64::from(max) - position) as u16
 }
 
-fn main() -> Result<(), String> {
+// The latest transitions at the bottom of the pet's column
+fn draw_history(out: &mut impl Write, runtime: &PetRuntime, origin: (u16, u16), size: (u16, u16)) -> io::Result<()> {
+    let history: Vec<_> = runtime.history().collect();
+    let shown = &history[history.len().saturating_sub(DEBUG_HISTORY_LINES)..];
+    let top = (origin.1 + size.1).saturating_sub(shown.len() as u16);
+
+    for (i, transition) in shown.iter().enumerate() {
+        let line: String = transition.to_string().chars().take(size.0.saturating_sub(1).into()).collect();
+        execute!(out, MoveTo(origin.0, top + i as u16), Print(line))?;
+    }
+
+    Ok(())
+}
+
+fn main() {
     let args = Args::parse();
 
+    if let Err(failure) = run(args) {
+        failure.exit();
+    }
+}
+
+fn run(args: Args) -> Result<(), Failure> {
+    let config_dir = get_config_dir()
+        .map_err(|e| Failure::new(ExitCode::Other,
+            format!("The configuration directory couldn't be created: {e}")))?;
+
+    if args.debug {
+        logger::init(&config_dir.join("a_duk.log"))
+            .map_err(|e| Failure::new(ExitCode::Other, format!("The log file couldn't be opened: {e}")))?;
+    }
+
+    let pets_dir = args.pet_dir.unwrap_or_else(|| config_dir.join("pets"));
+    let index_path = config_dir.join("index.toml");
+
+    match args.command {
+        None => run_interactive(&pets_dir, &config_dir.join("saves"), &index_path, args.run, args.debug),
+        Some(Command::Run(run)) => run_interactive(&pets_dir, &config_dir.join("saves"), &index_path, run, args.debug),
+        Some(Command::New { name }) => commands::new_pet(&pets_dir, &name),
+        Some(Command::List) => commands::list_pets(&pets_dir, &index_path),
+        Some(Command::Index) => commands::index_pets(&pets_dir, &index_path),
+        Some(Command::Validate { pet, strict }) => commands::validate_pet(&pets_dir, &pet, strict),
+        Some(Command::Audit { pet }) => commands::audit_pet(&pets_dir, &pet),
+        Some(Command::Export { pet, animation, format, output }) =>
+            commands::export_animation(&pets_dir, &pet, &animation, format, output),
+    }
+}
+
+fn run_interactive(pets_dir: &Path, saves_dir: &Path, index_path: &Path, mut args: RunArgs, debug: bool) -> Result<(), Failure> {
+    if args.pet.is_empty() {
+        match choose_pet(pets_dir, index_path)? {
+            Some(name) => args.pet.push(name),
+            None => return Ok(()),
+        }
+    }
+
+    // Load the pets, each into its own Lua state
+
+    // Pets are addressed by name on the message bus
+    if let Some(name) = args.pet.iter().enumerate().find_map(|(i, p)| args.pet[..i].contains(p).then_some(p)) {
+        return Err(Failure::new(ExitCode::Other, format!("The pet '{name}' is given more than once")));
+    }
+
+    let luas: Vec<Lua> = args.pet.iter().map(|_| Lua::new()).collect();
+
+    let mut pets = Vec::new();
+    for (name, lua) in args.pet.iter().zip(&luas) {
+        let pet_path = commands::pet_dir_or_embedded(pets_dir, name)?;
+
+        let pet = Pet::load_with_options(lua, &pet_path, load_options(&args, debug), |done, total| show_progress(name, done, total));
+        clear_progress();
+        let pet = pet.map_err(|e| Failure::pet(e, &format!("Loading the pet '{name}' failed")))?;
+
+        let timings = pet.load_timings;
+        log::info!("Loaded '{name}' in {:?}: animations {:?}, states {:?}, Lua {:?}",
+            timings.total(), timings.animations, timings.states, timings.lua);
+        pets.push(pet);
+    }
+    let load_timings: Vec<_> = pets.iter().map(|pet| pet.load_timings).collect();
+
+    if let Some(name) = args.inspect.iter().chain(&args.strip).find(|name| !pets[0].animations.contains_key(*name)) {
+        return Err(Failure::new(ExitCode::InvalidPet, format!("The animation '{name}' doesn't exist")));
+    }
+
+    // Printed like any other output, without taking over the terminal
+    if let Some(name) = &args.strip {
+        let cols = terminal::size().map_or(80, |(cols, _)| usize::from(cols));
+        for line in animation_strip(&pets[0].animations[name], cols) {
+            println!("{line}");
+        }
+        return Ok(());
+    }
+
     // Weird async shit just to handle sigint :D
     // I have no idea what im doing :D
     let running = Arc::new(AtomicBool::new(true));
     let r = running.clone();
 
-    enable_raw_mode().expect("Unable to enable raw mode");
-
     ctrlc::set_handler(move || {
         println!("sigint");
         r.store(false, Ordering::SeqCst);
     })
-    .expect("Error setting sigint handler");
-
-    let mut stdout = stdout();
-    stdout.execute(cursor::Hide).unwrap();
+    .map_err(|e| Failure::new(ExitCode::Other, format!("Error setting sigint handler: {e}")))?;
 
-    // Load the pet
+    let line_ending = match args.line_ending {
+        LineEndingChoice::Lf => LineEnding::Lf,
+        LineEndingChoice::Crlf => LineEnding::CrLf,
+    };
 
-    let lua = Lua::new();
+    let mut out: Box<dyn Write> = match args.output {
+        Output::Stdout => Box::new(stdout()),
+        Output::Stderr => Box::new(stderr()),
+    };
+    if let Some(path) = &args.record_cast {
+        let recording_failed = |e: io::Error| Failure::new(ExitCode::Other, format!("Recording to {} failed: {e}", path.display()));
 
-    let pet_path_buf = get_config_dir()
-        .expect("The configuration directory conldn't be created")
-        .join("pets")
-        .join(args.pet);
-    let pet_path = pet_path_buf.as_path();
+        let file = fs::File::create(path).map_err(recording_failed)?;
+        let size = terminal::size().unwrap_or((80, 24));
+        out = Box::new(CastRecorder::new(out, io::BufWriter::new(file), size).map_err(recording_failed)?);
+    }
 
-    let pet = Pet::load(&lua, pet_path)
-        .map_err(|e| format!("Loading the pet failed: {e}"))?;
+    enable_raw_mode().map_err(Failure::terminal)?;
 
-    println!("Loaded pet:");
-    println!("Name: {}", pet.metadata.name);
-    println!("Description: {}", pet.metadata.description);
-    sleep(Duration::from_secs(1));
-    clear(&mut stdout);
+    // A panic message printed in raw mode comes out garbled, so it's kept
+    // until the pets are saved and the terminal is restored
+    let panic_message = Arc::new(Mutex::new(None));
+    let default_hook = panic::take_hook();
+    let hook_message = panic_message.clone();
+    panic::set_hook(Box::new(move |info| {
+        if let Ok(mut message) = hook_message.lock() {
+            *message = Some(info.to_string());
+        }
+    }));
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| out.execute(cursor::Hide)
+        .map_err(Failure::terminal)
+        .and_then(|out| if let Some(name) = &args.inspect {
+            run_inspect(out, &pets[0].animations[name], line_ending, &running)
+        } else if args.preview {
+            run_preview(out, &pets[0], line_ending, &running)
+        } else {
+            run_pets(out, pets, saves_dir, pets_dir, &args, debug, &running)
+        })));
 
-    // init loop
-    let current_state = &pet.metadata.default_state;
-    let mut current_anim = pet.states.get(current_state).unwrap().metadata.animation.clone();
+    // Cleanup
+    panic::set_hook(default_hook);
+    let restored = execute!(out, SetCursorStyle::DefaultUserShape, cursor::Show)
+        .and_then(|_| disable_raw_mode());
 
-    let mut current_frame = 0;
+    let result = result.unwrap_or_else(|panic| {
+        if let Some(message) = panic_message.lock().ok().and_then(|mut message| message.take()) {
+            eprintln!("{message}");
+        }
+        panic::resume_unwind(panic)
+    });
+    restored.map_err(Failure::terminal)?;
+
+    // The screen is taken over right after loading, so this waits until the end
+    if debug {
+        for (name, timings) in args.pet.iter().zip(&load_timings) {
+            eprintln!("Loading '{name}' took {:.1?} (animations {:.1?}, states {:.1?}, Lua {:.1?})",
+                timings.total(), timings.animations, timings.states, timings.lua);
+        }
+    }
 
-    let mut now = Instant::now();
+    result
+}
 
-    let mut last_render = now;
-    let mut last_update = now;
+// How the pets of a run are loaded, the ones swapped in by load_pet too
+fn load_options(args: &RunArgs, debug: bool) -> LoadOptions {
+    LoadOptions { debug, strict: args.strict, max_frame_bytes: args.max_assets_mb.saturating_mul(1024 * 1024) }
+}
 
-    let delay = Duration::from_millis(pet.metadata.global_tick_delay);
+// With several valid pets installed the user picks one, or gets the first
+// when there's no terminal to pick on. A single pet is run as it is, and
+// without any it's the built-in duk. None if the user backed out of the picker.
+fn choose_pet(pets_dir: &Path, index_path: &Path) -> Result<Option<String>, Failure> {
+    let pets: Vec<_> = indexed_pets(pets_dir, commands::load_index(index_path).as_ref())
+        .into_iter()
+        .filter_map(|(name, summary)| summary
+            .inspect_err(|e| log::warn!("Leaving '{name}' out of the picker: {e}"))
+            .ok()
+            .map(|summary| (name, summary)))
+        .collect();
+
+    let Some((first, _)) = pets.first() else {
+        return Ok(Some(embedded::NAME.to_string()));
+    };
+    if pets.len() == 1 || !stdout().is_terminal() || !io::stdin().is_terminal() {
+        return Ok(Some(first.clone()));
+    }
 
-    let current_anim_closure = current_anim.clone();
-    // Init lua globals
-    lua.globals().set(
-        "get_current_anim",
-        lua.create_function(
-            move |_, ()| Ok(current_anim_closure.clone())
-        ).unwrap()
-    ).unwrap();
+    picker::pick_pet(&mut stdout(), &pets)
+}
 
-    let current_anim_ptr = &mut current_anim as *mut String;
-    let current_frame_ptr = &mut current_frame as *mut usize;
+// A single line on stderr that's overwritten as the frames are read
+fn show_progress(name: &str, done: usize, total: usize) {
+    if total > 0 && stderr().is_terminal() {
+        eprint!("\rLoading {name}... {done}/{total} frames");
+    }
+}
 
-    lua.globals().set(
-        "set_current_anim",
-       lua.create_function_mut(move |_, anim_name: String| {
-            unsafe {
-                *current_anim_ptr = anim_name;
-                *current_frame_ptr = 0;
-            }
-            Ok(())
-        }).unwrap()
-    ).unwrap();
+fn clear_progress() {
+    if stderr().is_terminal() {
+        let _ = execute!(stderr(), Print("\r"), terminal::Clear(terminal::ClearType::CurrentLine));
+    }
+}
 
-    // Call the init event of the initial status
-    if let Some(f) = &pet.states.get(current_state).unwrap().event_handlers.init {
-        f.call::<(), ()>(())
-            .map_err(|e| format!("The pet's init function failed: '{}'", e))?;
+fn run_pets(out: &mut impl Write, pets: Vec<Pet>, saves_dir: &Path, pets_dir: &Path, args: &RunArgs, debug: bool, running: &AtomicBool) -> Result<(), Failure> {
+    // A region leaves the rest of the terminal alone, it's overwritten in
+    // full by every draw anyway
+    if args.region.is_none() {
+        clear(out).map_err(Failure::terminal)?;
     }
 
-    while running.load(Ordering::SeqCst) {
-        now = Instant::now();
-        let state = pet.states.get(current_state).unwrap();
+    let delay = pets.iter()
+        .map(|pet| Duration::from_millis(pet.metadata.global_tick_delay))
+        .min()
+        .unwrap_or_default();
+
+    let color_depth = match args.color {
+        ColorChoice::Auto => ColorDepth::detect(),
+        ColorChoice::Truecolor => ColorDepth::TrueColor,
+        ColorChoice::Ansi256 => ColorDepth::Ansi256,
+        ColorChoice::Ansi16 => ColorDepth::Ansi16,
+        ColorChoice::None => ColorDepth::None,
+    };
 
-        if now.duration_since(last_render).as_millis() >= pet.animations.get(current_state).unwrap().metadata.delay.into() {
-            let anim = pet.animations.get(&current_anim).unwrap();
+    let output_is_tty = match args.output {
+        Output::Stdout => stdout().is_terminal(),
+        Output::Stderr => stderr().is_terminal(),
+    };
 
-            clear(&mut stdout);
-            disable_raw_mode().unwrap();
-            println!("{}", anim.frames[current_frame]);
-            enable_raw_mode().unwrap();
-            // stdout.write_all(anim.frames[current_frame].as_bytes()).unwrap();
+    let bus = Rc::new(RefCell::new(MessageBus::default()));
+    // Before the runtimes, which may borrow from it
+    let detached_luas = DetachedLuas::default();
+    let mut runtimes = Vec::new();
+
+    // Key releases for Key_up and is_key_down, where the terminal has them
+    let enhanced_keys = terminal::supports_keyboard_enhancement().unwrap_or(false);
+
+    let mut paused = false;
+    // The pets stand still while the quit prompt is up
+    let mut confirming_quit = false;
+    let mut screen = Screen {
+        canvas: Canvas::new((0, 0)),
+        viewport: args.viewport.map(|viewport| (viewport, args.viewport_anchor)),
+        region: args.region,
+        drawn: None,
+        usage: debug.then(ProcessUsage::new),
+        scroll_started: args.scroll.then(Instant::now),
+    };
+    // With --deterministic, time moves by exactly one delay per iteration
+    let mut clock: Box<dyn Clock> = if args.deterministic {
+        Box::new(MockClock::new())
+    } else {
+        Box::new(SystemClock)
+    };
+    let deadline = (args.duration > 0).then(|| clock.now() + Duration::from_secs(args.duration));
+
+    let attract_interval = Duration::try_from_secs_f64(args.attract_interval)
+        .ok()
+        .filter(|interval| !interval.is_zero())
+        .ok_or_else(|| Failure::new(ExitCode::Other, "--attract-interval must be a positive number of seconds"))?;
+    let mut next_attract = clock.now() + attract_interval;
+
+    // The pets are saved however the run ends, a failing handler or a panic
+    // shouldn't lose their progress, even while the others are starting
+    let result = panic::catch_unwind(AssertUnwindSafe(|| -> Result<(), Failure> {
+        for pet in pets {
+            runtimes.push(start_runtime(pet, saves_dir, pets_dir, args, debug, &bus, color_depth, output_is_tty)?);
+        }
+
+        if args.pause_on_blur {
+            out.execute(EnableFocusChange).map_err(Failure::terminal)?;
+        }
+        if enhanced_keys {
+            out.execute(PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES))
+                .map_err(Failure::terminal)?;
+        }
+
+        while running.load(Ordering::SeqCst) {
+            let now = clock.now();
 
-            if current_frame == anim.frames.len() - 1 && anim.name != state.metadata.animation {
-                current_anim = state.metadata.animation.clone();
-                current_frame = 0;
+            if deadline.is_some_and(|deadline| now >= deadline) {
+                break;
             }
 
-            current_frame = next_frame(&current_frame, anim);
-            last_render = now;
-        }
+            let halted = paused || confirming_quit;
 
-        if state.event_handlers.update.is_some() && now.duration_since(last_update).as_millis() >= state.metadata.update_delay.into() {
-            if let Some(f) = &state.event_handlers.update {
-                f.call::<(), ()>(())
-                    .map_err(|e|
-                        format!("The pet's update function failed: '{e}'"))?;
+            if args.attract && !halted && now >= next_attract {
+                for runtime in &mut runtimes {
+                    runtime.next_demo_state()
+                        .map_err(|e| Failure::pet(e, "Switching to the next state failed"))?;
+                }
+                next_attract = now + attract_interval;
             }
 
-            last_update = now;
-        }
+            if !halted {
+                let messages = bus.borrow_mut().drain();
+                for message in messages {
+                    let Some(runtime) = runtimes.iter_mut().find(|r| r.id() == message.target) else {
+                        continue;
+                    };
+
+                    runtime.receive(message)
+                        .map_err(|e| Failure::pet(e, "The pet's message handler failed"))?;
+                }
+
+                let mut frame_changed = false;
+                for runtime in &mut runtimes {
+                    frame_changed |= runtime.tick(now)
+                        .map_err(|e| Failure::pet(e, "The pet's update function failed"))?;
+                }
+
+                // Pets that asked for another pet with load_pet are replaced by it
+                for runtime in &mut runtimes {
+                    let Some(pet) = runtime.take_loaded_pet() else {
+                        continue;
+                    };
+                    let pet = detached_luas.attach(pet);
+
+                    runtime.stop_music();
+                    runtime.save()
+                        .map_err(|e| Failure::pet(e, "Saving the pet failed"))?;
+                    *runtime = start_runtime(pet, saves_dir, pets_dir, args, debug, &bus, color_depth, output_is_tty)?;
+                    frame_changed = true;
+                }
 
-        if event::poll(Duration::ZERO).unwrap() {
-            if let event::Event::Key(KeyEvent { code, ..}) = event::read().unwrap() {
-                match code {
-                    event::KeyCode::Esc => break,
-                    _ => println!()
+                // Panning moves on its own, draw skips it when nothing did
+                if frame_changed || args.scroll {
+                    draw(out, &mut runtimes, &mut screen, debug).map_err(Failure::terminal)?;
                 }
             }
+
+            // While paused, block on input instead of spinning
+            let timeout = if halted { Duration::from_millis(250) } else { Duration::ZERO };
+
+            if event::poll(timeout).map_err(Failure::terminal)? {
+                match event::read().map_err(Failure::terminal)? {
+                    event::Event::Key(key @ KeyEvent { code, kind, .. }) => match code {
+                        // Releasing the Esc that brought the prompt up doesn't answer it
+                        _ if confirming_quit => match code {
+                            _ if kind == KeyEventKind::Release => {},
+                            event::KeyCode::Char('y' | 'Y') => break,
+                            event::KeyCode::Char('n' | 'N') | event::KeyCode::Esc => {
+                                confirming_quit = false;
+                                runtimes[0].set_prompt(None);
+                                draw(out, &mut runtimes, &mut screen, debug).map_err(Failure::terminal)?;
+                            },
+                            _ => {},
+                        },
+                        // Neither does releasing the Esc that answered it
+                        event::KeyCode::Esc if kind == KeyEventKind::Release => {},
+                        event::KeyCode::Esc if args.confirm_quit || runtimes.iter().any(|runtime| runtime.pet.metadata.confirm_quit) => {
+                            confirming_quit = true;
+                            runtimes[0].set_prompt(Some(QUIT_PROMPT));
+                            draw(out, &mut runtimes, &mut screen, debug).map_err(Failure::terminal)?;
+                        },
+                        event::KeyCode::Esc => break,
+                        _ if args.attract => {},
+                        event::KeyCode::F(5) if args.keep_alive => for runtime in &mut runtimes {
+                            runtime.enable_handlers();
+                        },
+                        _ => for runtime in &mut runtimes {
+                            runtime.handle_key(key)
+                                .map_err(|e| Failure::pet(e, "The pet's key handler failed"))?;
+                        },
+                    },
+                    event::Event::Resize(..) => draw(out, &mut runtimes, &mut screen, debug).map_err(Failure::terminal)?,
+                    event::Event::FocusLost if args.pause_on_blur => paused = true,
+                    event::Event::FocusGained => paused = false,
+                    _ => {},
+                }
+            }
+
+            if !halted {
+                sleep(delay);
+                clock.advance(delay);
+            }
         }
 
-        sleep(delay);
-    };
+        Ok(())
+    }));
 
-    // Cleanup
-    stdout.execute(cursor::Show).unwrap();
-    disable_raw_mode().expect("Failed to disable raw mode");
+    // Saved before anything about the terminal is restored
+    let saved = save_pets(&mut runtimes);
+
+    let mut restored = Ok(());
+    if args.pause_on_blur {
+        restored = restored.and(out.execute(DisableFocusChange).map(|_| ()));
+    }
+    if enhanced_keys {
+        restored = restored.and(out.execute(PopKeyboardEnhancementFlags).map(|_| ()));
+    }
+
+    let result = result.unwrap_or_else(|panic| panic::resume_unwind(panic));
+    restored.map_err(Failure::terminal)?;
+
+    result.and(saved)
+}
+
+// Saves every pet even when one of them fails to, the first failure is returned
+fn save_pets(runtimes: &mut [PetRuntime]) -> Result<(), Failure> {
+    let mut result = Ok(());
+
+    for runtime in runtimes {
+        runtime.stop_music();
+        if let Err(e) = runtime.save() {
+            log::error!("{}: saving failed: {e}", runtime.id());
+            if result.is_ok() {
+                result = Err(Failure::pet(e, "Saving the pet failed"));
+            }
+        }
+    }
+
+    result
+}
+
+// Sets a pet up to run alongside the others, with its progress restored
+#[allow(clippy::too_many_arguments)]
+fn start_runtime<'lua>(pet: Pet<'lua>, saves_dir: &Path, pets_dir: &Path, args: &RunArgs, debug: bool, bus: &Rc<RefCell<MessageBus>>,
+    color_depth: ColorDepth, output_is_tty: bool) -> Result<PetRuntime<'lua>, Failure> {
+    let pet_id = pet.path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+    let save = SaveFile::load(&saves_dir.join(format!("{pet_id}.toml")))
+        .map_err(|e| Failure::pet(e, "Loading the pet's save file failed"))?;
+
+    let mut runtime = PetRuntime::with_save(pet, Some(save))
+        .map_err(|e| Failure::pet(e, "Starting the pet failed"))?;
+    runtime.join_bus(bus);
+    runtime.set_speed(args.speed);
+    runtime.set_keep_alive(args.keep_alive);
+    runtime.set_color_depth(color_depth);
+    runtime.set_output_is_tty(output_is_tty);
+    runtime.set_viewport(match (args.region, args.viewport) {
+        (Some(region), _) => Some((region.width, region.height)),
+        (None, Some(viewport)) => Some((viewport.cols, viewport.rows)),
+        (None, None) => None,
+    });
+
+    if let Some(seed) = args.seed.or(args.deterministic.then_some(0)) {
+        runtime.set_seed(seed).map_err(|e| Failure::pet(e, "Seeding the pet failed"))?;
+    }
+    if args.deterministic {
+        runtime.use_virtual_time().map_err(|e| Failure::pet(e, "Starting the virtual clock failed"))?;
+    }
+
+    runtime.set_pets_dir(Some(pets_dir.to_path_buf()));
+    runtime.set_load_options(load_options(args, debug));
+    runtime.set_launch_args(launch_args(&pet_id, args, debug));
+
+    Ok(runtime)
+}
+
+// What get_args shows the pet, flags that weren't given are left out
+fn launch_args(pet_id: &str, args: &RunArgs, debug: bool) -> Vec<(String, Payload)> {
+    let mut launch_args = vec![
+        ("pet".to_string(), Payload::String(pet_id.to_string())),
+        ("debug".to_string(), Payload::Bool(debug)),
+        ("speed".to_string(), Payload::Number(args.speed)),
+        ("duration".to_string(), Payload::Integer(args.duration as i64)),
+        ("deterministic".to_string(), Payload::Bool(args.deterministic)),
+        ("keep_alive".to_string(), Payload::Bool(args.keep_alive)),
+        ("attract".to_string(), Payload::Bool(args.attract)),
+        ("pause_on_blur".to_string(), Payload::Bool(args.pause_on_blur)),
+    ];
+    if let Some(seed) = args.seed {
+        launch_args.push(("seed".to_string(), Payload::Integer(seed as i64)));
+    }
+    if let Some(viewport) = args.viewport {
+        launch_args.push(("viewport".to_string(), Payload::String(format!("{}x{}", viewport.cols, viewport.rows))));
+    }
+    if let Some(region) = args.region {
+        launch_args.push(("region".to_string(),
+            Payload::String(format!("{},{},{},{}", region.x, region.y, region.width, region.height))));
+    }
+
+    launch_args
+}
+
+fn run_preview(out: &mut impl Write, pet: &Pet, line_ending: LineEnding, running: &AtomicBool) -> Result<(), Failure> {
+    let mut page = 0;
+    let mut redraw = true;
+
+    while running.load(Ordering::SeqCst) {
+        let (cols, rows) = terminal::size().map_err(Failure::terminal)?;
+        // Leave a row for the footer
+        let pages = preview_pages(pet, cols.into(), usize::from(rows).saturating_sub(1));
+        page = page.min(pages.len().saturating_sub(1));
+
+        if redraw {
+            clear(out).map_err(Failure::terminal)?;
+            write_lines(out, pages.get(page).into_iter().flatten(), line_ending).map_err(Failure::terminal)?;
+            execute!(out, MoveTo(0, rows.saturating_sub(1))).map_err(Failure::terminal)?;
+            write!(out, "Page {}/{} - Left/Right to switch pages, Esc to quit",
+                page + 1, pages.len().max(1)).map_err(Failure::terminal)?;
+            out.flush().map_err(Failure::terminal)?;
+            redraw = false;
+        }
+
+        if !event::poll(Duration::from_millis(100)).map_err(Failure::terminal)? {
+            continue;
+        }
+
+        match event::read().map_err(Failure::terminal)? {
+            event::Event::Key(KeyEvent { code, .. }) => match code {
+                event::KeyCode::Esc => break,
+                event::KeyCode::Left | event::KeyCode::PageUp => {
+                    page = page.saturating_sub(1);
+                    redraw = true;
+                },
+                event::KeyCode::Right | event::KeyCode::PageDown => {
+                    page += 1;
+                    redraw = true;
+                },
+                _ => {},
+            },
+            event::Event::Resize(..) => redraw = true,
+            _ => {},
+        }
+    }
 
     Ok(())
 }
 
-fn next_frame(frame: &usize, animation: &Animation) -> usize {
-    if *frame < animation.frames.len() - 1 {
-        frame + 1
-    } else {
-        0
+// Shows one frame at a time, no timing and no scripts
+fn run_inspect(out: &mut impl Write, anim: &Animation, line_ending: LineEnding, running: &AtomicBool) -> Result<(), Failure> {
+    let mut frame = anim.metadata.start_frame;
+    let mut redraw = true;
+
+    while running.load(Ordering::SeqCst) {
+        if redraw {
+            let (_, rows) = terminal::size().map_err(Failure::terminal)?;
+            let content = &anim.frames[frame];
+
+            clear(out).map_err(Failure::terminal)?;
+            write_lines(out, content.lines(), line_ending).map_err(Failure::terminal)?;
+            execute!(out, MoveTo(0, rows.saturating_sub(1))).map_err(Failure::terminal)?;
+            write!(out, "{} frame {}/{} ({}x{}) - Left/Right to step, Esc to quit",
+                anim.name, frame, anim.frames.len() - 1,
+                frame_width(content), content.lines().count()).map_err(Failure::terminal)?;
+            out.flush().map_err(Failure::terminal)?;
+            redraw = false;
+        }
+
+        if !event::poll(Duration::from_millis(100)).map_err(Failure::terminal)? {
+            continue;
+        }
+
+        match event::read().map_err(Failure::terminal)? {
+            event::Event::Key(KeyEvent { code, .. }) => match code {
+                event::KeyCode::Esc => break,
+                event::KeyCode::Left => {
+                    frame = frame.checked_sub(1).unwrap_or(anim.frames.len() - 1);
+                    redraw = true;
+                },
+                event::KeyCode::Right => {
+                    frame = (frame + 1) % anim.frames.len();
+                    redraw = true;
+                },
+                _ => {},
+            },
+            event::Event::Resize(..) => redraw = true,
+            _ => {},
+        }
     }
+
+    Ok(())
 }
 
 fn get_config_dir() -> Result<PathBuf, String> {
@@ -169,4 +728,3 @@ fn get_config_dir() -> Result<PathBuf, String> {
         Err("BaseDirs couldn't be instantiated".to_string())
     }
 }
-
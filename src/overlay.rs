@@ -0,0 +1,122 @@
+use std::collections::BTreeMap;
+
+// A layer of characters scripts draw on, shown over the pet's frame. It's
+// in terminal coordinates and stays until it's cleared.
+#[derive(Debug, Default, Clone)]
+pub struct Overlay {
+    cells: BTreeMap<(u16, u16), char>,
+    bounds: Option<(u16, u16)>,
+    changed: bool,
+    // Goes up with every change, unlike `changed` it's never reset
+    revision: u64,
+}
+
+impl Overlay {
+    // Points outside of `size` are dropped instead of drawn
+    pub fn set_bounds(&mut self, size: Option<(u16, u16)>) {
+        self.bounds = size;
+    }
+
+    pub fn cells(&self) -> impl Iterator<Item = ((u16, u16), char)> + '_ {
+        self.cells.iter().map(|(&position, &c)| (position, c))
+    }
+
+    pub fn get(&self, x: u16, y: u16) -> Option<char> {
+        self.cells.get(&(x, y)).copied()
+    }
+
+    pub fn clear(&mut self) {
+        if !self.cells.is_empty() {
+            self.changed = true;
+            self.revision += 1;
+        }
+        self.cells.clear();
+    }
+
+    // Whether anything was drawn or cleared since the last call
+    pub fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub fn point(&mut self, x: i64, y: i64, c: char) {
+        let (Ok(x), Ok(y)) = (u16::try_from(x), u16::try_from(y)) else {
+            return;
+        };
+        if self.bounds.is_some_and(|(w, h)| x >= w || y >= h) {
+            return;
+        }
+
+        if self.cells.insert((x, y), c) != Some(c) {
+            self.changed = true;
+            self.revision += 1;
+        }
+    }
+
+    // Bresenham's line, both ends included
+    pub fn line(&mut self, from: (i64, i64), to: (i64, i64), c: char) {
+        let (x0, y0) = clamp_point(from);
+        let (x1, y1) = clamp_point(to);
+
+        let dx = (x1 - x0).abs();
+        let dy = -(y1 - y0).abs();
+        let step_x = if x0 < x1 { 1 } else { -1 };
+        let step_y = if y0 < y1 { 1 } else { -1 };
+        let mut error = dx + dy;
+        let (mut x, mut y) = (x0, y0);
+
+        loop {
+            self.point(x, y, c);
+            if x == x1 && y == y1 {
+                break;
+            }
+
+            let doubled = 2 * error;
+            if doubled >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if doubled <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+    }
+
+    pub fn hline(&mut self, x: i64, y: i64, length: i64, c: char) {
+        if length > 0 {
+            self.line((x, y), (x.saturating_add(length - 1), y), c);
+        }
+    }
+
+    pub fn vline(&mut self, x: i64, y: i64, length: i64, c: char) {
+        if length > 0 {
+            self.line((x, y), (x, y.saturating_add(length - 1)), c);
+        }
+    }
+
+    // The outline of a rectangle
+    pub fn rect(&mut self, x: i64, y: i64, width: i64, height: i64, c: char) {
+        if width <= 0 || height <= 0 {
+            return;
+        }
+
+        let right = x.saturating_add(width - 1);
+        let bottom = y.saturating_add(height - 1);
+
+        self.hline(x, y, width, c);
+        self.hline(x, bottom, width, c);
+        self.vline(x, y, height, c);
+        self.vline(right, y, height, c);
+    }
+}
+
+// No terminal is this big, and it keeps absurd lines from taking forever
+const MAX_COORDINATE: i64 = 1 << 16;
+
+fn clamp_point((x, y): (i64, i64)) -> (i64, i64) {
+    (x.clamp(-MAX_COORDINATE, MAX_COORDINATE), y.clamp(-MAX_COORDINATE, MAX_COORDINATE))
+}
@@ -5,21 +5,38 @@ use std::{
     io,
     fmt::Display,
     path::{
+        Component,
         Path,
         PathBuf
-    }
+    },
+    rc::Rc,
+    time::{Duration, Instant},
 };
-use serde::{Deserialize, Serialize};
-use mlua::{Function, Lua};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use mlua::{Function, Lua, Table};
+
+use crate::{color::parse_hex, require, text::{frame_width, grapheme_width, graphemes}};
 
 #[derive(Debug)]
 pub enum Error {
     IO(std::io::Error),
     TomlDeserializer(toml::de::Error),
+    TomlSerializer(toml::ser::Error),
+    JsonDeserializer(serde_json::Error),
     Utf8(OsString),
     InvalidFileName,
     Lua(mlua::Error),
     InvalidObject(&'static str),
+    // The kind of object ("state") and its name
+    MissingMetadata(&'static str, String),
+    // The animation's name and the files in its directory, none of which
+    // are named like a frame
+    NoFrames(String, Vec<String>),
+    // The animation's name and the frame numbers missing between its frames,
+    // an error only with --strict
+    FrameGap(String, Vec<usize>),
+    // The bytes of frames found so far and the most that may be loaded
+    TooManyFrameBytes(u64, u64),
 }
 
 impl Display for Error {
@@ -27,42 +44,185 @@ impl Display for Error {
         write!(f, "{}", match self {
             Self::IO(e) => format!("IO Error: {e}"),
             Self::TomlDeserializer(e) => format!("Toml deserialization error: {e}"),
+            Self::TomlSerializer(e) => format!("Toml serialization error: {e}"),
+            Self::JsonDeserializer(e) => format!("Json deserialization error: {e}"),
             Self::Utf8(s) => format!("Utf8 conversion error: Not a valid UTF8 string: {}", s.to_string_lossy()),
             Self::InvalidFileName => "Invalid file name".to_string(),
             Self::Lua(e) => format!("Lua error: {e}"),
             Self::InvalidObject(msg) => format!("Invalid object: {msg}"),
+            Self::MissingMetadata(kind, name) => format!("Invalid object: {kind} '{name}' is missing meta.toml"),
+            Self::NoFrames(name, files) if files.is_empty() =>
+                format!("Invalid object: animation '{name}' contains no frames"),
+            Self::NoFrames(name, files) =>
+                format!("Invalid object: animation '{name}' contains no frames, they're named 0.txt, 1.txt... but it has {}",
+                    files.join(", ")),
+            Self::FrameGap(name, missing) =>
+                format!("Invalid object: animation '{name}' has no frame {}", join_numbers(missing)),
+            Self::TooManyFrameBytes(bytes, limit) =>
+                format!("Invalid object: the frames found so far add up to {bytes} bytes, more than the {limit} allowed"),
         })
     }
 }
 
+fn join_numbers(numbers: &[usize]) -> String {
+    numbers.iter().map(usize::to_string).collect::<Vec<_>>().join(", ")
+}
+
+// Metadata can be written as meta.toml or meta.json, TOML wins if there are both
+pub fn metadata_path(dir: &Path) -> PathBuf {
+    let toml_path = dir.join("meta.toml");
+    let json_path = dir.join("meta.json");
+
+    match (toml_path.exists(), json_path.exists()) {
+        (true, true) => {
+            log::warn!("{} has both meta.toml and meta.json, using meta.toml", dir.display());
+            toml_path
+        },
+        (false, true) => json_path,
+        _ => toml_path,
+    }
+}
+
+// Parses a metadata file by its extension
+fn parse_metadata<T: DeserializeOwned>(path: &Path) -> Result<T, Error> {
+    let string = fs::read_to_string(path).map_err(Error::IO)?;
+
+    if path.extension().is_some_and(|ext| ext == "json") {
+        serde_json::from_str(&string).map_err(Error::JsonDeserializer)
+    } else {
+        toml::de::from_str(&string).map_err(Error::TomlDeserializer)
+    }
+}
+
 #[derive(Deserialize, Debug)]
 pub struct AnimationMetadata {
+    // Milliseconds per frame, can be given as `fps` instead. Read by
+    // RawAnimationMetadata, which knows about both.
+    #[serde(skip_deserializing, default = "default_delay")]
     pub delay: u64,
+    // The frame the animation starts on when it's switched to
+    #[serde(default)]
+    pub start_frame: usize,
+    // Sounds played when a frame is reached, keyed by frame index
+    #[serde(default)]
+    pub frame_sounds: HashMap<String, String>,
+    // Names for frames scripts can check for, keyed by frame index
+    #[serde(default)]
+    pub frame_tags: HashMap<String, Vec<String>>,
+    // Expand tabs to the next multiple of this many columns
+    #[serde(default)]
+    pub expand_tabs: Option<usize>,
+    // Per-frame [x, y] offsets from the pet's position
+    #[serde(default)]
+    pub frame_offsets: Vec<(i32, i32)>,
+    // Overlay frames are drawn over what's on screen without clearing it,
+    // `transparent` characters leave the cell below untouched
+    #[serde(default)]
+    pub overlay: bool,
+    #[serde(default = "default_transparent")]
+    pub transparent: char,
+    #[serde(default)]
+    pub baseline: Baseline,
+    // The color the frames are drawn in, as "#rrggbb"
+    #[serde(default)]
+    pub color: Option<String>,
+    // The state to go to once the animation has shown its last frame,
+    // instead of going back to the state's animation. The state's animation
+    // itself loops, so this does nothing there.
+    #[serde(default)]
+    pub on_complete_state: Option<String>,
 }
 
-impl AnimationMetadata {
-    pub fn load(path: &Path) -> Result<AnimationMetadata, Error> {
-        let toml_string = match fs::read_to_string(path) {
-            Ok(val) => val,
-            Err(e) => return Err(Error::IO(e)),
-        };
+// Which edge frames of different heights line up on
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Baseline {
+    #[default]
+    Top,
+    Bottom,
+}
+
+// For animations that don't give one
+pub const DEFAULT_DELAY: u64 = 100;
 
-        match toml::de::from_str::<Self>(toml_string.as_str()) {
-            Ok(val) => Ok(val),
-            Err(e) => Err(Error::TomlDeserializer(e)),
+fn default_delay() -> u64 {
+    DEFAULT_DELAY
+}
+
+fn default_transparent() -> char {
+    ' '
+}
+
+impl Default for AnimationMetadata {
+    fn default() -> Self {
+        Self {
+            delay: DEFAULT_DELAY,
+            start_frame: 0,
+            frame_sounds: HashMap::new(),
+            frame_tags: HashMap::new(),
+            expand_tabs: None,
+            frame_offsets: Vec::new(),
+            overlay: false,
+            transparent: default_transparent(),
+            baseline: Baseline::default(),
+            color: None,
+            on_complete_state: None,
         }
     }
 }
 
+// The metadata as it's written, with the two ways of giving the animation's
+// speed left to be turned into a `delay`
+#[derive(Deserialize)]
+struct RawAnimationMetadata {
+    delay: Option<u64>,
+    fps: Option<f64>,
+    #[serde(flatten)]
+    metadata: AnimationMetadata,
+}
+
+impl TryFrom<RawAnimationMetadata> for AnimationMetadata {
+    type Error = Error;
+
+    fn try_from(raw: RawAnimationMetadata) -> Result<Self, Error> {
+        let delay = match (raw.delay, raw.fps) {
+            (Some(_), Some(_)) => return Err(Error::InvalidObject("An animation can't have both delay and fps")),
+            (None, Some(fps)) if !(fps > 0.0 && fps.is_finite()) => return Err(Error::InvalidObject("fps must be positive")),
+            (None, Some(fps)) => (1000.0 / fps).round().max(1.0) as u64,
+            (delay, None) => delay.unwrap_or(DEFAULT_DELAY),
+        };
+
+        Ok(AnimationMetadata { delay, ..raw.metadata })
+    }
+}
+
+impl AnimationMetadata {
+    pub fn load(path: &Path) -> Result<AnimationMetadata, Error> {
+        parse_metadata::<RawAnimationMetadata>(path)?.try_into()
+    }
+}
+
 #[derive(Debug)]
 pub struct Animation {
     pub name: String,
     pub metadata: AnimationMetadata,
+    // Never empty
     pub frames: Vec<String>,
+    pub frame_sounds: HashMap<usize, String>,
+    pub frame_tags: HashMap<usize, Vec<String>>,
+    pub color: Option<(u8, u8, u8)>,
+    // The line count of the tallest frame
+    pub height: usize,
+    // The display width of the widest frame
+    pub width: usize,
 }
 
 impl Animation {
     pub fn load(path: &Path) -> Result<Self, Error> {
+        Self::load_with_options(path, LoadOptions::default())
+    }
+
+    pub fn load_with_options(path: &Path, options: LoadOptions) -> Result<Self, Error> {
         let name = path.file_name()
             .and_then(|f| f.to_str())
             .ok_or_else(||
@@ -71,38 +231,195 @@ impl Animation {
             .to_string();
 
 
-        let metadata = AnimationMetadata::load(path.join("meta.toml").as_path())?;
+        // Frames alone are enough for an animation, but it's most likely a mistake
+        let metadata_file = metadata_path(path);
+        let metadata = if metadata_file.exists() {
+            AnimationMetadata::load(&metadata_file)?
+        } else {
+            log::warn!("The animation '{name}' is missing meta.toml, using a delay of {DEFAULT_DELAY}ms");
+            AnimationMetadata::default()
+        };
+        let frame_files = frame_files(path)?;
 
-        let mut frame_files: Vec<_> = fs::read_dir(path)
-            .map_err(Error::IO)?
-            .filter_map(Result::ok)
-            .filter(|entry| {
-                let filename = entry.file_name().to_string_lossy().into_owned();
+        if frame_files.is_empty() {
+            return Err(Error::NoFrames(name, other_files(path)?));
+        }
+
+        // Sparse numbering works, the frames play in order, but it's usually
+        // a frame that was forgotten or misnamed
+        let missing = missing_frame_numbers(&frame_files);
+        if !missing.is_empty() {
+            let gap = Error::FrameGap(name.clone(), missing);
+            if options.strict {
+                return Err(gap);
+            }
+            log::warn!("{gap}");
+        }
+
+        if metadata.frame_offsets.len() > frame_files.len() {
+            return Err(Error::InvalidObject("Animation has more frame_offsets than frames"));
+        }
+
+        if metadata.start_frame >= frame_files.len() {
+            return Err(Error::InvalidObject("Animation start_frame is out of range"));
+        }
 
-                filename.ends_with(".txt") &&
-                    filename.strip_suffix(".txt")
-                        .and_then(|prefix| prefix.parse::<usize>().ok() )
-                        .is_some()
+        let frame_sounds = metadata.frame_sounds.iter()
+            .map(|(frame, sound)| match frame.parse::<usize>() {
+                Ok(i) if i < frame_files.len() => Ok((i, sound.clone())),
+                _ => Err(Error::InvalidObject("Animation frame_sounds refers to an invalid frame")),
             })
-            .collect();
+            .collect::<Result<_, _>>()?;
 
-        frame_files.sort_by_key(|e| e.file_name()
-            .to_string_lossy()
-            .strip_suffix(".txt")
-            .unwrap()
-            .parse::<u32>()
-            .unwrap()
-        );
+        let frame_tags = metadata.frame_tags.iter()
+            .map(|(frame, tags)| match frame.parse::<usize>() {
+                Ok(i) if i < frame_files.len() => Ok((i, tags.clone())),
+                _ => Err(Error::InvalidObject("Animation frame_tags refers to an invalid frame")),
+            })
+            .collect::<Result<_, _>>()?;
 
-        if frame_files.is_empty() {
-            return Err(Error::InvalidObject("Animation contains no frames"));
+        let color = match &metadata.color {
+            Some(hex) => Some(parse_hex(hex)
+                .ok_or(Error::InvalidObject("Animation color must look like \"#rrggbb\""))?),
+            None => None,
+        };
+
+        // By file size, before any of it is read
+        let bytes = frame_bytes(&frame_files)?;
+        if bytes > options.max_frame_bytes {
+            return Err(Error::TooManyFrameBytes(bytes, options.max_frame_bytes));
         }
 
         let frames = frame_files.iter()
-            .map(|entry| fs::read_to_string(entry.path()).map_err(Error::IO))
-            .collect::<Result<_, _>>()?;
+            .map(|(_, entry)| fs::read_to_string(entry.path()).map_err(Error::IO))
+            .map(|frame| match metadata.expand_tabs {
+                Some(tab_width) if tab_width > 0 => frame.map(|f| expand_tabs(&f, tab_width)),
+                _ => frame,
+            })
+            .collect::<Result<Vec<String>, _>>()?;
+
+        let height = frames.iter().map(|f| f.lines().count()).max().unwrap_or(0);
+        let width = frames.iter().map(|f| frame_width(f)).max().unwrap_or(0);
+
+        Ok(Self { name, metadata, frames, frame_sounds, frame_tags, color, height, width })
+    }
+}
+
+// The frame files of an animation in order. Hidden files and editor backups
+// are skipped before anything else, so `0.txt~` or `.#0.txt` can't pass for
+// frames.
+fn frame_files(path: &Path) -> Result<Vec<(usize, fs::DirEntry)>, Error> {
+    let mut frame_files = Vec::new();
+    let mut ignored = Vec::new();
+
+    for entry in fs::read_dir(path).map_err(Error::IO)?.filter_map(Result::ok) {
+        let filename = entry.file_name().to_string_lossy().into_owned();
+
+        if is_editor_artifact(&filename) {
+            ignored.push(filename);
+        } else if let Some(number) = frame_number(&filename) {
+            frame_files.push((number, entry));
+        }
+    }
+
+    if !ignored.is_empty() {
+        ignored.sort();
+        log::debug!("Ignoring hidden and backup files in {}: {}", path.display(), ignored.join(", "));
+    }
+
+    frame_files.sort_by_key(|(number, _)| *number);
+
+    Ok(frame_files)
+}
+
+fn frame_bytes(frame_files: &[(usize, fs::DirEntry)]) -> Result<u64, Error> {
+    frame_files.iter()
+        .map(|(_, entry)| entry.metadata().map(|metadata| metadata.len()).map_err(Error::IO))
+        .sum()
+}
+
+// The numbers up to the last frame's that no frame has
+fn missing_frame_numbers(frame_files: &[(usize, fs::DirEntry)]) -> Vec<usize> {
+    let mut missing = Vec::new();
+    let mut expected = 0;
+
+    for (number, _) in frame_files {
+        missing.extend(expected..*number);
+        expected = number + 1;
+    }
+
+    missing
+}
+
+fn is_editor_artifact(filename: &str) -> bool {
+    filename.starts_with('.') || ["~", ".bak", ".swp"].iter().any(|suffix| filename.ends_with(suffix))
+}
+
+// The number of a frame file like `12.txt`, digits only
+fn frame_number(filename: &str) -> Option<usize> {
+    filename.strip_suffix(".txt")
+        .filter(|number| !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit()))
+        .and_then(|number| number.parse().ok())
+}
 
-        Ok(Self { name, metadata, frames })
+// The files of an animation that aren't its metadata, sorted, to show what
+// was there instead of frames
+fn other_files(path: &Path) -> Result<Vec<String>, Error> {
+    let mut files: Vec<_> = fs::read_dir(path)
+        .map_err(Error::IO)?
+        .filter_map(Result::ok)
+        .filter(|entry| entry.path().is_file())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|name| name != "meta.toml" && name != "meta.json")
+        .collect();
+    files.sort();
+
+    Ok(files)
+}
+
+// Replaces tabs with spaces up to the next tab stop
+pub fn expand_tabs(frame: &str, tab_width: usize) -> String {
+    let mut expanded = String::with_capacity(frame.len());
+    let mut column = 0;
+
+    for grapheme in graphemes(frame) {
+        match grapheme {
+            "\t" => {
+                let spaces = tab_width - column % tab_width;
+                expanded.extend(std::iter::repeat_n(' ', spaces));
+                column += spaces;
+            },
+            // "\r\n" is a single grapheme
+            _ if grapheme.ends_with('\n') => {
+                expanded.push_str(grapheme);
+                column = 0;
+            },
+            _ => {
+                expanded.push_str(grapheme);
+                column += grapheme_width(grapheme);
+            },
+        }
+    }
+
+    expanded
+}
+
+impl Animation {
+    // Frames without an offset aren't moved
+    pub fn frame_offset(&self, frame: usize) -> (i32, i32) {
+        self.metadata.frame_offsets.get(frame).copied().unwrap_or((0, 0))
+    }
+
+    // How far a frame is moved down to sit on the animation's baseline
+    pub fn baseline_offset(&self, frame: usize) -> usize {
+        match self.metadata.baseline {
+            Baseline::Top => 0,
+            Baseline::Bottom => self.height - self.frames[frame].lines().count(),
+        }
+    }
+
+    pub fn frame_has_tag(&self, frame: usize, tag: &str) -> bool {
+        self.frame_tags.get(&frame).is_some_and(|tags| tags.iter().any(|t| t == tag))
     }
 }
 
@@ -110,146 +427,516 @@ impl Animation {
 pub struct StateMetadata {
     pub animation: String,
     pub update_delay: u64,
+    // Animations to pick from by weight whenever an animation finishes
+    #[serde(default)]
+    pub idle_anims: Vec<(String, f64)>,
+    // States to pick from by weight once the pet has been left alone in this
+    // one for idle_timeout_ms, a key press starts the wait over
+    #[serde(default)]
+    pub idle_next: Vec<(String, f64)>,
+    #[serde(default)]
+    pub idle_timeout_ms: Option<u64>,
+    // A sound looped while the pet is in the state
+    #[serde(default)]
+    pub music: Option<String>,
+    // Key names (as scripts get them) and the state they switch to. The
+    // switch is queued before this state's `Key_down` runs and applied after
+    // it, so the handler still sees the key and can go somewhere else.
+    #[serde(default)]
+    pub input_transitions: HashMap<String, String>,
+    // Whether --attract tours this state
+    #[serde(default = "default_demo")]
+    pub demo: bool,
+    // Experimental states are only loaded with --debug
+    #[serde(default)]
+    pub debug_only: bool,
+}
+
+fn default_demo() -> bool {
+    true
 }
 
 impl StateMetadata {
     pub fn load(path: &Path) -> Result<Self, Error> {
-        let toml_string = fs::read_to_string(path)
-            .map_err(Error::IO)?;
+        let metadata: Self = parse_metadata(path)?;
+
+        if metadata.idle_anims.iter().any(|(_, weight)| !(*weight > 0.0 && weight.is_finite())) {
+            return Err(Error::InvalidObject("idle_anims weights must be positive"));
+        }
+
+        if metadata.idle_next.iter().any(|(_, weight)| !(*weight > 0.0 && weight.is_finite())) {
+            return Err(Error::InvalidObject("idle_next weights must be positive"));
+        }
+
+        if !metadata.idle_next.is_empty() && metadata.idle_timeout_ms.is_none() {
+            return Err(Error::InvalidObject("idle_next needs an idle_timeout_ms"));
+        }
 
-        toml::de::from_str(&toml_string).map_err(Error::TomlDeserializer)
+        Ok(metadata)
     }
 }
 
+// The functions a state's script can define. All of them are optional, a
+// state without `Init` or `Update` just doesn't react to those events.
 #[derive(Debug)]
 pub struct StateEventHandlers<'lua> {
     pub init: Option<Function<'lua>>,
     pub update: Option<Function<'lua>>,
     pub key_down: Option<Function<'lua>>,
     pub key_up: Option<Function<'lua>>,
+    // Run as a coroutine that can `wait(ms)`
+    pub main: Option<Function<'lua>>,
+    pub message_received: Option<Function<'lua>>,
+    // Called with the animation's name when it wraps back to its first frame
+    pub anim_loop: Option<Function<'lua>>,
 }
 
 impl<'lua> StateEventHandlers<'lua> {
-    pub fn get_from(lua: &'lua Lua) -> Self {
-        let globals = lua.globals();
+    pub fn get_from(env: &Table<'lua>) -> Self {
+        Self {
+            init: env.raw_get("Init").ok(),
+            update: env.raw_get("Update").ok(),
+            key_down: env.raw_get("Key_down").ok(),
+            key_up: env.raw_get("Key_up").ok(),
+            main: env.raw_get("Main").ok(),
+            message_received: env.raw_get("Message_received").ok(),
+            anim_loop: env.raw_get("Anim_loop").ok(),
+        }
+    }
+}
+
+// The functions the pet-level global.lua can define, called no matter the state
+#[derive(Debug, Default)]
+pub struct GlobalEventHandlers<'lua> {
+    // Called every `global_tick_delay`
+    pub tick: Option<Function<'lua>>,
+}
 
+impl<'lua> GlobalEventHandlers<'lua> {
+    pub fn get_from(env: &Table<'lua>) -> Self {
         Self {
-            init: globals.get("Init").ok(),
-            update: globals.get("Update").ok(),
-            key_down: globals.get("Key_down").ok(),
-            key_up: globals.get("Key_up").ok(),
+            tick: env.raw_get("Tick").ok(),
         }
     }
+
+    // global.lua is optional. It runs in the real globals, so the helpers
+    // it defines can be used by every state.
+    pub fn load(lua: &'lua Lua, path: &Path) -> Result<Self, Error> {
+        let script_path = path.join("global.lua");
+        if !script_path.exists() {
+            return Ok(Self::default());
+        }
+
+        let lua_script = fs::read_to_string(&script_path).map_err(Error::IO)?;
+        lua.load(&lua_script)
+            .set_name("global")
+            .exec()
+            .map_err(Error::Lua)?;
+
+        let globals = lua.globals();
+        let handlers = Self::get_from(&globals);
+        // States shouldn't see Tick as their own global
+        globals.raw_set("Tick", mlua::Value::Nil).map_err(Error::Lua)?;
+
+        Ok(handlers)
+    }
 }
 
 #[derive(Debug)]
 pub struct State<'lua> {
+    // The state's directory, in a base pet's directory if it's inherited
+    pub path: PathBuf,
     pub metadata: StateMetadata,
     pub event_handlers: StateEventHandlers<'lua>,
-
-    pub init_function: Function<'lua>,
-    pub update_function: Function<'lua>,
 }
 
 impl<'lua> State<'lua> {
     pub fn load(lua: &'lua Lua, path: &Path) -> Result<Self, Error> {
-        let metadata = StateMetadata::load(path.join("meta.toml").as_path())?;
+        Self::load_timed(lua, path).map(|(state, _)| state)
+    }
 
+    // Also returns how long the script took to run
+    fn load_timed(lua: &'lua Lua, path: &Path) -> Result<(Self, Duration), Error> {
         let name = path.file_name()
             .and_then(|f| f.to_str())
             .ok_or_else(|| Error::InvalidFileName)?;
 
+        let metadata_file = metadata_path(path);
+        if !metadata_file.exists() {
+            return Err(Error::MissingMetadata("state", name.to_string()));
+        }
+        let metadata = StateMetadata::load(&metadata_file)?;
+
         let lua_script = fs::read_to_string(path.join("state.lua")).map_err(Error::IO)?;
 
+        // Every state gets its own environment so that states don't clobber
+        // each other's handlers. Lookups fall through to the real globals.
+        let env = lua.create_table().map_err(Error::Lua)?;
+        let env_meta = lua.create_table().map_err(Error::Lua)?;
+        env_meta.set("__index", lua.globals()).map_err(Error::Lua)?;
+        env.set_metatable(Some(env_meta));
+
+        let started = Instant::now();
         lua.load(&lua_script)
             .set_name(name)
+            .set_environment(env.clone())
             .exec()
             .map_err(Error::Lua)?;
+        let script_time = started.elapsed();
 
-        let init_function: Function = lua.globals().get("Init").map_err(Error::Lua)?;
-        let update_function: Function = lua.globals().get("Update").map_err(Error::Lua)?;
-
-        let event_handlers = StateEventHandlers::get_from(lua);
+        let event_handlers = StateEventHandlers::get_from(&env);
 
-        Ok(Self{ metadata, event_handlers, init_function, update_function })
+        Ok((Self{ path: path.to_path_buf(), metadata, event_handlers }, script_time))
     }
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct PetMetadata {
     pub name: String,
     pub description: String,
     pub default_state: String,
     pub global_tick_delay: u64,
+    // Start in the state the pet was in when it was last closed
+    #[serde(default)]
+    pub persist_state: bool,
+    // The name of a pet to inherit animations and states from
+    #[serde(default)]
+    pub extends: Option<String>,
+    // Where the pet is drawn in its part of the terminal
+    #[serde(default)]
+    pub anchor: Anchor,
+    // Pets with a higher z are drawn over ones with a lower z, ties go by
+    // the order they're given in
+    #[serde(default)]
+    pub z: i32,
+    // Start out mirrored, scripts can change it with `set_flip`
+    #[serde(default)]
+    pub flip: bool,
+    // Characters swapped when mirroring, each entry is a pair like "()"
+    #[serde(default = "default_mirror_pairs")]
+    pub mirror_pairs: Vec<String>,
+    // Ask before quitting with Esc, for pets that have been raised a while
+    #[serde(default)]
+    pub confirm_quit: bool,
+}
+
+fn default_mirror_pairs() -> Vec<String> {
+    ["/\\", "()", "<>", "[]", "{}"].map(String::from).to_vec()
+}
+
+impl PetMetadata {
+    // Both ways, so a pair only has to be listed once
+    pub fn mirror_map(&self) -> Result<HashMap<char, char>, Error> {
+        let mut map = HashMap::new();
+
+        for pair in &self.mirror_pairs {
+            let (a, b) = match pair.chars().collect::<Vec<_>>()[..] {
+                [a, b] => (a, b),
+                _ => return Err(Error::InvalidObject("mirror_pairs entries must be two characters")),
+            };
+
+            map.insert(a, b);
+            map.insert(b, a);
+        }
+
+        Ok(map)
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Anchor {
+    #[default]
+    TopLeft,
+    // Every frame's bounding box is centered
+    Center,
 }
 
 impl PetMetadata {
     pub fn load(path: PathBuf) -> Result<Self, Error> {
-        let toml_string = match fs::read_to_string(path) {
-            Ok(val) => val,
+        parse_metadata(&path)
+    }
+}
+
+// Pets are named after their directory in a pets directory, so a name can't
+// be a path that leads anywhere else
+pub fn is_valid_pet_name(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+
+    !name.contains(['/', '\\'])
+        && matches!((components.next(), components.next()), (Some(Component::Normal(_)), None))
+}
+
+// Whether `path` is in `pets_dir` once symlinks are followed, a pet's
+// directory could still lead out of it otherwise
+pub fn is_inside(pets_dir: &Path, path: &Path) -> bool {
+    path.canonicalize().ok()
+        .zip(pets_dir.canonicalize().ok())
+        .is_some_and(|(path, root)| path.starts_with(root))
+}
+
+// The pets in `pets_dir` by directory name, sorted, with their metadata or
+// why it couldn't be read. A missing directory has no pets.
+pub fn installed_pets(pets_dir: &Path) -> Vec<(String, Result<PetMetadata, Error>)> {
+    pet_dirs(pets_dir)
+        .into_iter()
+        .map(|(name, path)| (name, PetMetadata::load(metadata_path(&path))))
+        .collect()
+}
+
+// The subdirectories of `pets_dir` by name, sorted
+pub(crate) fn pet_dirs(pets_dir: &Path) -> Vec<(String, PathBuf)> {
+    let Ok(entries) = fs::read_dir(pets_dir) else {
+        return Vec::new();
+    };
+
+    let mut dirs: Vec<_> = entries
+        .filter_map(Result::ok)
+        .filter(|entry| entry.path().is_dir())
+        .map(|entry| (entry.file_name().to_string_lossy().into_owned(), entry.path()))
+        .collect();
+    dirs.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    dirs
+}
+
+// The pet's directory followed by the ones of the pets it extends, nearest
+// first. Base pets are looked up next to the pet.
+fn inheritance_chain(path: &Path, metadata: &PetMetadata) -> Result<Vec<PathBuf>, Error> {
+    let mut chain = vec![path.canonicalize().map_err(Error::IO)?];
+    let mut extends = metadata.extends.clone();
+
+    while let Some(base) = extends {
+        if !is_valid_pet_name(&base) {
+            return Err(Error::InvalidObject("extends must be the name of a pet"));
+        }
+
+        let base_path = chain[chain.len() - 1].parent()
+            .ok_or(Error::InvalidFileName)?
+            .join(&base)
+            .canonicalize()
+            .map_err(Error::IO)?;
+
+        if chain.contains(&base_path) {
+            return Err(Error::InvalidObject("The pets extend each other in a cycle"));
+        }
+
+        extends = PetMetadata::load(metadata_path(&base_path))?.extends;
+        chain.push(base_path);
+    }
+
+    Ok(chain)
+}
+
+// The subdirectories of `kind` ("anim" or "state") by name over the whole
+// chain, with the nearest pet's winning. A pet doesn't need to have any.
+fn layered_dirs(chain: &[PathBuf], kind: &str) -> Result<Vec<(String, PathBuf)>, Error> {
+    let mut dirs = HashMap::new();
+
+    for pet_dir in chain.iter().rev() {
+        let entries = match fs::read_dir(pet_dir.join(kind)) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
             Err(e) => return Err(Error::IO(e)),
         };
 
-        match toml::de::from_str(toml_string.as_str()) {
-            Ok(val) => Ok(val),
-            Err(e) => Err(Error::TomlDeserializer(e)),
+        for entry in entries.filter_map(Result::ok).filter(|entry| entry.path().is_dir()) {
+            let name = entry.file_name().into_string().map_err(Error::Utf8)?;
+            dirs.insert(name, entry.path());
         }
     }
+
+    Ok(dirs.into_iter().collect())
+}
+
+// How long the parts of loading a pet took. Running scripts counts as Lua,
+// not as states.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LoadTimings {
+    pub animations: Duration,
+    pub states: Duration,
+    pub lua: Duration,
+}
+
+impl LoadTimings {
+    pub fn total(&self) -> Duration {
+        self.animations + self.states + self.lua
+    }
 }
 
 pub struct Pet<'lua> {
+    pub lua: &'lua Lua,
+    pub path: PathBuf,
     pub metadata: PetMetadata,
-    pub animations: HashMap<String, Animation>,
+    pub animations: Rc<HashMap<String, Animation>>,
     pub states: HashMap<String, State<'lua>>,
+    pub global_handlers: GlobalEventHandlers<'lua>,
+    pub load_timings: LoadTimings,
+}
+
+// The Lua parts can't be printed
+impl std::fmt::Debug for Pet<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Pet")
+            .field("path", &self.path)
+            .field("name", &self.metadata.name)
+            .finish_non_exhaustive()
+    }
+}
+
+// What's loaded besides the pet itself
+#[derive(Debug, Clone, Copy)]
+pub struct LoadOptions {
+    // Load the states marked debug_only too
+    pub debug: bool,
+    // Fail on gaps in frame numbers instead of warning about them
+    pub strict: bool,
+    // How much frame text a pet may have, all animations together
+    pub max_frame_bytes: u64,
+}
+
+pub const DEFAULT_MAX_FRAME_BYTES: u64 = 64 * 1024 * 1024;
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        Self { debug: false, strict: false, max_frame_bytes: DEFAULT_MAX_FRAME_BYTES }
+    }
 }
 
 impl<'lua> Pet<'lua> {
     pub fn load(lua: &'lua Lua, path: &Path) -> Result<Pet<'lua>, Error> {
-        let metadata = PetMetadata::load(path.join("meta.toml") )?;
-
-        let animation_dirs: Vec<_> = fs::read_dir(path.join("anim"))
-            .map_err(Error::IO)?
-            .filter_map(|d|
-                d.map_err(Error::IO)
-                    .ok()
-                    .filter(|entry|
-                        entry.path().is_dir())
-            )
-            .collect();
+        Self::load_with_progress(lua, path, |_, _| {})
+    }
+
+    // `progress` is called with the frames read so far and the total after
+    // every animation
+    pub fn load_with_progress(lua: &'lua Lua, path: &Path, progress: impl FnMut(usize, usize)) -> Result<Pet<'lua>, Error> {
+        Self::load_with_options(lua, path, LoadOptions::default(), progress)
+    }
+
+    pub fn load_with_options(lua: &'lua Lua, path: &Path, options: LoadOptions, mut progress: impl FnMut(usize, usize)) -> Result<Pet<'lua>, Error> {
+        let mut timings = LoadTimings::default();
+
+        let metadata = PetMetadata::load(metadata_path(path))?;
+        let chain = inheritance_chain(path, &metadata)?;
+
+        let started = Instant::now();
+        require::install(lua, &chain).map_err(Error::Lua)?;
+
+        // Before the states, which may use what it defines
+        let global_dir = chain.iter().find(|dir| dir.join("global.lua").exists()).unwrap_or(&chain[0]);
+        let global_handlers = GlobalEventHandlers::load(lua, global_dir)?;
+        timings.lua += started.elapsed();
+
+        let started = Instant::now();
+        let animation_dirs = layered_dirs(&chain, "anim")?;
+        let mut total_frames = 0;
+        let mut total_bytes = 0;
+        for (_, dir) in &animation_dirs {
+            let files = frame_files(dir)?;
+            total_frames += files.len();
+            total_bytes += frame_bytes(&files)?;
+
+            // A pet shared by someone else could be big enough to run out of memory
+            if total_bytes > options.max_frame_bytes {
+                return Err(Error::TooManyFrameBytes(total_bytes, options.max_frame_bytes));
+            }
+        }
+        let mut frames_read = 0;
+        progress(frames_read, total_frames);
 
         let mut animations = HashMap::new();
 
-        for animation_path in animation_dirs {
-            let name = animation_path.file_name().into_string().map_err(|s| Error::Utf8(s.into()))?;
-            let animation = Animation::load(animation_path.path().as_path())?;
+        for (name, animation_path) in animation_dirs {
+            let animation = Animation::load_with_options(&animation_path, options)?;
+            frames_read += animation.frames.len();
+            progress(frames_read, total_frames);
 
             animations.insert(name, animation);
         }
+        timings.animations = started.elapsed();
 
-        let state_dirs: Vec<_> = fs::read_dir(path.join("state"))
-            .map_err(Error::IO)?
-            .filter_map(|d|
-                d.map_err(Error::IO)
-                    .ok()
-                    .filter(|entry|
-                        entry.path().is_dir())
-            )
-            .collect();
-
+        let started = Instant::now();
         let mut states = HashMap::new();
+        let mut skipped = Vec::new();
+        let mut scripts = Duration::ZERO;
+
+        for (name, state_path) in layered_dirs(&chain, "state")? {
+            // Before the script runs, so it can't leave anything behind
+            let debug_only = StateMetadata::load(&metadata_path(&state_path)).is_ok_and(|m| m.debug_only);
+            if debug_only && !options.debug {
+                log::info!("Skipping the state '{name}', it's only loaded with --debug");
+                skipped.push(name);
+                continue;
+            }
 
-        for state_path in state_dirs {
-            let name = state_path.file_name().into_string().map_err(|s| Error::Utf8(s.into()))?;
-            let state = State::load(lua, state_path.path().as_path())?;
+            let (state, script_time) = State::load_timed(lua, &state_path)?;
+            scripts += script_time;
 
             states.insert(name, state);
         }
 
-        Ok(Self {
+        // Keys and animations that would switch to a skipped state do
+        // nothing instead
+        for state in states.values_mut() {
+            state.metadata.input_transitions.retain(|_, target| !skipped.contains(target));
+            state.metadata.idle_next.retain(|(target, _)| !skipped.contains(target));
+        }
+        for animation in animations.values_mut() {
+            if animation.metadata.on_complete_state.as_ref().is_some_and(|target| skipped.contains(target)) {
+                animation.metadata.on_complete_state = None;
+            }
+        }
+        timings.states = started.elapsed().saturating_sub(scripts);
+        timings.lua += scripts;
+
+        let pet = Self {
+            lua,
+            path: path.to_path_buf(),
             metadata,
-            animations,
+            animations: Rc::new(animations),
             states,
-        })
+            global_handlers,
+            load_timings: timings,
+        };
+        pet.validate()?;
+
+        Ok(pet)
+    }
+
+    // Checks that everything the pet refers to by name exists
+    pub fn validate(&self) -> Result<(), Error> {
+        self.metadata.mirror_map()?;
+
+        if !self.states.contains_key(&self.metadata.default_state) {
+            return Err(Error::InvalidObject("The default state doesn't exist"));
+        }
+
+        for animation in self.animations.values() {
+            if animation.metadata.on_complete_state.as_ref().is_some_and(|target| !self.states.contains_key(target)) {
+                return Err(Error::InvalidObject("on_complete_state refers to a state that doesn't exist"));
+            }
+        }
+
+        for state in self.states.values() {
+            if !self.animations.contains_key(&state.metadata.animation) {
+                return Err(Error::InvalidObject("A state's animation doesn't exist"));
+            }
+
+            if state.metadata.idle_anims.iter().any(|(anim, _)| !self.animations.contains_key(anim)) {
+                return Err(Error::InvalidObject("idle_anims refers to an animation that doesn't exist"));
+            }
+
+            if state.metadata.input_transitions.values().any(|target| !self.states.contains_key(target)) {
+                return Err(Error::InvalidObject("input_transitions refers to a state that doesn't exist"));
+            }
+
+            if state.metadata.idle_next.iter().any(|(target, _)| !self.states.contains_key(target)) {
+                return Err(Error::InvalidObject("idle_next refers to a state that doesn't exist"));
+            }
+        }
+
+        Ok(())
     }
 }
 
@@ -0,0 +1,81 @@
+use std::{io::{self, Write}, time::Duration};
+
+use crossterm::{cursor::{self, MoveTo}, event::{self, KeyEvent}, execute, style::{Attribute, Print, SetAttribute}, terminal::{self, disable_raw_mode, enable_raw_mode}, ExecutableCommand};
+
+use a_duk::index::PetSummary;
+use crate::exit::Failure;
+
+// Lets the user choose one of `pets` (directory name and summary) with the
+// arrow keys. None if they quit with Esc instead.
+pub fn pick_pet(out: &mut impl Write, pets: &[(String, PetSummary)]) -> Result<Option<String>, Failure> {
+    enable_raw_mode().map_err(Failure::terminal)?;
+
+    let result = out.execute(cursor::Hide)
+        .map_err(Failure::terminal)
+        .and_then(|out| run_picker(out, pets));
+
+    out.execute(cursor::Show).map_err(Failure::terminal)?;
+    execute!(out, terminal::Clear(terminal::ClearType::All), MoveTo(0, 0)).map_err(Failure::terminal)?;
+    disable_raw_mode().map_err(Failure::terminal)?;
+
+    result
+}
+
+fn run_picker(out: &mut impl Write, pets: &[(String, PetSummary)]) -> Result<Option<String>, Failure> {
+    let mut selected = 0;
+    let mut redraw = true;
+
+    loop {
+        if redraw {
+            draw(out, pets, selected).map_err(Failure::terminal)?;
+            redraw = false;
+        }
+
+        if !event::poll(Duration::from_millis(100)).map_err(Failure::terminal)? {
+            continue;
+        }
+
+        match event::read().map_err(Failure::terminal)? {
+            event::Event::Key(KeyEvent { code, .. }) => match code {
+                event::KeyCode::Esc => return Ok(None),
+                event::KeyCode::Enter => return Ok(Some(pets[selected].0.clone())),
+                event::KeyCode::Up => {
+                    selected = selected.checked_sub(1).unwrap_or(pets.len() - 1);
+                    redraw = true;
+                },
+                event::KeyCode::Down => {
+                    selected = (selected + 1) % pets.len();
+                    redraw = true;
+                },
+                _ => {},
+            },
+            event::Event::Resize(..) => redraw = true,
+            _ => {},
+        }
+    }
+}
+
+// A header line, then as many pets as fit, scrolled so the selected one shows
+fn draw(out: &mut impl Write, pets: &[(String, PetSummary)], selected: usize) -> io::Result<()> {
+    let (cols, rows) = terminal::size()?;
+    let visible = usize::from(rows).saturating_sub(2).max(1);
+    let first = selected.saturating_sub(visible - 1);
+    let fit = |line: String| line.chars().take(cols.saturating_sub(1).into()).collect::<String>();
+
+    execute!(out, terminal::Clear(terminal::ClearType::All), MoveTo(0, 0))?;
+    write!(out, "{}\r\n\r\n", fit("Choose a pet - Up/Down to move, Enter to run, Esc to quit".to_string()))?;
+
+    for (i, (dir_name, meta)) in pets.iter().enumerate().skip(first).take(visible) {
+        let line = fit(format!("{} {} ({dir_name}) - {}",
+            if i == selected { ">" } else { " " }, meta.name, meta.description));
+
+        if i == selected {
+            execute!(out, SetAttribute(Attribute::Reverse), Print(line), SetAttribute(Attribute::Reset))?;
+        } else {
+            execute!(out, Print(line))?;
+        }
+        write!(out, "\r\n")?;
+    }
+
+    out.flush()
+}
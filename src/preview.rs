@@ -0,0 +1,97 @@
+use crate::{pet::{Animation, Pet}, text::{display_width, pad_to_width}};
+
+// Lays out the first frame of every animation in a labeled grid. Every cell
+// gets the size of the largest frame so the columns line up. Returns the
+// lines of each page, each page fitting into `cols` x `rows`.
+pub fn preview_pages(pet: &Pet, cols: usize, rows: usize) -> Vec<Vec<String>> {
+    let mut names: Vec<_> = pet.animations.keys().collect();
+    names.sort();
+
+    let cells: Vec<(String, Vec<&str>)> = names.iter()
+        .map(|name| {
+            let anim = &pet.animations[*name];
+            let label = format!("{} ({} frames)", name, anim.frames.len());
+            (label, anim.frames[0].lines().collect())
+        })
+        .collect();
+
+    let cell_width = cells.iter()
+        .flat_map(|(label, lines)| lines.iter().map(|l| display_width(l)).chain([display_width(label)]))
+        .max()
+        .unwrap_or(0) + 2;
+    let cell_height = cells.iter()
+        .map(|(_, lines)| lines.len())
+        .max()
+        .unwrap_or(0) + 2;
+
+    let per_row = (cols / cell_width).max(1);
+    let rows_per_page = (rows / cell_height).max(1);
+
+    cells.chunks(per_row * rows_per_page)
+        .map(|page| {
+            let mut lines = Vec::new();
+
+            for row in page.chunks(per_row) {
+                for line in 0..cell_height - 1 {
+                    let text: String = row.iter()
+                        .map(|(label, frame)| {
+                            let cell = if line == 0 {
+                                label.as_str()
+                            } else {
+                                frame.get(line - 1).copied().unwrap_or("")
+                            };
+                            pad_to_width(cell, cell_width)
+                        })
+                        .collect();
+
+                    lines.push(text.trim_end().to_string());
+                }
+                lines.push(String::new());
+            }
+
+            lines
+        })
+        .collect()
+}
+
+// Lays out every frame of an animation side by side, numbered and separated
+// by bars, starting a new row of frames once `cols` is full
+pub fn animation_strip(anim: &Animation, cols: usize) -> Vec<String> {
+    const SEPARATOR: &str = " | ";
+
+    let cells: Vec<(String, Vec<&str>)> = anim.frames.iter()
+        .enumerate()
+        .map(|(i, frame)| (format!("#{i}"), frame.lines().collect()))
+        .collect();
+
+    let cell_width = cells.iter()
+        .flat_map(|(label, lines)| lines.iter().map(|l| display_width(l)).chain([display_width(label)]))
+        .max()
+        .unwrap_or(0);
+    let per_row = ((cols + SEPARATOR.len()) / (cell_width + SEPARATOR.len())).max(1);
+
+    let mut lines = Vec::new();
+    for (i, row) in cells.chunks(per_row).enumerate() {
+        if i > 0 {
+            lines.push(String::new());
+        }
+
+        for line in 0..=anim.height {
+            let text = row.iter()
+                .map(|(label, frame)| {
+                    let cell = if line == 0 {
+                        label.as_str()
+                    } else {
+                        frame.get(line - 1).copied().unwrap_or("")
+                    };
+                    pad_to_width(cell, cell_width)
+                })
+                .collect::<Vec<_>>()
+                .join(SEPARATOR);
+
+            lines.push(text.trim_end().to_string());
+        }
+    }
+
+    lines
+}
@@ -0,0 +1,69 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use mlua::{Function, Lua, Table, Value};
+
+// `require("name")` only loads Lua files from within the pet's directory,
+// or the directories of the pets it extends:
+//
+// - dots separate directories, so `require("lib.util")` loads `lib/util.lua`
+// - names containing slashes, backslashes or empty parts (`..`) are rejected
+// - files that resolve (e.g. through symlinks) to outside the pet are rejected
+// - C modules can't be loaded
+//
+// Modules preloaded into `package.preload` still work.
+pub(crate) fn install(lua: &Lua, pet_paths: &[PathBuf]) -> mlua::Result<()> {
+    let roots = pet_paths.iter()
+        .map(|path| path.canonicalize().map_err(mlua::Error::external))
+        .collect::<mlua::Result<Vec<_>>>()?;
+
+    let package: Table = lua.globals().get("package")?;
+    let searchers: Table = package.get("searchers")?;
+    let preload_searcher: Function = searchers.get(1)?;
+
+    let searcher_roots = roots.clone();
+    let pet_searcher = lua.create_function(move |lua, name: String| {
+        let Some(path) = searcher_roots.iter().find_map(|root| resolve(root, &name)) else {
+            let msg = format!("\n\tno file for '{name}' in the pet directory");
+            return Ok((Value::String(lua.create_string(msg)?), Value::Nil));
+        };
+
+        let source = fs::read_to_string(&path).map_err(mlua::Error::external)?;
+        let path = path.to_string_lossy().into_owned();
+        let loader = lua.load(source)
+            .set_name(format!("@{path}"))
+            .into_function()?;
+
+        Ok((Value::Function(loader), Value::String(lua.create_string(path)?)))
+    })?;
+
+    let search_path = roots.iter()
+        .map(|root| root.join("?.lua").to_string_lossy().into_owned())
+        .collect::<Vec<_>>()
+        .join(";");
+
+    package.set("searchers", lua.create_sequence_from([preload_searcher, pet_searcher])?)?;
+    package.set("path", search_path)?;
+    package.set("cpath", "")?;
+
+    Ok(())
+}
+
+fn resolve(root: &Path, name: &str) -> Option<PathBuf> {
+    if name.contains(['/', '\\', ':']) {
+        return None;
+    }
+
+    let parts: Vec<_> = name.split('.').collect();
+    if parts.iter().any(|p| p.is_empty()) {
+        return None;
+    }
+
+    let path = root.join(format!("{}.lua", parts.join("/")))
+        .canonicalize()
+        .ok()?;
+
+    (path.starts_with(root) && path.is_file()).then_some(path)
+}
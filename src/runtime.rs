@@ -0,0 +1,1413 @@
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    fmt::Display,
+    io::{self, Write},
+    path::PathBuf,
+    rc::Rc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use mlua::{Function, RegistryKey, Thread, ThreadStatus, Value};
+use rand::{distributions::WeightedIndex, prelude::Distribution, rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    bus::{Message, MessageBus, Payload},
+    canvas::Canvas,
+    color::ColorDepth,
+    cursor::PetCursor,
+    detached::DetachedPet,
+    frame_writer::{write_lines, LineEnding},
+    lua_api,
+    overlay::Overlay,
+    pet::{Anchor, Animation, Baseline, Error, LoadOptions, Pet, PetMetadata, State},
+    save::{SaveFile, StoreValue},
+    sound::{Music, Sounds},
+    stats::{Crossing, Stats},
+    text::{fill_template, frame_width, graphemes, mirror_frame, skip_columns},
+    tween::Tween,
+};
+
+// The part of the runtime that the Lua API needs access to
+#[derive(Debug)]
+pub struct RuntimeState {
+    pub animations: Rc<HashMap<String, Animation>>,
+    pub state_names: HashSet<String>,
+    // As loaded, for `get_metadata`
+    pub metadata: PetMetadata,
+    pub current_state: String,
+    // Set by `set_current_state`, entered on the next tick
+    pub pending_state: Option<String>,
+    // Set by `transition_after_anim`, becomes the pending state once the
+    // current animation has shown its last frame
+    pub state_after_anim: Option<String>,
+    pub current_anim: String,
+    pub current_frame: usize,
+    pub stats: Stats,
+    // The on_high and on_low callbacks given to `register_stat`
+    pub stat_callbacks: HashMap<(String, Crossing), RegistryKey>,
+    // The key-value store scripts persist small bits of data in
+    pub store: BTreeMap<String, StoreValue>,
+    // Lifetime totals like feeds, kept in the save file
+    pub counters: BTreeMap<String, i64>,
+    // When the pet was first run, in seconds since the Unix epoch
+    pub first_run: u64,
+    // What the terminal the pet is drawn on can do, set by the driver
+    pub color_depth: ColorDepth,
+    pub output_is_tty: bool,
+    // The size of the region the pet is confined to, if not the whole terminal
+    pub viewport: Option<(u16, u16)>,
+    // Time since the pet's first tick, by the clock passed to `tick`
+    pub elapsed: Duration,
+    // Hidden pets keep running, only their frames aren't drawn
+    pub visible: bool,
+    // Whether frames are drawn mirrored
+    pub flip: bool,
+    // What scripts have drawn with the draw_* functions
+    pub overlay: Overlay,
+    // Keys pressed since a script last read them, oldest first
+    pub key_buffer: VecDeque<String>,
+    // The keys being held, with when they were last pressed or repeated
+    pub held_keys: HashMap<String, Duration>,
+    // Whether the terminal reports key releases. Without them a key counts
+    // as released once it stops repeating.
+    pub reports_releases: bool,
+    // Multiplies how fast animations, updates and waits run
+    pub speed: f64,
+    // The name the pet is addressed by on the message bus
+    pub pet_id: String,
+    pub bus: Rc<RefCell<MessageBus>>,
+    // Shared by the engine and the random_* functions, so --seed covers both
+    pub rng: StdRng,
+    // Where load_pet looks pets up, it's unavailable without one
+    pub pets_dir: Option<PathBuf>,
+    // How load_pet loads pets, the same as the pet it replaces
+    pub load_options: LoadOptions,
+    // Loaded by load_pet, for the driver to swap in after the tick
+    pub loaded_pet: Option<DetachedPet>,
+    // What get_args returns, the values the pet was launched with
+    pub launch_args: Vec<(String, Payload)>,
+    // Animations played over the current one, bottom to top
+    pub tracks: Vec<Track>,
+    // Whether a track was started or stopped since the last tick
+    pub tracks_changed: bool,
+    // How far the pet is shaken from its place at most, decaying to 0
+    pub shake: Option<Tween>,
+    // Where the shake has moved the pet this tick
+    pub shake_offset: (i32, i32),
+    // What `say` queued, the first line is the one shown
+    pub speech: VecDeque<Speech>,
+    // When the shown line is done, set once it's first shown
+    pub speech_until: Option<Duration>,
+    pub speech_changed: bool,
+    // Shown where speech goes, instead of it, until it's taken down
+    pub prompt: Option<String>,
+    // The terminal cursor, hidden when None
+    pub cursor: Option<PetCursor>,
+    // How many columns and rows of a frame too big for the screen are
+    // scrolled off its top left, set by the driver with --scroll
+    pub pan: (u16, u16),
+    // What `{key}` placeholders in frames are replaced with
+    pub template_vars: BTreeMap<String, String>,
+    // Whether a template variable changed since the last tick
+    pub template_changed: bool,
+}
+
+// An animation playing on top of the pet's current one, on its own timer.
+// Tracks loop until they're stopped and outlive state changes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Track {
+    pub name: String,
+    pub anim: String,
+    pub frame: usize,
+    // None until the track is first shown
+    pub last_advance: Option<Instant>,
+}
+
+pub struct PetRuntime<'lua> {
+    pub pet: Pet<'lua>,
+    shared: Rc<RefCell<RuntimeState>>,
+    sounds: Rc<Sounds>,
+    music: Music,
+
+    last_render: Option<Instant>,
+    last_update: Option<Instant>,
+    last_tick: Option<Instant>,
+    last_global_tick: Option<Instant>,
+    // Since when the pet has been left alone in its state, as time elapsed
+    // since it started
+    idle_since: Option<Duration>,
+
+    // The last frame a sound was played for, so a frame that stays on screen
+    // for several ticks doesn't retrigger its sound
+    last_sound_frame: Option<(String, usize)>,
+
+    // When the pet first ticked, history times are relative to it
+    started: Option<Instant>,
+    // The last (state, animation) recorded in the history
+    last_seen: (String, String),
+    history: VecDeque<Transition>,
+
+    // The running `Main` coroutine of a state
+    script: Option<Script<'lua>>,
+
+    save: Option<SaveFile>,
+
+    // With keep-alive, a failing handler is logged and switched off instead
+    // of failing the whole runtime. Keyed by (state, handler).
+    keep_alive: bool,
+    disabled_handlers: HashSet<(String, &'static str)>,
+
+    mirror_map: HashMap<char, char>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransitionKind {
+    State,
+    Animation,
+}
+
+// A state or animation change, kept for debugging
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transition {
+    // Since the pet started
+    pub at: Duration,
+    pub kind: TransitionKind,
+    pub from: String,
+    pub to: String,
+}
+
+impl Display for Transition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self.kind {
+            TransitionKind::State => "state",
+            TransitionKind::Animation => "anim",
+        };
+
+        write!(f, "{:.1}s {kind} {} -> {}", self.at.as_secs_f64(), self.from, self.to)
+    }
+}
+
+// Something the pet says, shown for `duration` once its turn comes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Speech {
+    pub text: String,
+    pub duration: Duration,
+}
+
+// Everything that goes into drawing a pet. Drawing it again with an equal
+// key gives the same picture.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderKey {
+    anim: String,
+    frame: usize,
+    flip: bool,
+    visible: bool,
+    origin: (u16, u16),
+    overlay: u64,
+    // The animation and frame of every track
+    tracks: Vec<(String, usize)>,
+    shake_offset: (i32, i32),
+    template_vars: BTreeMap<String, String>,
+    pan: (u16, u16),
+    speech: Option<String>,
+    cursor: Option<PetCursor>,
+}
+
+struct Script<'lua> {
+    state: String,
+    thread: Thread<'lua>,
+    // When to resume the coroutine next, `None` once it has finished
+    resume_at: Option<Instant>,
+}
+
+impl<'lua> PetRuntime<'lua> {
+    pub fn new(pet: Pet<'lua>) -> Result<Self, Error> {
+        Self::with_save(pet, None)
+    }
+
+    // Like `new`, with the pet's progress restored from and kept in `save`
+    pub fn with_save(pet: Pet<'lua>, save: Option<SaveFile>) -> Result<Self, Error> {
+        let saved_state = save.as_ref()
+            .filter(|_| pet.metadata.persist_state)
+            .and_then(|save| save.data.state.clone());
+
+        let current_state = match saved_state {
+            Some(state) if pet.states.contains_key(&state) => state,
+            Some(state) => {
+                log::warn!("The saved state '{state}' doesn't exist anymore, using the default state");
+                pet.metadata.default_state.clone()
+            },
+            None => pet.metadata.default_state.clone(),
+        };
+        let state = pet.states.get(&current_state)
+            .ok_or(Error::InvalidObject("The default state doesn't exist"))?;
+
+        let current_anim = state.metadata.animation.clone();
+        let current_frame = pet.animations.get(&current_anim)
+            .ok_or(Error::InvalidObject("The state's animation doesn't exist"))?
+            .metadata.start_frame;
+
+        let pet_id = pet.path.file_name()
+            .map(|f| f.to_string_lossy().into_owned())
+            .unwrap_or_else(|| pet.metadata.name.clone());
+        let bus = Rc::new(RefCell::new(MessageBus::default()));
+        bus.borrow_mut().register(&pet_id);
+
+        let shared = Rc::new(RefCell::new(RuntimeState {
+            animations: pet.animations.clone(),
+            state_names: pet.states.keys().cloned().collect(),
+            metadata: pet.metadata.clone(),
+            current_state,
+            pending_state: None,
+            state_after_anim: None,
+            current_anim,
+            current_frame,
+            stats: Stats::default(),
+            stat_callbacks: HashMap::new(),
+            store: save.as_ref().map(|save| save.data.store.clone()).unwrap_or_default(),
+            counters: save.as_ref().map(|save| save.data.counters.clone()).unwrap_or_default(),
+            first_run: save.as_ref().and_then(|save| save.data.first_run).unwrap_or_else(unix_time),
+            color_depth: ColorDepth::TrueColor,
+            output_is_tty: true,
+            viewport: None,
+            elapsed: Duration::ZERO,
+            visible: true,
+            flip: pet.metadata.flip,
+            overlay: Overlay::default(),
+            key_buffer: VecDeque::new(),
+            held_keys: HashMap::new(),
+            reports_releases: false,
+            speed: 1.0,
+            pet_id,
+            bus,
+            rng: StdRng::from_entropy(),
+            pets_dir: None,
+            load_options: LoadOptions::default(),
+            loaded_pet: None,
+            launch_args: Vec::new(),
+            tracks: Vec::new(),
+            tracks_changed: false,
+            shake: None,
+            shake_offset: (0, 0),
+            speech: VecDeque::new(),
+            speech_until: None,
+            speech_changed: false,
+            prompt: None,
+            cursor: None,
+            template_vars: BTreeMap::new(),
+            template_changed: false,
+            pan: (0, 0),
+        }));
+        let sounds = Rc::new(Sounds::new(&pet.path));
+        let mirror_map = pet.metadata.mirror_map()?;
+
+        lua_api::register(pet.lua, &shared, &sounds).map_err(Error::Lua)?;
+
+        let runtime = Self {
+            pet,
+            shared,
+            sounds,
+            music: Music::default(),
+            last_render: None,
+            last_update: None,
+            last_tick: None,
+            last_global_tick: None,
+            idle_since: None,
+            last_sound_frame: None,
+            started: None,
+            last_seen: (String::new(), String::new()),
+            history: VecDeque::new(),
+            script: None,
+            save,
+            keep_alive: false,
+            disabled_handlers: HashSet::new(),
+            mirror_map,
+        };
+
+        // Call the init event of the initial state
+        if let Some(f) = &runtime.state().event_handlers.init {
+            f.call::<(), ()>(()).map_err(Error::Lua)?;
+        }
+
+        Ok(runtime)
+    }
+
+    pub fn state(&self) -> &State<'lua> {
+        let shared = self.shared.borrow();
+        self.pet.states.get(&shared.current_state).unwrap()
+    }
+
+    pub fn current_animation(&self) -> &Animation {
+        let shared = self.shared.borrow();
+        self.pet.animations.get(&shared.current_anim).unwrap()
+    }
+
+    pub fn current_frame(&self) -> usize {
+        self.shared.borrow().current_frame
+    }
+
+    pub fn current_state(&self) -> String {
+        self.shared.borrow().current_state.clone()
+    }
+
+    // Switches to another state, starting its animation and calling its `Init`
+    pub fn set_state(&mut self, name: &str) -> Result<(), Error> {
+        let state = self.pet.states.get(name)
+            .ok_or(Error::InvalidObject("The state doesn't exist"))?;
+        let anim = state.metadata.animation.clone();
+        let start_frame = self.pet.animations.get(&anim).map_or(0, |a| a.metadata.start_frame);
+
+        {
+            let mut shared = self.shared.borrow_mut();
+            shared.current_state = name.to_string();
+            shared.current_anim = anim;
+            shared.current_frame = start_frame;
+            // It was meant for the animation of the state that's being left
+            shared.state_after_anim = None;
+            // Like it is for a pet that doesn't use it
+            shared.cursor = None;
+        }
+
+        self.last_render = None;
+        self.last_update = None;
+        self.idle_since = None;
+
+        if !self.is_disabled("Init") {
+            let result = match &self.state().event_handlers.init {
+                Some(f) => f.call::<(), ()>(()).map_err(Error::Lua),
+                None => Ok(()),
+            };
+            self.guard("Init", result)?;
+        }
+
+        Ok(())
+    }
+
+    // The states --attract tours, by name
+    pub fn demo_states(&self) -> Vec<&str> {
+        let mut states: Vec<_> = self.pet.states.iter()
+            .filter(|(_, state)| state.metadata.demo)
+            .map(|(name, _)| name.as_str())
+            .collect();
+        states.sort_unstable();
+        states
+    }
+
+    // Moves on to the demo state after the current one. Returns whether
+    // there was one to move to.
+    pub fn next_demo_state(&mut self) -> Result<bool, Error> {
+        let current = self.current_state();
+        let states = self.demo_states();
+
+        let next = match states.iter().position(|&state| state == current) {
+            Some(i) => states[(i + 1) % states.len()],
+            // Outside the tour, e.g. after a script changed the state
+            None => match states.iter().find(|&&state| state > current.as_str()).or(states.first()) {
+                Some(state) => state,
+                None => return Ok(false),
+            },
+        }.to_string();
+
+        if next != current {
+            self.set_state(&next)?;
+        }
+
+        Ok(true)
+    }
+
+    // Animation colors are downgraded to what the terminal can show
+    pub fn set_color_depth(&mut self, color_depth: ColorDepth) {
+        self.shared.borrow_mut().color_depth = color_depth;
+    }
+
+    pub fn set_output_is_tty(&mut self, output_is_tty: bool) {
+        self.shared.borrow_mut().output_is_tty = output_is_tty;
+    }
+
+    // The directory load_pet finds pets in
+    pub fn set_pets_dir(&mut self, pets_dir: Option<PathBuf>) {
+        self.shared.borrow_mut().pets_dir = pets_dir;
+    }
+
+    // The options load_pet loads pets with, like --strict and --max-assets-mb
+    pub fn set_load_options(&mut self, load_options: LoadOptions) {
+        self.shared.borrow_mut().load_options = load_options;
+    }
+
+    // The command line as scripts see it through get_args
+    pub fn set_launch_args(&mut self, args: Vec<(String, Payload)>) {
+        self.shared.borrow_mut().launch_args = args;
+    }
+
+    // The pet a script asked to switch to with load_pet. It's up to the
+    // driver to start it in place of this one.
+    pub fn take_loaded_pet(&mut self) -> Option<DetachedPet> {
+        self.shared.borrow_mut().loaded_pet.take()
+    }
+
+    // Scripts see the viewport as the screen, the driver does the drawing
+    pub fn set_viewport(&mut self, viewport: Option<(u16, u16)>) {
+        self.shared.borrow_mut().viewport = viewport;
+    }
+
+    // Makes the pet's randomness repeatable, both the engine's and Lua's
+    pub fn set_seed(&mut self, seed: u64) -> Result<(), Error> {
+        self.shared.borrow_mut().rng = StdRng::seed_from_u64(seed);
+
+        self.pet.lua.load(format!("math.randomseed({seed})"))
+            .exec()
+            .map_err(Error::Lua)
+    }
+
+    // Points os.clock and os.time at the clock passed to `tick` so that
+    // scripts can't tell how fast the run really was. os.time counts from
+    // the Unix epoch as if the pet was started then.
+    pub fn use_virtual_time(&mut self) -> Result<(), Error> {
+        let shared_closure = self.shared.clone();
+        let elapsed = self.pet.lua
+            .create_function(move |_, ()| Ok(shared_closure.borrow().elapsed.as_secs_f64()))
+            .map_err(Error::Lua)?;
+
+        self.pet.lua.load(r#"
+            local elapsed = ...
+            local time = os.time
+
+            os.clock = elapsed
+            os.time = function(date)
+                if date ~= nil then
+                    return time(date)
+                end
+                return math.floor(elapsed())
+            end
+        "#)
+            .set_name("virtual time")
+            .call::<_, ()>(elapsed)
+            .map_err(Error::Lua)
+    }
+
+    pub fn set_keep_alive(&mut self, keep_alive: bool) {
+        self.keep_alive = keep_alive;
+    }
+
+    // Turns the handlers keep-alive switched off back on
+    pub fn enable_handlers(&mut self) {
+        if !self.disabled_handlers.is_empty() {
+            log::info!("{}: re-enabling {} handler(s)", self.id(), self.disabled_handlers.len());
+        }
+
+        self.disabled_handlers.clear();
+        self.script = None;
+    }
+
+    // Global handlers don't belong to a state, they're keyed by ""
+    fn handler_key(&self, handler: &'static str) -> (String, &'static str) {
+        if GLOBAL_HANDLERS.contains(&handler) {
+            (String::new(), handler)
+        } else {
+            (self.shared.borrow().current_state.clone(), handler)
+        }
+    }
+
+    fn is_disabled(&self, handler: &'static str) -> bool {
+        self.disabled_handlers.contains(&self.handler_key(handler))
+    }
+
+    // Passes handler errors through, unless keep-alive is on
+    fn guard(&mut self, handler: &'static str, result: Result<(), Error>) -> Result<(), Error> {
+        match result {
+            Err(e) if self.keep_alive => {
+                let key = self.handler_key(handler);
+                match key.0.as_str() {
+                    "" => log::error!("{}: {handler} failed and was disabled: {e}", self.id()),
+                    state => log::error!("{}: {handler} of the state '{state}' failed and was disabled: {e}", self.id()),
+                }
+
+                self.disabled_handlers.insert(key);
+                Ok(())
+            },
+            result => result,
+        }
+    }
+
+    fn apply_pending_state(&mut self) -> Result<(), Error> {
+        let pending = self.shared.borrow_mut().pending_state.take();
+
+        match pending {
+            Some(state) => self.set_state(&state),
+            None => Ok(()),
+        }
+    }
+
+    // Writes the pet's progress to its save file, if it has one
+    pub fn save(&mut self) -> Result<(), Error> {
+        let current_state = self.current_state();
+        let persist_state = self.pet.metadata.persist_state;
+
+        let Some(save) = &mut self.save else {
+            return Ok(());
+        };
+
+        if persist_state {
+            save.data.state = Some(current_state);
+        }
+        let shared = self.shared.borrow();
+        save.data.store = shared.store.clone();
+        save.data.counters = shared.counters.clone();
+        save.data.first_run = Some(shared.first_run);
+
+        save.write()
+    }
+
+    // Advances the pet to `now`. Returns whether the displayed frame changed.
+    pub fn tick(&mut self, now: Instant) -> Result<bool, Error> {
+        let started = *self.started.get_or_insert(now);
+        self.shared.borrow_mut().elapsed = now.duration_since(started);
+
+        self.apply_pending_state()?;
+        self.ensure_animation();
+        self.update_music();
+        self.sounds.reap();
+
+        if let Some(last_tick) = self.last_tick {
+            self.shared.borrow_mut().stats.decay(now.duration_since(last_tick));
+        }
+        self.last_tick = Some(now);
+        self.run_stat_callbacks()?;
+
+        let mut frame_changed = false;
+        let mut looped = None;
+
+        let delay = self.current_animation().metadata.delay;
+        match self.last_render {
+            None => frame_changed = true,
+            Some(last) if now.duration_since(last) >= self.scaled(delay) => {
+                looped = self.advance_frame();
+                frame_changed = true;
+            },
+            _ => {},
+        }
+
+        if frame_changed {
+            self.last_render = Some(now);
+            self.play_frame_sound();
+        }
+
+        frame_changed |= self.advance_tracks(now);
+        frame_changed |= self.update_shake();
+        frame_changed |= self.update_speech();
+
+        if let Some(anim) = looped.filter(|_| !self.is_disabled("Anim_loop")) {
+            let result = match &self.state().event_handlers.anim_loop {
+                Some(f) => f.call::<_, ()>(anim).map_err(Error::Lua),
+                None => Ok(()),
+            };
+            self.guard("Anim_loop", result)?;
+        }
+
+        let update_due = self.last_update.is_none_or(|last|
+            now.duration_since(last) >= self.scaled(self.state().metadata.update_delay));
+
+        if update_due && !self.is_disabled("Update") {
+            // Update can return the state to go to, which wins over a
+            // set_current_state from the same call
+            let result = match &self.state().event_handlers.update {
+                Some(f) => f.call::<_, Value>(()).map_err(Error::Lua)
+                    .and_then(|next| self.request_state_from("Update", next)),
+                None => Ok(()),
+            };
+            self.guard("Update", result)?;
+
+            self.last_update = Some(now);
+        }
+
+        let global_tick_due = self.last_global_tick.is_none_or(|last|
+            now.duration_since(last) >= self.scaled(self.pet.metadata.global_tick_delay));
+
+        if global_tick_due && !self.is_disabled("Tick") {
+            let result = match &self.pet.global_handlers.tick {
+                Some(f) => f.call::<(), ()>(()).map_err(Error::Lua),
+                None => Ok(()),
+            };
+            self.guard("Tick", result)?;
+
+            self.last_global_tick = Some(now);
+        }
+
+        if !self.is_disabled("Main") {
+            let result = self.run_script(now);
+            self.guard("Main", result)?;
+        }
+
+        self.check_idle();
+
+        // A transition requested during this tick shows up right away
+        if self.shared.borrow().pending_state.is_some() {
+            self.apply_pending_state()?;
+            self.last_render = Some(now);
+            self.last_update = Some(now);
+            frame_changed = true;
+        }
+
+        self.record_transitions(now);
+
+        frame_changed |= self.shared.borrow_mut().overlay.take_changed();
+        frame_changed |= std::mem::take(&mut self.shared.borrow_mut().tracks_changed);
+        frame_changed |= std::mem::take(&mut self.shared.borrow_mut().template_changed);
+
+        Ok(frame_changed)
+    }
+
+    // Picks a new random offset every tick while the pet is shaking, returns
+    // whether it's shaking or just stopped
+    fn update_shake(&mut self) -> bool {
+        let mut shared = self.shared.borrow_mut();
+        let Some(shake) = shared.shake else {
+            return false;
+        };
+
+        let now = shared.elapsed;
+        if shake.is_done(now) {
+            shared.shake = None;
+            shared.shake_offset = (0, 0);
+            return true;
+        }
+
+        let amplitude = shake.value(now).max(0.0);
+        let dx = shared.rng.gen_range(-amplitude..=amplitude).round() as i32;
+        let dy = shared.rng.gen_range(-amplitude..=amplitude).round() as i32;
+        shared.shake_offset = (dx, dy);
+        true
+    }
+
+    // A state name returned by a handler, nil stays in the current state
+    fn request_state_from(&self, handler: &str, next: Value) -> Result<(), Error> {
+        let name = match next {
+            Value::Nil => return Ok(()),
+            Value::String(name) => name.to_str().map_err(Error::Lua)?.to_string(),
+            other => return Err(Error::Lua(mlua::Error::RuntimeError(format!(
+                "{handler} returned a {}, it can only return a state name or nil", other.type_name())))),
+        };
+
+        let mut shared = self.shared.borrow_mut();
+        if !shared.state_names.contains(&name) {
+            return Err(Error::Lua(mlua::Error::RuntimeError(format!("{handler} returned an unknown state '{name}'"))));
+        }
+
+        shared.pending_state = Some(name);
+        Ok(())
+    }
+
+    // Calls back for the stats that reached a threshold, with the stat's
+    // name and value
+    fn run_stat_callbacks(&mut self) -> Result<(), Error> {
+        let crossings = self.shared.borrow_mut().stats.take_crossings();
+
+        for (name, crossing) in crossings {
+            if self.is_disabled("Stat_threshold") {
+                break;
+            }
+
+            let callback = self.shared.borrow().stat_callbacks.get(&(name.clone(), crossing))
+                .map(|key| self.pet.lua.registry_value::<Function>(key));
+            let value = self.shared.borrow().stats.get(&name);
+
+            let result = match callback {
+                Some(f) => f.and_then(|f| f.call::<_, ()>((name, value))).map_err(Error::Lua),
+                None => Ok(()),
+            };
+            self.guard("Stat_threshold", result)?;
+        }
+
+        Ok(())
+    }
+
+    // Moves on to the next thing to say once the shown one's time is up,
+    // returns whether what the pet says changed
+    fn update_speech(&mut self) -> bool {
+        let mut shared = self.shared.borrow_mut();
+        let now = shared.elapsed;
+        let mut changed = std::mem::take(&mut shared.speech_changed);
+
+        if shared.speech_until.is_some_and(|until| now >= until) {
+            shared.speech.pop_front();
+            shared.speech_until = None;
+            changed = true;
+        }
+        if shared.speech_until.is_none() {
+            shared.speech_until = shared.speech.front().map(|speech| now.saturating_add(speech.duration));
+        }
+
+        changed
+    }
+
+    pub fn cursor(&self) -> Option<PetCursor> {
+        self.shared.borrow().cursor
+    }
+
+    pub fn speech(&self) -> Option<String> {
+        self.shared.borrow().speech.front().map(|speech| speech.text.clone())
+    }
+
+    // A question from the program rather than the pet, like whether to quit
+    pub fn set_prompt(&mut self, prompt: Option<&str>) {
+        self.shared.borrow_mut().prompt = prompt.map(str::to_string);
+    }
+
+    // The rows what the pet says goes on, right above the frame starting at
+    // row `y`, or below it when there's no room above
+    fn speech_lines(&self, y: u16, rows: u16) -> Vec<(u16, String)> {
+        let Some(text) = self.shared.borrow().prompt.clone().or_else(|| self.speech()) else {
+            return Vec::new();
+        };
+
+        let lines: Vec<_> = text.lines().map(str::to_string).collect();
+        let count = u16::try_from(lines.len()).unwrap_or(u16::MAX);
+        let top = y.checked_sub(count).unwrap_or_else(||
+            y.saturating_add(u16::try_from(self.current_animation().height).unwrap_or(u16::MAX)));
+
+        lines.into_iter()
+            .enumerate()
+            .map(|(i, line)| (top.saturating_add(i as u16), line))
+            .filter(|(row, _)| *row < rows)
+            .collect()
+    }
+
+    // Moves every track whose frame has been shown long enough on to its next
+    // frame, returns whether any did
+    fn advance_tracks(&mut self, now: Instant) -> bool {
+        let speed = self.shared.borrow().speed;
+        let mut shared = self.shared.borrow_mut();
+        let mut changed = false;
+
+        for track in &mut shared.tracks {
+            let Some(anim) = self.pet.animations.get(&track.anim) else {
+                continue;
+            };
+            let delay = Duration::from_millis(anim.metadata.delay).div_f64(speed);
+
+            match track.last_advance {
+                None => track.last_advance = Some(now),
+                Some(last) if now.duration_since(last) >= delay => {
+                    track.frame = next_frame(&track.frame, anim);
+                    track.last_advance = Some(now);
+                },
+                _ => continue,
+            }
+            changed = true;
+        }
+
+        changed
+    }
+
+    // The recent state and animation changes, oldest first
+    pub fn history(&self) -> impl Iterator<Item = &Transition> {
+        self.history.iter()
+    }
+
+    fn record_transitions(&mut self, now: Instant) {
+        let started = self.started.unwrap_or(now);
+        let (state, anim) = {
+            let shared = self.shared.borrow();
+            (shared.current_state.clone(), shared.current_anim.clone())
+        };
+
+        // The first tick only sets the baseline
+        if self.last_seen.0.is_empty() {
+            self.last_seen = (state, anim);
+            return;
+        }
+
+        let at = now.duration_since(started);
+        let changes = [
+            (TransitionKind::State, &self.last_seen.0, &state),
+            (TransitionKind::Animation, &self.last_seen.1, &anim),
+        ];
+
+        for (kind, from, to) in changes {
+            if from == to {
+                continue;
+            }
+
+            log::debug!("{}: {kind:?} {from} -> {to}", self.shared.borrow().pet_id);
+
+            if self.history.len() == HISTORY_SIZE {
+                self.history.pop_front();
+            }
+            self.history.push_back(Transition { at, kind, from: from.clone(), to: to.clone() });
+        }
+
+        self.last_seen = (state, anim);
+    }
+
+    fn run_script(&mut self, now: Instant) -> Result<(), Error> {
+        let current_state = self.shared.borrow().current_state.clone();
+
+        // (Re)start the coroutine whenever the state changes
+        if self.script.as_ref().is_none_or(|s| s.state != current_state) {
+            self.script = match &self.state().event_handlers.main {
+                Some(main) => Some(Script {
+                    state: current_state,
+                    thread: self.pet.lua.create_thread(main.clone()).map_err(Error::Lua)?,
+                    resume_at: Some(now),
+                }),
+                None => None,
+            };
+        }
+
+        let speed = self.speed();
+        let Some(script) = &mut self.script else {
+            return Ok(());
+        };
+
+        if script.resume_at.is_none_or(|at| now < at) {
+            return Ok(());
+        }
+
+        let yielded = script.thread.resume::<_, Value>(()).map_err(|e| {
+            script.resume_at = None;
+            Error::Lua(e)
+        })?;
+
+        script.resume_at = match script.thread.status() {
+            ThreadStatus::Resumable => {
+                let ms = match yielded {
+                    Value::Integer(ms) => ms.max(0) as u64,
+                    Value::Number(ms) => ms.max(0.0) as u64,
+                    _ => 0,
+                };
+                Some(now + Duration::from_millis(ms).div_f64(speed))
+            },
+            _ => None,
+        };
+
+        Ok(())
+    }
+
+    // Falls back to the state's animation if the current one went missing
+    fn ensure_animation(&mut self) {
+        let base_anim = self.state().metadata.animation.clone();
+        let mut shared = self.shared.borrow_mut();
+
+        if !self.pet.animations.contains_key(&shared.current_anim) {
+            log::warn!("The animation '{}' doesn't exist, falling back to '{}'",
+                shared.current_anim, base_anim);
+
+            shared.current_frame = self.pet.animations.get(&base_anim)
+                .map_or(0, |a| a.metadata.start_frame);
+            shared.current_anim = base_anim;
+        }
+    }
+
+    // Returns the name of the animation if it wrapped around past its last
+    // frame, whether it starts over or another one of the state's takes over
+    fn advance_frame(&mut self) -> Option<String> {
+        let base_anim = self.state().metadata.animation.clone();
+        let anim = self.current_animation();
+        let next = next_frame(&self.current_frame(), anim);
+        let finished = self.current_frame() == anim.frames.len() - 1;
+        let is_base = anim.name == base_anim;
+
+        if finished {
+            let mut shared = self.shared.borrow_mut();
+            // What the script asked for goes before what the animation says.
+            // The state's own animation loops, so it never completes.
+            let on_complete = anim.metadata.on_complete_state.clone().filter(|_| !is_base);
+            if let Some(state) = shared.state_after_anim.take().or(on_complete) {
+                shared.pending_state = Some(state);
+                return None;
+            }
+        }
+
+        let wrapped = finished.then(|| anim.name.clone());
+
+        let follow_up = if !finished {
+            None
+        } else if let Some(idle_anim) = self.pick_idle_anim() {
+            Some(idle_anim)
+        } else if !is_base {
+            // Non-default animations play once and return to the state's animation
+            Some(base_anim)
+        } else {
+            None
+        };
+
+        let mut shared = self.shared.borrow_mut();
+
+        match follow_up {
+            Some(anim) => {
+                shared.current_frame = self.pet.animations.get(&anim).unwrap().metadata.start_frame;
+                shared.current_anim = anim;
+            },
+            None => shared.current_frame = next,
+        }
+
+        wrapped
+    }
+
+    // Moves on to one of the state's idle_next states once its idle timeout
+    // has passed, unless something else already switches states
+    fn check_idle(&mut self) {
+        let Some(timeout) = self.state().metadata.idle_timeout_ms else {
+            return;
+        };
+
+        let elapsed = self.shared.borrow().elapsed;
+        let idle_since = *self.idle_since.get_or_insert(elapsed);
+        if elapsed.saturating_sub(idle_since) < self.scaled(timeout) || self.shared.borrow().pending_state.is_some() {
+            return;
+        }
+
+        let mut shared = self.shared.borrow_mut();
+        let Some(state) = self.pet.states.get(&shared.current_state) else {
+            return;
+        };
+        let pool = &state.metadata.idle_next;
+        if let Ok(weights) = WeightedIndex::new(pool.iter().map(|(_, weight)| *weight)) {
+            shared.pending_state = Some(pool[weights.sample(&mut shared.rng)].0.clone());
+        }
+    }
+
+    fn pick_idle_anim(&mut self) -> Option<String> {
+        let mut shared = self.shared.borrow_mut();
+        let pool = &self.pet.states.get(&shared.current_state)?.metadata.idle_anims;
+        let weights = WeightedIndex::new(pool.iter().map(|(_, weight)| *weight)).ok()?;
+
+        Some(pool[weights.sample(&mut shared.rng)].0.clone())
+    }
+
+    // Keeps the state's music playing, and stops it on leaving the state
+    fn update_music(&mut self) {
+        let music = self.state().metadata.music.clone();
+
+        // Missing music shouldn't take the pet down either
+        let result = self.music.switch(&self.sounds, music.as_deref())
+            .and_then(|_| self.music.update(&self.sounds));
+
+        if let Err(e) = result {
+            log::warn!("{}: the music '{}' couldn't be played: {e}", self.id(), music.unwrap_or_default());
+        }
+    }
+
+    pub fn stop_music(&mut self) {
+        self.music.stop();
+    }
+
+    fn play_frame_sound(&mut self) {
+        let anim = self.current_animation();
+        let shown = (anim.name.clone(), self.current_frame());
+
+        if self.last_sound_frame.as_ref() != Some(&shown) {
+            if let Some(sound) = anim.frame_sounds.get(&shown.1) {
+                // A missing sound shouldn't take the pet down
+                let _ = self.sounds.play(sound);
+            }
+            self.last_sound_frame = Some(shown);
+        }
+    }
+
+    pub fn speed(&self) -> f64 {
+        self.shared.borrow().speed
+    }
+
+    pub fn set_speed(&mut self, speed: f64) {
+        self.shared.borrow_mut().speed = clamp_speed(speed);
+    }
+
+    // A delay in milliseconds, adjusted to the speed
+    fn scaled(&self, ms: u64) -> Duration {
+        Duration::from_millis(ms).div_f64(self.speed())
+    }
+
+    pub fn id(&self) -> String {
+        self.shared.borrow().pet_id.clone()
+    }
+
+    // Lets the pet talk to the other pets on `bus`
+    pub fn join_bus(&mut self, bus: &Rc<RefCell<MessageBus>>) {
+        let mut shared = self.shared.borrow_mut();
+        bus.borrow_mut().register(&shared.pet_id);
+        shared.bus = bus.clone();
+    }
+
+    pub fn receive(&mut self, message: Message) -> Result<(), Error> {
+        if self.is_disabled("Message_received") {
+            return Ok(());
+        }
+
+        let result = match &self.state().event_handlers.message_received {
+            Some(f) => message.payload.into_lua(self.pet.lua)
+                .and_then(|payload| f.call::<_, ()>((message.sender, message.event, payload)))
+                .map_err(Error::Lua),
+            None => Ok(()),
+        };
+
+        self.guard("Message_received", result)
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> Result<(), Error> {
+        {
+            let mut shared = self.shared.borrow_mut();
+            let name = key_name(key.code, key.modifiers);
+
+            if key.kind == KeyEventKind::Release {
+                shared.held_keys.remove(&name);
+                shared.reports_releases = true;
+            } else {
+                let now = shared.elapsed;
+                shared.held_keys.insert(name, now);
+                self.idle_since = Some(now);
+            }
+        }
+
+        if key.kind != KeyEventKind::Release {
+            let name = key_name(key.code, key.modifiers);
+            let transition = self.state().metadata.input_transitions.get(&name).cloned();
+
+            let mut shared = self.shared.borrow_mut();
+            // Queued like set_current_state, so it's this state's Key_down
+            // that gets the key, and it can still go somewhere else
+            if transition.is_some() {
+                shared.pending_state = transition;
+            }
+            if shared.key_buffer.len() == KEY_BUFFER_SIZE {
+                shared.key_buffer.pop_front();
+            }
+            shared.key_buffer.push_back(name);
+        }
+
+        let handlers = &self.state().event_handlers;
+        let (name, handler) = match key.kind {
+            KeyEventKind::Release => ("Key_up", &handlers.key_up),
+            _ => ("Key_down", &handlers.key_down),
+        };
+
+        if self.is_disabled(name) {
+            return Ok(());
+        }
+
+        let result = match handler {
+            Some(f) => self.call_key_handler(f, key).map_err(Error::Lua),
+            None => Ok(()),
+        };
+
+        self.guard(name, result)
+    }
+
+    fn call_key_handler(&self, f: &mlua::Function<'lua>, key: KeyEvent) -> mlua::Result<()> {
+        let modifiers = self.pet.lua.create_table()?;
+        modifiers.set("ctrl", key.modifiers.contains(KeyModifiers::CONTROL))?;
+        modifiers.set("shift", key.modifiers.contains(KeyModifiers::SHIFT))?;
+        modifiers.set("alt", key.modifiers.contains(KeyModifiers::ALT))?;
+
+        f.call::<_, ()>((key_name(key.code, key.modifiers), modifiers))
+    }
+
+    // The current frame as it's drawn, mirrored if the pet is flipped
+    pub fn displayed_frame(&self) -> Cow<'_, str> {
+        self.as_displayed(&self.current_animation().frames[self.current_frame()])
+    }
+
+    // With the template variables filled in, then mirrored if the pet is
+    // flipped
+    fn as_displayed<'a>(&self, frame: &'a str) -> Cow<'a, str> {
+        let shared = self.shared.borrow();
+
+        if shared.flip {
+            // Reversed, so they read the right way round once mirrored
+            let reversed = shared.template_vars.iter()
+                .map(|(key, value)| (key.clone(), graphemes(value).rev().collect()))
+                .collect();
+            Cow::Owned(mirror_frame(&fill_template(frame, &reversed), &self.mirror_map))
+        } else {
+            fill_template(frame, &shared.template_vars)
+        }
+    }
+
+    // The lines of a frame that aren't scrolled off the screen
+    fn visible_lines<'a>(&self, frame: &'a str) -> Vec<Cow<'a, str>> {
+        let (columns, rows) = self.shared.borrow().pan;
+
+        frame.lines()
+            .skip(usize::from(rows))
+            .map(|line| match columns {
+                0 => Cow::Borrowed(line),
+                columns => Cow::Owned(skip_columns(line, usize::from(columns))),
+            })
+            .collect()
+    }
+
+    // With --scroll, how far frames bigger than the screen are scrolled.
+    // Returns whether it moved.
+    pub fn set_pan(&mut self, pan: (u16, u16)) -> bool {
+        let mut shared = self.shared.borrow_mut();
+        let moved = shared.pan != pan;
+        shared.pan = pan;
+        moved
+    }
+
+    // The size of the current animation's biggest frame
+    pub fn animation_size(&self) -> (u16, u16) {
+        let anim = self.current_animation();
+        (u16::try_from(anim.width).unwrap_or(u16::MAX), u16::try_from(anim.height).unwrap_or(u16::MAX))
+    }
+
+    // The tracks' frames as they're drawn, bottom to top
+    fn track_layers(&self) -> Vec<(&Animation, Cow<'_, str>)> {
+        let tracks: Vec<_> = self.shared.borrow().tracks.iter()
+            .map(|track| (track.anim.clone(), track.frame))
+            .collect();
+
+        tracks.into_iter()
+            .filter_map(|(anim, frame)| {
+                let anim = self.pet.animations.get(&anim)?;
+                Some((anim, self.as_displayed(anim.frames.get(frame)?)))
+            })
+            .collect()
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.shared.borrow().visible
+    }
+
+    // Writes the frame out as lines ended with \n
+    pub fn render_to(&self, buf: &mut impl Write) -> io::Result<()> {
+        self.render_to_with(buf, LineEnding::Lf)
+    }
+
+    // Like `render_to`, with the lines ended with `ending`. The last one
+    // isn't ended.
+    pub fn render_to_with(&self, buf: &mut impl Write, ending: LineEnding) -> io::Result<()> {
+        if !self.is_visible() {
+            return Ok(());
+        }
+
+        write_lines(buf, self.displayed_frame().lines(), ending)?;
+        buf.flush()
+    }
+
+    // Where to draw the current frame inside an area of the terminal,
+    // according to the pet's anchor
+    pub fn anchored_origin(&self, area_origin: (u16, u16), area_size: (u16, u16)) -> (u16, u16) {
+        match self.pet.metadata.anchor {
+            Anchor::TopLeft => area_origin,
+            Anchor::Center => {
+                let anim = self.current_animation();
+                let frame = &anim.frames[self.current_frame()];
+                let width = u16::try_from(frame_width(frame)).unwrap_or(u16::MAX);
+                // Bottom aligned frames are centered as a whole so their bottom edge stays put
+                let lines = match anim.metadata.baseline {
+                    Baseline::Top => frame.lines().count(),
+                    Baseline::Bottom => anim.height,
+                };
+                let height = u16::try_from(lines).unwrap_or(u16::MAX);
+
+                (area_origin.0 + area_size.0.saturating_sub(width) / 2,
+                    area_origin.1 + area_size.1.saturating_sub(height) / 2)
+            },
+        }
+    }
+
+    // Draws the frame on a terminal with its top left corner at `origin`,
+    // moved by the frame's offset and kept within `size`. It goes through a
+    // canvas like `render_into`, so the whole of `size` is written.
+    pub fn render_at(&self, buf: &mut impl Write, origin: (u16, u16), size: (u16, u16)) -> io::Result<()> {
+        if !self.is_visible() {
+            return Ok(());
+        }
+
+        let mut canvas = Canvas::new(size);
+        self.render_into(&mut canvas, origin);
+        canvas.write_to(buf)?;
+        buf.flush()
+    }
+
+    // Draws the frame into a canvas, which may be shared with other pets
+    pub fn render_into(&self, canvas: &mut Canvas, origin: (u16, u16)) {
+        if !self.is_visible() {
+            return;
+        }
+
+        let anim = self.current_animation();
+        let frame = self.current_frame();
+        let size = canvas.size();
+        let (dx, dy) = anim.frame_offset(frame);
+        let dx = if self.shared.borrow().flip { -dx } else { dx };
+        let dy = dy.saturating_add(i32::try_from(anim.baseline_offset(frame)).unwrap_or(i32::MAX));
+        let (shake_x, shake_y) = self.shared.borrow().shake_offset;
+        let (dx, dy) = (dx.saturating_add(shake_x), dy.saturating_add(shake_y));
+
+        let x = clamp_to(i32::from(origin.0) + dx, size.0);
+        let y = clamp_to(i32::from(origin.1) + dy, size.1);
+
+        let color_depth = self.shared.borrow().color_depth;
+        let color = anim.color.and_then(|rgb| color_depth.convert(rgb));
+
+        // The canvas isn't cleared while other pets show overlay animations,
+        // so blank the whole bounding box rather than relying on the frame's
+        // transparent cells
+        if !anim.metadata.overlay {
+            let width = u16::try_from(anim.width).unwrap_or(u16::MAX);
+            let height = u16::try_from(anim.height).unwrap_or(u16::MAX);
+            canvas.clear_rect(x, y, width, height);
+        }
+
+        let displayed = self.displayed_frame();
+        for (i, line) in self.visible_lines(&displayed).iter().enumerate() {
+            let row = y + i as u16;
+            if row >= size.1 {
+                break;
+            }
+
+            canvas.put_str(x, row, line, color, Some(anim.metadata.transparent));
+        }
+
+        for (track_anim, frame) in self.track_layers() {
+            let color = track_anim.color.and_then(|rgb| color_depth.convert(rgb));
+
+            for (i, line) in self.visible_lines(&frame).iter().enumerate() {
+                let row = y + i as u16;
+                if row >= size.1 {
+                    break;
+                }
+
+                canvas.put_str(x, row, line, color, Some(track_anim.metadata.transparent));
+            }
+        }
+
+        for (row, line) in self.speech_lines(y, size.1) {
+            canvas.put_str(x, row, &line, None, None);
+        }
+
+        let mut buf = [0; 4];
+        for ((x, y), c) in self.shared.borrow().overlay.cells() {
+            canvas.put_str(x, y, c.encode_utf8(&mut buf), None, None);
+        }
+    }
+
+    // For `render_into` at `origin`
+    pub fn render_key(&self, origin: (u16, u16)) -> RenderKey {
+        let shared = self.shared.borrow();
+
+        RenderKey {
+            anim: shared.current_anim.clone(),
+            frame: shared.current_frame,
+            flip: shared.flip,
+            visible: shared.visible,
+            origin,
+            overlay: shared.overlay.revision(),
+            tracks: shared.tracks.iter().map(|track| (track.anim.clone(), track.frame)).collect(),
+            shake_offset: shared.shake_offset,
+            template_vars: shared.template_vars.clone(),
+            pan: shared.pan,
+            speech: shared.prompt.clone().or_else(|| shared.speech.front().map(|speech| speech.text.clone())),
+            cursor: shared.cursor,
+        }
+    }
+
+    pub fn z(&self) -> i32 {
+        self.pet.metadata.z
+    }
+
+    // Overlay animations are drawn over the previous frame
+    pub fn needs_clear(&self) -> bool {
+        !self.current_animation().metadata.overlay
+    }
+}
+
+// Handlers that don't belong to a state, from global.lua or given to
+// `register_stat`
+const GLOBAL_HANDLERS: &[&str] = &["Tick", "Stat_threshold"];
+
+// How many transitions the history keeps
+const HISTORY_SIZE: usize = 32;
+
+// Older keys are dropped once a script hasn't read this many
+const KEY_BUFFER_SIZE: usize = 32;
+
+// How long a key counts as held after it was last pressed or repeated, on
+// terminals that don't report releases. Longer than the usual delay before
+// a held key starts repeating.
+pub const KEY_HOLD_TIMEOUT: Duration = Duration::from_millis(600);
+
+// How long `say` shows a line when it isn't given a duration
+pub const SPEECH_DURATION: Duration = Duration::from_millis(2000);
+
+// Further lines are dropped while this many are waiting to be said
+pub const SPEECH_QUEUE_SIZE: usize = 16;
+
+pub const MIN_SPEED: f64 = 0.1;
+pub const MAX_SPEED: f64 = 10.0;
+
+pub fn clamp_speed(speed: f64) -> f64 {
+    if speed.is_nan() {
+        1.0
+    } else {
+        speed.clamp(MIN_SPEED, MAX_SPEED)
+    }
+}
+
+// Seconds since the Unix epoch, 0 if the clock is set before it
+pub fn unix_time() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs())
+}
+
+fn clamp_to(position: i32, size: u16) -> u16 {
+    position.clamp(0, i32::from(size.saturating_sub(1))) as u16
+}
+
+// The frame after `frame`, wrapping around to the first one. Relies on
+// `frames` never being empty, which `Animation::load` enforces.
+pub fn next_frame(frame: &usize, animation: &Animation) -> usize {
+    if *frame < animation.frames.len() - 1 {
+        frame + 1
+    } else {
+        0
+    }
+}
+
+// The name a key is passed to Lua by, prefixed with the held modifiers
+// like "ctrl+alt+r". Shift is left out for characters, where it's already
+// part of the character.
+pub fn key_name(code: KeyCode, modifiers: KeyModifiers) -> String {
+    let mut name = String::new();
+
+    if modifiers.contains(KeyModifiers::CONTROL) {
+        name.push_str("ctrl+");
+    }
+    if modifiers.contains(KeyModifiers::ALT) {
+        name.push_str("alt+");
+    }
+    if modifiers.contains(KeyModifiers::SHIFT) && !matches!(code, KeyCode::Char(_)) {
+        name.push_str("shift+");
+    }
+
+    name.push_str(&base_key_name(code));
+    name
+}
+
+fn base_key_name(code: KeyCode) -> String {
+    match code {
+        KeyCode::Char(' ') => "space".to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::F(n) => format!("f{n}"),
+        KeyCode::Backspace => "backspace".to_string(),
+        KeyCode::Enter => "enter".to_string(),
+        KeyCode::Left => "left".to_string(),
+        KeyCode::Right => "right".to_string(),
+        KeyCode::Up => "up".to_string(),
+        KeyCode::Down => "down".to_string(),
+        KeyCode::Home => "home".to_string(),
+        KeyCode::End => "end".to_string(),
+        KeyCode::PageUp => "pageup".to_string(),
+        KeyCode::PageDown => "pagedown".to_string(),
+        KeyCode::Tab => "tab".to_string(),
+        KeyCode::BackTab => "backtab".to_string(),
+        KeyCode::Delete => "delete".to_string(),
+        KeyCode::Insert => "insert".to_string(),
+        KeyCode::Esc => "esc".to_string(),
+        other => format!("{other:?}").to_lowercase(),
+    }
+}
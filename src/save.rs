@@ -0,0 +1,98 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use mlua::{Lua, Value};
+use serde::{Deserialize, Serialize};
+
+use crate::pet::Error;
+
+// What's kept of a pet between runs
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct SaveData {
+    // Only saved for pets with `persist_state`
+    #[serde(default)]
+    pub state: Option<String>,
+    // When the pet was first run, in seconds since the Unix epoch. Ahead of
+    // the tables, TOML can't have plain values after them.
+    #[serde(default)]
+    pub first_run: Option<u64>,
+    // Written with `store_set`
+    #[serde(default)]
+    pub store: BTreeMap<String, StoreValue>,
+    // Written with `increment_counter`
+    #[serde(default)]
+    pub counters: BTreeMap<String, i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum StoreValue {
+    Bool(bool),
+    Integer(i64),
+    Number(f64),
+    String(String),
+}
+
+impl StoreValue {
+    pub fn from_lua(value: Value) -> mlua::Result<Self> {
+        Ok(match value {
+            Value::Boolean(b) => Self::Bool(b),
+            Value::Integer(i) => Self::Integer(i),
+            Value::Number(n) => Self::Number(n),
+            Value::String(s) => Self::String(s.to_str()?.to_string()),
+            other => return Err(mlua::Error::RuntimeError(format!(
+                "the store can't hold a {}", other.type_name()))),
+        })
+    }
+
+    pub fn into_lua(self, lua: &Lua) -> mlua::Result<Value<'_>> {
+        Ok(match self {
+            Self::Bool(b) => Value::Boolean(b),
+            Self::Integer(i) => Value::Integer(i),
+            Self::Number(n) => Value::Number(n),
+            Self::String(s) => Value::String(lua.create_string(s)?),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct SaveFile {
+    pub path: PathBuf,
+    pub data: SaveData,
+}
+
+impl SaveFile {
+    // A missing save file is an empty save
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let data = match fs::read_to_string(path) {
+            Ok(toml_string) => toml::de::from_str(&toml_string).map_err(Error::TomlDeserializer)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => SaveData::default(),
+            Err(e) => return Err(Error::IO(e)),
+        };
+
+        Ok(Self { path: path.to_path_buf(), data })
+    }
+
+    // Written next to the save and renamed over it, so a crash halfway
+    // through leaves the old save rather than half of the new one
+    pub fn write(&self) -> Result<(), Error> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).map_err(Error::IO)?;
+        }
+
+        let toml_string = toml::to_string(&self.data).map_err(Error::TomlSerializer)?;
+
+        let mut temp_name = self.path.file_name().unwrap_or_default().to_os_string();
+        temp_name.push(".tmp");
+        let temp_path = self.path.with_file_name(temp_name);
+
+        let mut file = fs::File::create(&temp_path).map_err(Error::IO)?;
+        file.write_all(toml_string.as_bytes()).map_err(Error::IO)?;
+        file.sync_all().map_err(Error::IO)?;
+        fs::rename(&temp_path, &self.path).map_err(Error::IO)
+    }
+}
@@ -0,0 +1,183 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    env,
+    fs,
+    io,
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+};
+
+use crate::pet::Error;
+
+// Players that can play a file given as their only argument
+const PLAYERS: [&str; 3] = ["paplay", "aplay", "afplay"];
+
+fn find_player() -> Option<PathBuf> {
+    let path = env::var_os("PATH")?;
+
+    env::split_paths(&path)
+        .flat_map(|dir| PLAYERS.iter().map(move |p| dir.join(p)))
+        .find(|p| p.is_file())
+}
+
+// Plays the sound files of a pet (`<pet>/sound/<name>.<ext>`) through
+// whichever system player is available. Without one, sounds are no-ops.
+// The files are looked up once, when the pet loads.
+#[derive(Debug)]
+pub struct Sounds {
+    files: HashMap<String, PathBuf>,
+    player: Option<PathBuf>,
+    // One-shot sounds still playing, waited on once they're done so they
+    // don't linger as zombies
+    playing: RefCell<Vec<Child>>,
+}
+
+impl Sounds {
+    pub fn new(pet_path: &Path) -> Self {
+        // A pet without sounds doesn't need the directory
+        let files = fs::read_dir(pet_path.join("sound"))
+            .into_iter()
+            .flatten()
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|p| p.is_file())
+            .filter_map(|p| Some((p.file_stem()?.to_str()?.to_string(), p)))
+            .collect();
+
+        Self {
+            files,
+            player: find_player(),
+            playing: RefCell::new(Vec::new()),
+        }
+    }
+
+    pub fn resolve(&self, name: &str) -> Result<PathBuf, Error> {
+        if name.is_empty() || name.contains(['/', '\\']) || name.contains("..") {
+            return Err(Error::InvalidFileName);
+        }
+
+        self.files.get(name)
+            .cloned()
+            .ok_or_else(|| Error::IO(io::Error::new(io::ErrorKind::NotFound,
+                format!("Sound '{name}' not found"))))
+    }
+
+    pub fn play(&self, name: &str) -> Result<(), Error> {
+        let file = self.resolve(name)?;
+        if let Some(child) = self.spawn(&file)? {
+            self.playing.borrow_mut().push(child);
+        }
+
+        Ok(())
+    }
+
+    // Waits on the sounds that have finished playing
+    pub fn reap(&self) {
+        self.playing.borrow_mut().retain_mut(|child| matches!(child.try_wait(), Ok(None)));
+    }
+
+    // `None` without a player
+    fn spawn(&self, file: &Path) -> Result<Option<Child>, Error> {
+        let Some(player) = &self.player else {
+            return Ok(None);
+        };
+
+        Command::new(player)
+            .arg(file)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map(Some)
+            .map_err(Error::IO)
+    }
+}
+
+impl Drop for Sounds {
+    fn drop(&mut self) {
+        for mut child in self.playing.get_mut().drain(..) {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+// A single looping track, separate from one-shot sounds. It's stopped when
+// dropped, so music never outlives the pet.
+#[derive(Debug, Default)]
+pub struct Music {
+    track: Option<Track>,
+    // A track that couldn't be played isn't retried until another one is asked for
+    failed: Option<String>,
+}
+
+#[derive(Debug)]
+struct Track {
+    name: String,
+    file: PathBuf,
+    child: Option<Child>,
+}
+
+impl Music {
+    pub fn playing(&self) -> Option<&str> {
+        self.track.as_ref().map(|t| t.name.as_str())
+    }
+
+    // Switches to another track, or stops with `None`. The same track keeps
+    // playing instead of starting over.
+    pub fn switch(&mut self, sounds: &Sounds, name: Option<&str>) -> Result<(), Error> {
+        if self.playing() == name || (name.is_some() && self.failed.as_deref() == name) {
+            return Ok(());
+        }
+
+        self.stop();
+        self.failed = None;
+
+        if let Some(name) = name {
+            let started = sounds.resolve(name)
+                .and_then(|file| Ok(Track { name: name.to_string(), child: sounds.spawn(&file)?, file }));
+
+            match started {
+                Ok(track) => self.track = Some(track),
+                Err(e) => {
+                    self.failed = Some(name.to_string());
+                    return Err(e);
+                },
+            }
+        }
+
+        Ok(())
+    }
+
+    // Starts the track over once it has finished
+    pub fn update(&mut self, sounds: &Sounds) -> Result<(), Error> {
+        let Some(track) = &mut self.track else {
+            return Ok(());
+        };
+
+        let finished = match &mut track.child {
+            Some(child) => child.try_wait().map_err(Error::IO)?.is_some(),
+            None => false,
+        };
+
+        if finished {
+            track.child = sounds.spawn(&track.file)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(Track { child: Some(mut child), .. }) = self.track.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+impl Drop for Music {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
@@ -0,0 +1,132 @@
+use std::{collections::HashMap, time::Duration};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stat {
+    pub value: f64,
+    // Change per second, negative values decay towards `min`
+    pub rate_per_sec: f64,
+    pub min: f64,
+    pub max: f64,
+    pub thresholds: Thresholds,
+    // Whether the value is past a threshold it was reported crossing
+    above_high: bool,
+    below_low: bool,
+}
+
+// Values a stat reports reaching, once each time. After reaching one it
+// has to come back by `hysteresis` before it's reported again, so a value
+// hovering around a threshold isn't reported over and over.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Thresholds {
+    pub high: Option<f64>,
+    pub low: Option<f64>,
+    pub hysteresis: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Crossing {
+    High,
+    Low,
+}
+
+impl Stat {
+    pub fn new(initial: f64, rate_per_sec: f64, min: f64, max: f64) -> Result<Self, String> {
+        if min > max {
+            return Err(format!("min ({min}) is greater than max ({max})"));
+        }
+
+        Ok(Self {
+            value: initial.clamp(min, max),
+            rate_per_sec,
+            min,
+            max,
+            thresholds: Thresholds::default(),
+            above_high: false,
+            below_low: false,
+        })
+    }
+
+    // A value that starts out past a threshold isn't reported until it has
+    // come back and crossed it again
+    pub fn with_thresholds(mut self, thresholds: Thresholds) -> Result<Self, String> {
+        if !(thresholds.hysteresis >= 0.0 && thresholds.hysteresis.is_finite()) {
+            return Err(format!("the hysteresis ({}) has to be a positive number", thresholds.hysteresis));
+        }
+        if let (Some(high), Some(low)) = (thresholds.high, thresholds.low) {
+            if low >= high {
+                return Err(format!("the low threshold ({low}) isn't below the high one ({high})"));
+            }
+        }
+
+        self.above_high = thresholds.high.is_some_and(|high| self.value >= high);
+        self.below_low = thresholds.low.is_some_and(|low| self.value <= low);
+        self.thresholds = thresholds;
+        Ok(self)
+    }
+
+    // Returns the threshold the value reached, if it did
+    pub fn adjust(&mut self, delta: f64) -> Option<Crossing> {
+        self.value = (self.value + delta).clamp(self.min, self.max);
+
+        let Thresholds { high, low, hysteresis } = self.thresholds;
+        if self.above_high && high.is_some_and(|high| self.value < high - hysteresis) {
+            self.above_high = false;
+        }
+        if self.below_low && low.is_some_and(|low| self.value > low + hysteresis) {
+            self.below_low = false;
+        }
+
+        if !self.above_high && high.is_some_and(|high| self.value >= high) {
+            self.above_high = true;
+            Some(Crossing::High)
+        } else if !self.below_low && low.is_some_and(|low| self.value <= low) {
+            self.below_low = true;
+            Some(Crossing::Low)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Stats {
+    stats: HashMap<String, Stat>,
+    // Thresholds reached since `take_crossings` was last called
+    crossings: Vec<(String, Crossing)>,
+}
+
+impl Stats {
+    pub fn register(&mut self, name: String, stat: Stat) {
+        self.stats.insert(name, stat);
+    }
+
+    pub fn get(&self, name: &str) -> Option<f64> {
+        self.stats.get(name).map(|s| s.value)
+    }
+
+    pub fn adjust(&mut self, name: &str, delta: f64) -> bool {
+        match self.stats.get_mut(name) {
+            Some(stat) => {
+                if let Some(crossing) = stat.adjust(delta) {
+                    self.crossings.push((name.to_string(), crossing));
+                }
+                true
+            },
+            None => false,
+        }
+    }
+
+    pub fn decay(&mut self, elapsed: Duration) {
+        let secs = elapsed.as_secs_f64();
+
+        for (name, stat) in &mut self.stats {
+            if let Some(crossing) = stat.adjust(stat.rate_per_sec * secs) {
+                self.crossings.push((name.clone(), crossing));
+            }
+        }
+    }
+
+    pub fn take_crossings(&mut self) -> Vec<(String, Crossing)> {
+        std::mem::take(&mut self.crossings)
+    }
+}
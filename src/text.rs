@@ -0,0 +1,143 @@
+use std::{borrow::Cow, collections::{BTreeMap, HashMap}};
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+// What a line looks like character by character. An emoji with a skin tone
+// or a letter with an accent is one grapheme, even though it's several chars,
+// and splitting it would garble it.
+pub fn graphemes(line: &str) -> impl DoubleEndedIterator<Item = &str> {
+    line.graphemes(true)
+}
+
+// The number of terminal columns a grapheme takes up. Wide characters (CJK,
+// most emoji) take two, lone combining characters none.
+pub fn grapheme_width(grapheme: &str) -> usize {
+    grapheme.width()
+}
+
+// The number of terminal columns a line takes up
+pub fn display_width(line: &str) -> usize {
+    graphemes(line).map(grapheme_width).sum()
+}
+
+// The widest line of a frame
+pub fn frame_width(frame: &str) -> usize {
+    frame.lines().map(display_width).max().unwrap_or(0)
+}
+
+// Pads a line with spaces to `width` columns
+pub fn pad_to_width(line: &str, width: usize) -> String {
+    let padding = width.saturating_sub(display_width(line));
+    format!("{line}{}", " ".repeat(padding))
+}
+
+// Cuts a line to `width` columns, then pads it to exactly that. A wide
+// grapheme that would stick out is dropped whole.
+pub fn fit_to_width(line: &str, width: usize) -> String {
+    let mut fitted = String::new();
+    let mut columns = 0;
+
+    for grapheme in graphemes(line) {
+        columns += grapheme_width(grapheme);
+        if columns > width {
+            break;
+        }
+        fitted.push_str(grapheme);
+    }
+
+    pad_to_width(&fitted, width)
+}
+
+// Drops the first `columns` columns of a line. A wide grapheme cut in half
+// leaves a space in place of its right half.
+pub fn skip_columns(line: &str, columns: usize) -> String {
+    let mut skipped = 0;
+    let mut rest = String::new();
+
+    for grapheme in graphemes(line) {
+        if skipped >= columns {
+            rest.push_str(grapheme);
+            continue;
+        }
+
+        skipped += grapheme_width(grapheme);
+        if skipped > columns {
+            rest.push_str(&" ".repeat(skipped - columns));
+        }
+    }
+
+    rest
+}
+
+// Replaces `{key}` placeholders with the values in `vars`, each cut or padded
+// to the placeholder's own width so the frame keeps its shape. Keys are
+// letters, digits and underscores, placeholders without a value are left as
+// they are. `{{` and `}}` stand for a literal brace, which makes the line one
+// column shorter.
+pub fn fill_template<'a>(frame: &'a str, vars: &BTreeMap<String, String>) -> Cow<'a, str> {
+    if !frame.contains(['{', '}']) {
+        return Cow::Borrowed(frame);
+    }
+
+    let mut filled = String::with_capacity(frame.len());
+    let mut rest = frame;
+
+    while let Some(i) = rest.find(['{', '}']) {
+        filled.push_str(&rest[..i]);
+        let tail = &rest[i..];
+
+        if tail.starts_with("{{") || tail.starts_with("}}") {
+            filled.push_str(&tail[..1]);
+            rest = &tail[2..];
+            continue;
+        }
+
+        let placeholder = tail.strip_prefix('{')
+            .and_then(|after| after.find('}').map(|end| &tail[..end + 2]))
+            .filter(|placeholder| {
+                let key = &placeholder[1..placeholder.len() - 1];
+                !key.is_empty() && key.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_')
+            });
+
+        match placeholder {
+            Some(placeholder) => {
+                match vars.get(&placeholder[1..placeholder.len() - 1]) {
+                    Some(value) => filled.push_str(&fit_to_width(value, placeholder.len())),
+                    None => filled.push_str(placeholder),
+                }
+                rest = &tail[placeholder.len()..];
+            },
+            None => {
+                filled.push_str(&tail[..1]);
+                rest = &tail[1..];
+            },
+        }
+    }
+    filled.push_str(rest);
+
+    Cow::Owned(filled)
+}
+
+// Mirrors a frame horizontally around its widest line, grapheme by grapheme.
+// Characters in `pairs` are swapped for their mirror image, e.g. '/' for '\\'.
+pub fn mirror_frame(frame: &str, pairs: &HashMap<char, char>) -> String {
+    let width = frame_width(frame);
+
+    frame.lines()
+        .map(|line| graphemes(&pad_to_width(line, width))
+            .rev()
+            .map(|grapheme| mirrored(grapheme, pairs))
+            .collect::<String>())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn mirrored(grapheme: &str, pairs: &HashMap<char, char>) -> String {
+    let mut chars = grapheme.chars();
+
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => pairs.get(&c).copied().unwrap_or(c).to_string(),
+        _ => grapheme.to_string(),
+    }
+}
@@ -0,0 +1,72 @@
+use std::{str::FromStr, time::Duration};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Easing {
+    #[default]
+    Linear,
+    // Starts slow and speeds up
+    EaseIn,
+    // Starts fast and slows down
+    EaseOut,
+}
+
+impl FromStr for Easing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "linear" => Ok(Self::Linear),
+            "ease_in" => Ok(Self::EaseIn),
+            "ease_out" => Ok(Self::EaseOut),
+            _ => Err(format!("unknown easing '{s}', expected linear, ease_in or ease_out")),
+        }
+    }
+}
+
+impl Easing {
+    // Maps the progress through the tween, from 0 to 1, to how far the
+    // value has moved from `from` to `to`
+    pub fn apply(self, t: f64) -> f64 {
+        match self {
+            Self::Linear => t,
+            Self::EaseIn => t * t,
+            Self::EaseOut => 1.0 - (1.0 - t) * (1.0 - t),
+        }
+    }
+}
+
+// A number moving from `from` to `to`. Times are since the pet started, the
+// way `RuntimeState::elapsed` counts them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tween {
+    pub from: f64,
+    pub to: f64,
+    pub start: Duration,
+    pub duration: Duration,
+    pub easing: Easing,
+}
+
+impl Tween {
+    pub fn progress(&self, now: Duration) -> f64 {
+        if self.duration.is_zero() {
+            return 1.0;
+        }
+
+        (now.saturating_sub(self.start).as_secs_f64() / self.duration.as_secs_f64()).min(1.0)
+    }
+
+    // Exactly `to` once it's done
+    pub fn value(&self, now: Duration) -> f64 {
+        let t = self.progress(now);
+
+        if t >= 1.0 {
+            self.to
+        } else {
+            self.from + (self.to - self.from) * self.easing.apply(t)
+        }
+    }
+
+    pub fn is_done(&self, now: Duration) -> bool {
+        self.progress(now) >= 1.0
+    }
+}
@@ -0,0 +1,51 @@
+use std::time::{Duration, Instant};
+
+// Sampling is cheap but not free, and faster changes couldn't be read anyway
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+// The CPU and memory use of this process for --debug. Without the
+// system-stats feature there's nothing to show.
+pub struct ProcessUsage {
+    #[cfg(feature = "system-stats")]
+    system: sysinfo::System,
+    last_sample: Option<Instant>,
+    line: Option<String>,
+}
+
+impl ProcessUsage {
+    pub fn new() -> Self {
+        Self {
+            #[cfg(feature = "system-stats")]
+            system: sysinfo::System::new(),
+            last_sample: None,
+            line: None,
+        }
+    }
+
+    // A line like "CPU 1.5% MEM 12.3 MiB", sampled anew if the last sample is
+    // over a second old
+    pub fn line(&mut self, now: Instant) -> Option<&str> {
+        if self.last_sample.is_none_or(|last| now.duration_since(last) >= SAMPLE_INTERVAL) {
+            self.line = self.sample();
+            self.last_sample = Some(now);
+        }
+
+        self.line.as_deref()
+    }
+
+    #[cfg(feature = "system-stats")]
+    fn sample(&mut self) -> Option<String> {
+        let pid = sysinfo::get_current_pid().ok()?;
+        self.system.refresh_process(pid);
+        let process = self.system.process(pid)?;
+
+        // The first sample has nothing to compare with, so its CPU is 0
+        Some(format!("CPU {:.1}% MEM {:.1} MiB",
+            process.cpu_usage(), process.memory() as f64 / (1024.0 * 1024.0)))
+    }
+
+    #[cfg(not(feature = "system-stats"))]
+    fn sample(&mut self) -> Option<String> {
+        None
+    }
+}
@@ -0,0 +1,65 @@
+use std::{cell::RefCell, path::Path, rc::Rc, time::{Duration, Instant}};
+
+use a_duk::{pet::Pet, PetRuntime};
+use mlua::Lua;
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/looping");
+
+fn load<'lua>(lua: &'lua Lua, loops: &Rc<RefCell<Vec<String>>>) -> PetRuntime<'lua> {
+    let loops = loops.clone();
+    lua.globals().set("record", lua.create_function(move |_, name: String| {
+        loops.borrow_mut().push(name);
+        Ok(())
+    }).unwrap()).unwrap();
+
+    PetRuntime::new(Pet::load(lua, Path::new(FIXTURE)).unwrap()).unwrap()
+}
+
+#[test]
+fn anim_loop_fires_when_the_animation_wraps() {
+    let lua = Lua::new();
+    let loops = Rc::new(RefCell::new(Vec::new()));
+    let mut runtime = load(&lua, &loops);
+    let start = Instant::now();
+
+    runtime.tick(start).unwrap();
+    runtime.tick(start + Duration::from_millis(100)).unwrap();
+    assert!(loops.borrow().is_empty());
+
+    runtime.tick(start + Duration::from_millis(200)).unwrap();
+    assert_eq!(runtime.current_frame(), 0);
+    assert_eq!(*loops.borrow(), ["idle"]);
+}
+
+#[test]
+fn animations_that_play_once_loop_back_to_the_state_animation() {
+    let lua = Lua::new();
+    let loops = Rc::new(RefCell::new(Vec::new()));
+    let mut runtime = load(&lua, &loops);
+    let start = Instant::now();
+    runtime.tick(start).unwrap();
+
+    lua.load("set_current_anim('wave')").exec().unwrap();
+    runtime.tick(start + Duration::from_millis(100)).unwrap();
+    // Back to idle after its last frame
+    runtime.tick(start + Duration::from_millis(200)).unwrap();
+    assert_eq!(runtime.current_animation().name, "idle");
+    assert_eq!(*loops.borrow(), ["wave"]);
+}
+
+#[test]
+fn idle_anims_that_pick_the_same_animation_loop() {
+    let lua = Lua::new();
+    let loops = Rc::new(RefCell::new(Vec::new()));
+    let mut runtime = load(&lua, &loops);
+    let start = Instant::now();
+    runtime.set_state("fidgeting").unwrap();
+
+    runtime.tick(start).unwrap();
+    runtime.tick(start + Duration::from_millis(100)).unwrap();
+    assert!(loops.borrow().is_empty());
+
+    runtime.tick(start + Duration::from_millis(200)).unwrap();
+    assert_eq!(runtime.current_frame(), 0);
+    assert_eq!(*loops.borrow(), ["idle"]);
+}
@@ -0,0 +1,32 @@
+use std::path::Path;
+
+use a_duk::{pet::Pet, PetRuntime};
+use mlua::Lua;
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/demo");
+
+#[test]
+fn the_tour_skips_states_left_out_of_demos() {
+    let lua = Lua::new();
+    let mut runtime = PetRuntime::new(Pet::load(&lua, Path::new(FIXTURE)).unwrap()).unwrap();
+
+    assert_eq!(runtime.demo_states(), ["sitting", "walking"]);
+
+    assert!(runtime.next_demo_state().unwrap());
+    assert_eq!(runtime.current_state(), "sitting");
+    assert_eq!(runtime.current_animation().name, "idle");
+
+    assert!(runtime.next_demo_state().unwrap());
+    assert_eq!(runtime.current_state(), "walking");
+    assert_eq!(runtime.current_animation().name, "walk");
+}
+
+#[test]
+fn the_tour_picks_up_after_a_state_outside_of_it() {
+    let lua = Lua::new();
+    let mut runtime = PetRuntime::new(Pet::load(&lua, Path::new(FIXTURE)).unwrap()).unwrap();
+
+    runtime.set_state("secret").unwrap();
+    runtime.next_demo_state().unwrap();
+    assert_eq!(runtime.current_state(), "sitting");
+}
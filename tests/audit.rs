@@ -0,0 +1,34 @@
+use std::path::{Path, PathBuf};
+
+use a_duk::{audit::audit_pet, pet::Pet};
+use mlua::Lua;
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name)
+}
+
+#[test]
+fn frames_and_states_are_counted() {
+    let lua = Lua::new();
+    let pet = Pet::load(&lua, &fixture("waking")).unwrap();
+    let audit = audit_pet(&pet).unwrap();
+
+    let frame_bytes: usize = pet.animations.values().flat_map(|anim| &anim.frames).map(String::len).sum();
+    assert_eq!((audit.frames, audit.frame_bytes, audit.states), (4, frame_bytes, 2));
+
+    let largest = audit.largest_animation.unwrap();
+    assert_eq!((largest.name.as_str(), largest.frames), ("yawn", 2));
+}
+
+#[test]
+fn required_modules_count_as_scripts() {
+    let lua = Lua::new();
+    let path = fixture("modular");
+    let pet = Pet::load(&lua, &path).unwrap();
+    let audit = audit_pet(&pet).unwrap();
+
+    let scripts: Vec<_> = audit.scripts.keys().map(|path| path.to_string_lossy().replace('\\', "/")).collect();
+    assert_eq!(scripts, ["lib/greet.lua", "state/idle/state.lua"]);
+    assert_eq!(audit.script_bytes(), std::fs::metadata(path.join("lib/greet.lua")).unwrap().len()
+        + std::fs::metadata(path.join("state/idle/state.lua")).unwrap().len());
+}
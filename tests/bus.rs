@@ -0,0 +1,46 @@
+use std::{cell::RefCell, path::Path, rc::Rc};
+
+use a_duk::{bus::MessageBus, pet::Pet, PetRuntime};
+use mlua::Lua;
+
+fn fixture(name: &str) -> std::path::PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name)
+}
+
+fn recording_lua(log: &Rc<RefCell<Vec<String>>>) -> Lua {
+    let lua = Lua::new();
+    let log = log.clone();
+    lua.globals().set("record", lua.create_function(move |_, msg: String| {
+        log.borrow_mut().push(msg);
+        Ok(())
+    }).unwrap()).unwrap();
+
+    lua
+}
+
+#[test]
+fn messages_are_delivered_between_pets() {
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let sender_lua = recording_lua(&log);
+    let receiver_lua = recording_lua(&log);
+
+    let bus = Rc::new(RefCell::new(MessageBus::default()));
+    let mut sender = PetRuntime::new(Pet::load(&sender_lua, &fixture("two_states")).unwrap()).unwrap();
+    let mut receiver = PetRuntime::new(Pet::load(&receiver_lua, &fixture("mouse")).unwrap()).unwrap();
+    sender.join_bus(&bus);
+    receiver.join_bus(&bus);
+
+    sender_lua.load(r#"send_message("mouse", "chase", { speed = 3 })"#).exec().unwrap();
+    assert!(sender_lua.load(r#"send_message("nobody", "chase", nil)"#).exec().is_err());
+
+    let messages = bus.borrow_mut().drain();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].target, receiver.id());
+
+    for message in messages {
+        receiver.receive(message).unwrap();
+    }
+
+    assert_eq!(log.borrow().last().unwrap(), "two_states chase 3");
+    assert!(bus.borrow_mut().drain().is_empty());
+}
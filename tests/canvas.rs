@@ -0,0 +1,77 @@
+use a_duk::canvas::Canvas;
+
+#[test]
+fn later_writes_win_except_for_transparent_cells() {
+    let mut canvas = Canvas::new((6, 1));
+
+    canvas.put_str(0, 0, "aaaa", None, Some(' '));
+    canvas.put_str(2, 0, "b b", None, Some(' '));
+    assert_eq!(canvas.row(0), "aabab ");
+}
+
+#[test]
+fn lines_are_clipped_to_the_canvas() {
+    let mut canvas = Canvas::new((4, 2));
+
+    canvas.put_str(2, 1, "xyz", None, None);
+    canvas.put_str(0, 5, "nope", None, None);
+    assert_eq!(canvas.row(0), "    ");
+    assert_eq!(canvas.row(1), "  xy");
+}
+
+#[test]
+fn wide_characters_are_never_split() {
+    let mut canvas = Canvas::new((5, 1));
+
+    canvas.put_str(0, 0, "鴨鴨", None, None);
+    assert_eq!(canvas.row(0), "鴨鴨 ");
+
+    // Overwriting the right half of the first one blanks its left half
+    canvas.put_str(1, 0, "x", None, None);
+    assert_eq!(canvas.row(0), " x鴨 ");
+
+    // One that doesn't fit isn't drawn
+    canvas.put_str(4, 0, "鴨", None, None);
+    assert_eq!(canvas.row(0), " x鴨 ");
+}
+
+#[test]
+fn clear_rect_blanks_whole_wide_characters() {
+    let mut canvas = Canvas::new((5, 2));
+    canvas.put_str(0, 0, "ab鴨", None, None);
+    canvas.put_str(0, 1, "cdef", None, None);
+
+    // Clipped to the canvas
+    canvas.clear_rect(1, 0, 1, 5);
+    assert_eq!(canvas.row(0), "a 鴨 ");
+    assert_eq!(canvas.row(1), "c ef ");
+
+    canvas.clear_rect(3, 0, 1, 1);
+    assert_eq!(canvas.row(0), "a    ");
+}
+
+#[test]
+fn write_to_repaints_every_row() {
+    let mut canvas = Canvas::new((3, 2));
+    canvas.put_str(0, 1, "hi", None, None);
+
+    let mut buf = Vec::new();
+    canvas.write_to(&mut buf).unwrap();
+    let written = String::from_utf8(buf).unwrap();
+
+    assert!(written.contains("\u{1b}[1;1H   "));
+    assert!(written.contains("\u{1b}[2;1Hhi "));
+}
+
+#[test]
+fn write_at_offsets_every_row() {
+    let mut canvas = Canvas::new((2, 2));
+    canvas.put_str(0, 0, "ab", None, None);
+
+    let mut buf = Vec::new();
+    canvas.write_at(&mut buf, (3, 1)).unwrap();
+    let written = String::from_utf8(buf).unwrap();
+
+    assert!(written.contains("\u{1b}[2;4Hab"));
+    assert!(written.contains("\u{1b}[3;4H  "));
+}
@@ -0,0 +1,35 @@
+use std::io::Write;
+
+use a_duk::cast::CastRecorder;
+use serde_json::Value;
+
+#[test]
+fn flushes_are_recorded_as_events() {
+    let mut screen = Vec::new();
+    let mut cast = Vec::new();
+
+    {
+        let mut recorder = CastRecorder::new(&mut screen, &mut cast, (80, 24)).unwrap();
+        write!(recorder, " o>\r\n").unwrap();
+        write!(recorder, "/|\\").unwrap();
+        recorder.flush().unwrap();
+        // Nothing new, no event
+        recorder.flush().unwrap();
+        write!(recorder, "\"quack\"").unwrap();
+        recorder.flush().unwrap();
+    }
+
+    assert_eq!(screen, b" o>\r\n/|\\\"quack\"");
+
+    let lines: Vec<Value> = String::from_utf8(cast).unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!((&lines[0]["version"], &lines[0]["width"], &lines[0]["height"]), (&Value::from(2), &Value::from(80), &Value::from(24)));
+
+    assert_eq!(lines[1][1], "o");
+    assert_eq!(lines[1][2], " o>\r\n/|\\");
+    assert_eq!(lines[2][2], "\"quack\"");
+    assert!(lines[1][0].as_f64().unwrap() <= lines[2][0].as_f64().unwrap());
+}
@@ -0,0 +1,42 @@
+use std::{path::Path, time::{Duration, Instant}};
+
+use a_duk::{clock::{Clock, MockClock, SystemClock}, pet::Pet, PetRuntime};
+use mlua::Lua;
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/two_states");
+
+#[test]
+fn mock_clocks_only_move_when_advanced() {
+    let start = Instant::now();
+    let mut clock = MockClock::starting_at(start);
+
+    assert_eq!(clock.now(), start);
+    clock.advance(Duration::from_millis(250));
+    clock.advance(Duration::from_millis(250));
+    assert_eq!(clock.now(), start + Duration::from_millis(500));
+}
+
+#[test]
+fn the_system_clock_ignores_advances() {
+    let mut clock = SystemClock;
+    let before = Instant::now();
+
+    clock.advance(Duration::from_secs(3600));
+    assert!(clock.now() < before + Duration::from_secs(60));
+}
+
+#[test]
+fn runtimes_follow_a_mock_clock() {
+    let lua = Lua::new();
+    lua.globals().set("record", lua.create_function(|_, _: String| Ok(())).unwrap()).unwrap();
+    let mut runtime = PetRuntime::new(Pet::load(&lua, Path::new(FIXTURE)).unwrap()).unwrap();
+    let mut clock = MockClock::new();
+
+    runtime.tick(clock.now()).unwrap();
+    clock.advance(Duration::from_millis(99));
+    assert!(!runtime.tick(clock.now()).unwrap());
+
+    clock.advance(Duration::from_millis(1));
+    assert!(runtime.tick(clock.now()).unwrap());
+    assert_eq!(runtime.current_frame(), 1);
+}
@@ -0,0 +1,32 @@
+use a_duk::color::{parse_hex, to_ansi16, to_ansi256, ColorDepth};
+use crossterm::style::Color;
+
+#[test]
+fn depth_is_detected_from_the_environment() {
+    assert_eq!(ColorDepth::from_env(Some("truecolor"), Some("xterm")), ColorDepth::TrueColor);
+    assert_eq!(ColorDepth::from_env(None, Some("xterm-256color")), ColorDepth::Ansi256);
+    assert_eq!(ColorDepth::from_env(None, Some("xterm")), ColorDepth::Ansi16);
+    assert_eq!(ColorDepth::from_env(None, Some("dumb")), ColorDepth::None);
+    assert_eq!(ColorDepth::from_env(None, None), ColorDepth::None);
+}
+
+#[test]
+fn hex_colors_are_parsed() {
+    assert_eq!(parse_hex("#ff8800"), Some((255, 136, 0)));
+    assert_eq!(parse_hex("ff8800"), None);
+    assert_eq!(parse_hex("#ff88"), None);
+    assert_eq!(parse_hex("#gg8800"), None);
+}
+
+#[test]
+fn colors_are_downgraded_to_the_nearest() {
+    assert_eq!(to_ansi256((255, 0, 0)), 196);
+    assert_eq!(to_ansi256((0, 0, 0)), 16);
+    assert_eq!(to_ansi256((128, 128, 128)), 244);
+
+    assert_eq!(to_ansi16((250, 10, 10)), Color::Red);
+    assert_eq!(to_ansi16((100, 100, 100)), Color::DarkGrey);
+
+    assert_eq!(ColorDepth::None.convert((1, 2, 3)), None);
+    assert_eq!(ColorDepth::TrueColor.convert((1, 2, 3)), Some(Color::Rgb { r: 1, g: 2, b: 3 }));
+}
@@ -0,0 +1,53 @@
+use std::{path::Path, time::Instant};
+
+use a_duk::{cursor::{CursorShape, PetCursor}, pet::Pet, PetRuntime};
+use mlua::Lua;
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/two_states");
+
+fn load(lua: &Lua) -> PetRuntime<'_> {
+    lua.globals().set("record", lua.create_function(|_, _: String| Ok(())).unwrap()).unwrap();
+    let mut runtime = PetRuntime::new(Pet::load(lua, Path::new(FIXTURE)).unwrap()).unwrap();
+    runtime.tick(Instant::now()).unwrap();
+    runtime
+}
+
+#[test]
+fn scripts_show_and_hide_the_cursor() {
+    let lua = Lua::new();
+    let runtime = load(&lua);
+    assert_eq!(runtime.cursor(), None);
+
+    lua.load("set_cursor(true, 'blinking_bar', 3, 1)").exec().unwrap();
+    assert_eq!(runtime.cursor(), Some(PetCursor { x: 3, y: 1, shape: CursorShape::BlinkingBar }));
+
+    // What isn't given stays as it was
+    lua.load("set_cursor(true, 'underline')").exec().unwrap();
+    assert_eq!(runtime.cursor(), Some(PetCursor { x: 3, y: 1, shape: CursorShape::SteadyUnderline }));
+
+    let (visible, style, x): (bool, String, u16) = lua.load("local v, s, x = get_cursor() return v, s, x").eval().unwrap();
+    assert_eq!((visible, style.as_str(), x), (true, "steady_underline", 3));
+
+    lua.load("set_cursor(false)").exec().unwrap();
+    assert_eq!(runtime.cursor(), None);
+}
+
+#[test]
+fn bad_styles_and_positions_are_errors() {
+    let lua = Lua::new();
+    let _runtime = load(&lua);
+
+    let error = lua.load("set_cursor(true, 'beam')").exec().unwrap_err();
+    assert!(error.to_string().contains("'beam' isn't a cursor style"), "{error}");
+    assert!(lua.load("set_cursor(true, 'bar', -1, 0)").exec().is_err());
+}
+
+#[test]
+fn changing_state_hides_the_cursor() {
+    let lua = Lua::new();
+    let mut runtime = load(&lua);
+
+    lua.load("set_cursor(true)").exec().unwrap();
+    runtime.set_state("second").unwrap();
+    assert_eq!(runtime.cursor(), None);
+}
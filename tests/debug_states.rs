@@ -0,0 +1,40 @@
+use std::{cell::RefCell, path::Path, rc::Rc};
+
+use a_duk::pet::{LoadOptions, Pet};
+use mlua::Lua;
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/experimental");
+
+fn recording_lua() -> (Lua, Rc<RefCell<Vec<String>>>) {
+    let lua = Lua::new();
+    let log = Rc::new(RefCell::new(Vec::new()));
+
+    let log_closure = log.clone();
+    lua.globals().set("record", lua.create_function(move |_, entry: String| {
+        log_closure.borrow_mut().push(entry);
+        Ok(())
+    }).unwrap()).unwrap();
+
+    (lua, log)
+}
+
+#[test]
+fn debug_only_states_are_skipped_by_default() {
+    let (lua, log) = recording_lua();
+    let pet = Pet::load(&lua, Path::new(FIXTURE)).unwrap();
+
+    assert!(!pet.states.contains_key("lab"));
+    // Never ran its script, and keys can't lead to it
+    assert!(log.borrow().is_empty());
+    assert!(pet.states["main"].metadata.input_transitions.is_empty());
+}
+
+#[test]
+fn debug_loads_every_state() {
+    let (lua, log) = recording_lua();
+    let pet = Pet::load_with_options(&lua, Path::new(FIXTURE), LoadOptions { debug: true, ..Default::default() }, |_, _| {}).unwrap();
+
+    assert!(pet.states.contains_key("lab"));
+    assert_eq!(*log.borrow(), ["lab"]);
+    assert_eq!(pet.states["main"].metadata.input_transitions["l"], "lab");
+}
@@ -0,0 +1,15 @@
+use a_duk::{embedded, pet::Pet, PetRuntime};
+use mlua::Lua;
+
+#[test]
+fn the_built_in_pet_loads() {
+    let dir = std::env::temp_dir().join(format!("a_duk-test-{}-embedded", std::process::id()));
+    let path = embedded::extract(&dir).unwrap();
+
+    let lua = Lua::new();
+    let pet = Pet::load(&lua, &path).unwrap();
+    assert_eq!(pet.metadata.name, "Duk");
+    PetRuntime::new(pet).unwrap();
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,22 @@
+use std::path::Path;
+
+use a_duk::{export::{animation_html, escape_html}, pet::Animation};
+
+const ANIMS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/anims");
+
+#[test]
+fn html_special_characters_are_escaped() {
+    assert_eq!(escape_html("<(\"')>&"), "&lt;(&quot;&#39;)&gt;&amp;");
+}
+
+#[test]
+fn every_frame_gets_its_share_of_the_cycle() {
+    let anim = Animation::load(&Path::new(ANIMS).join("bobbing")).unwrap();
+    let html = animation_html(&anim, "Bob <3");
+
+    assert!(html.contains("<title>Bob &lt;3</title>"));
+    assert!(html.contains("animation: frame 200ms step-end infinite"));
+    assert!(html.contains("50.0000% { visibility: hidden; }"));
+    assert!(html.contains("<pre style=\"animation-delay: -200ms\"> o\n/|\\\n/ \\\n</pre>"));
+    assert!(html.contains("<pre style=\"animation-delay: -100ms\">/o\\\n/ \\\n</pre>"));
+}
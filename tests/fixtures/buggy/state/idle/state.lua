@@ -0,0 +1,4 @@
+function Update()
+    record("update")
+    error("oops")
+end
@@ -0,0 +1,4 @@
+function Main()
+    wait(400)
+    set_current_state("sitting")
+end
@@ -0,0 +1,10 @@
+function Init()
+    set_current_anim("sit")
+end
+
+function Update()
+end
+
+function Key_down()
+    set_current_state("hopping")
+end
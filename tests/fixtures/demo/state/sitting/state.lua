@@ -0,0 +1,6 @@
+function Key_down(key)
+    _G.last_key = get_current_state() .. ":" .. key
+    if key == "x" then
+        set_current_state("walking")
+    end
+end
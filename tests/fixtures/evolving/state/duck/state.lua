@@ -0,0 +1,3 @@
+function Init()
+    _G.hatched = true
+end
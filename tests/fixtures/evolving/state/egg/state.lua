@@ -0,0 +1,3 @@
+function Update()
+    set_current_state("duck")
+end
@@ -0,0 +1 @@
+record("lab")
@@ -0,0 +1,2 @@
+function Update()
+end
@@ -0,0 +1,3 @@
+function Init()
+    _G.idle_from = require("lib.origin")
+end
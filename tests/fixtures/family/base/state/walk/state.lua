@@ -0,0 +1 @@
+error("the base walk state is overridden and never loaded")
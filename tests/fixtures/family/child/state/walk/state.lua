@@ -0,0 +1 @@
+walk_from = "child"
@@ -0,0 +1,8 @@
+local updates = 0
+
+function Update()
+    updates = updates + 1
+    if updates == 2 then
+        return "walking"
+    end
+end
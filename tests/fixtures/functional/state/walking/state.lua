@@ -0,0 +1,3 @@
+function Update()
+    return next_state
+end
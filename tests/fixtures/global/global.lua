@@ -0,0 +1,9 @@
+ticks = 0
+
+function greet(name)
+    return "hello " .. name
+end
+
+function Tick()
+    ticks = ticks + 1
+end
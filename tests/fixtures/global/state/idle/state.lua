@@ -0,0 +1,6 @@
+-- Helpers from global.lua are there when states load
+local greeting = greet("duk")
+
+function Init()
+    _G.greeting = greeting
+end
@@ -0,0 +1,3 @@
+function Anim_loop(name)
+    record(name)
+end
@@ -0,0 +1,8 @@
+local greet = require("lib.greet")
+
+function Init()
+    greet.hello("duk")
+end
+
+function Update()
+end
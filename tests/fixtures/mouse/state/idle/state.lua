@@ -0,0 +1,9 @@
+function Init()
+end
+
+function Update()
+end
+
+function Message_received(sender, event, payload)
+    record(sender .. " " .. event .. " " .. payload.speed)
+end
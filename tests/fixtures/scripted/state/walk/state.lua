@@ -0,0 +1,9 @@
+-- Init and Update are optional, this state only has Main
+
+function Main()
+    record("left")
+    wait(100)
+    record("quack")
+    wait(50)
+    record("right")
+end
@@ -0,0 +1,13 @@
+function Update()
+    set_current_anim("idle")
+end
+
+function Key_down(key)
+    if key == "q" then
+        set_current_state("quiet")
+    elseif key == "x" then
+        set_current_state "nowhere"
+    elseif key == "g" then
+        set_current_anim('ghost') -- set_current_state("commented")
+    end
+end
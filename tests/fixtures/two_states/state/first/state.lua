@@ -0,0 +1,7 @@
+function Init()
+    record("first init")
+end
+
+function Update()
+    record("first update")
+end
@@ -0,0 +1,7 @@
+function Init()
+    record("second init")
+end
+
+function Update()
+    record("second update")
+end
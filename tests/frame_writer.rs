@@ -0,0 +1,19 @@
+use a_duk::frame_writer::{write_lines, LineEnding};
+
+fn written(lines: &[&str], ending: LineEnding) -> String {
+    let mut out = Vec::new();
+    write_lines(&mut out, lines, ending).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn lines_are_joined_with_the_line_ending() {
+    assert_eq!(written(&[" o>", "/|\\", " |"], LineEnding::CrLf), " o>\r\n/|\\\r\n |");
+    assert_eq!(written(&[" o>", "/|\\", " |"], LineEnding::Lf), " o>\n/|\\\n |");
+}
+
+#[test]
+fn nothing_is_written_for_no_lines() {
+    assert_eq!(written(&[], LineEnding::CrLf), "");
+    assert_eq!(written(&["only"], LineEnding::CrLf), "only");
+}
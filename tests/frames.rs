@@ -0,0 +1,180 @@
+use a_duk::pet::expand_tabs;
+
+#[test]
+fn tabs_expand_to_tab_stops() {
+    assert_eq!(expand_tabs("\tx", 4), "    x");
+    assert_eq!(expand_tabs("ab\tx", 4), "ab  x");
+    assert_eq!(expand_tabs("abcd\tx", 4), "abcd    x");
+    assert_eq!(expand_tabs("a\tb\n\tc", 2), "a b\n  c");
+}
+
+#[test]
+fn display_width_counts_columns() {
+    use a_duk::text::{display_width, frame_width, pad_to_width};
+
+    assert_eq!(display_width("<(.)__"), 6);
+    assert_eq!(display_width("┌──┐"), 4);
+    assert_eq!(display_width("鴨"), 2);
+    assert_eq!(frame_width("ab\n鴨鴨鴨\n"), 6);
+    assert_eq!(pad_to_width("鴨", 4), "鴨  ");
+    assert_eq!(expand_tabs("鴨\tx", 4), "鴨  x");
+}
+
+#[test]
+fn lines_fit_to_a_width_by_columns() {
+    use a_duk::text::fit_to_width;
+
+    assert_eq!(fit_to_width("ab", 4), "ab  ");
+    assert_eq!(fit_to_width("abcdef", 4), "abcd");
+    // Half a wide character can't be drawn, so it's left out
+    assert_eq!(fit_to_width("a鴨鴨", 4), "a鴨 ");
+    assert_eq!(fit_to_width("e\u{301}", 2), "e\u{301} ");
+}
+
+mod next_frame {
+    use std::path::Path;
+
+    use a_duk::{pet::Animation, runtime::next_frame};
+
+    fn fixture_anim(pet: &str) -> Animation {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(pet)
+            .join("anim/idle");
+
+        Animation::load(&path).unwrap()
+    }
+
+    #[test]
+    fn wraps_multi_frame_animations() {
+        let anim = fixture_anim("two_states");
+        assert_eq!(anim.frames.len(), 2);
+
+        assert_eq!(next_frame(&0, &anim), 1);
+        assert_eq!(next_frame(&1, &anim), 0);
+    }
+
+    #[test]
+    fn single_frame_animations_stay_on_the_first_frame() {
+        let anim = fixture_anim("scripted");
+        assert_eq!(anim.frames.len(), 1);
+
+        assert_eq!(next_frame(&0, &anim), 0);
+    }
+}
+
+#[test]
+fn bottom_baseline_moves_shorter_frames_down() {
+    use a_duk::pet::Animation;
+    use std::path::Path;
+
+    let anim = Animation::load(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/anims/bobbing"))).unwrap();
+
+    assert_eq!(anim.height, 3);
+    assert_eq!(anim.width, 3);
+    assert_eq!(anim.baseline_offset(0), 0);
+    assert_eq!(anim.baseline_offset(1), 1);
+}
+
+#[test]
+fn mirrored_frames_swap_directional_glyphs() {
+    use a_duk::text::mirror_frame;
+    use std::collections::HashMap;
+
+    let pairs = HashMap::from([('/', '\\'), ('\\', '/'), ('(', ')'), (')', '('), ('<', '>'), ('>', '<')]);
+
+    assert_eq!(mirror_frame("  _\n<(.)__\n (___/", &pairs), "   _  \n__(.)>\n\\___) ");
+    assert_eq!(mirror_frame("鴨x", &pairs), "x鴨");
+}
+
+mod graphemes {
+    use std::{collections::HashMap, path::Path};
+
+    use a_duk::{canvas::Canvas, pet::{expand_tabs, Animation}, text::{display_width, fit_to_width, frame_width, mirror_frame}};
+
+    // A skin toned thumb, a family joined with ZWJs, an e with a combining
+    // accent and a flag, each a single grapheme of several chars
+    const THUMB: &str = "👍🏽";
+    const FAMILY: &str = "👨\u{200d}👩\u{200d}👧";
+    const ACCENTED: &str = "e\u{301}";
+
+    #[test]
+    fn emoji_frames_are_measured_by_grapheme() {
+        let anim = Animation::load(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/anims/emoji"))).unwrap();
+
+        assert_eq!(frame_width(&anim.frames[0]), 5);
+        assert_eq!(display_width(FAMILY), 2);
+        assert_eq!(frame_width(&anim.frames[1]), 3);
+        assert_eq!(anim.width, 5);
+    }
+
+    #[test]
+    fn clipping_never_splits_a_grapheme() {
+        assert_eq!(fit_to_width(&format!("{THUMB}{FAMILY}"), 3), format!("{THUMB} "));
+        assert_eq!(fit_to_width(&format!("{ACCENTED}x"), 1), ACCENTED);
+        assert_eq!(expand_tabs(&format!("{THUMB}\tx"), 4), format!("{THUMB}  x"));
+    }
+
+    #[test]
+    fn mirroring_keeps_graphemes_whole() {
+        let pairs = HashMap::from([('(', ')'), (')', '(')]);
+
+        assert_eq!(mirror_frame(&format!("{THUMB}({ACCENTED}"), &pairs), format!("{ACCENTED}){THUMB}"));
+        assert_eq!(mirror_frame(&format!("{FAMILY}\nab"), &pairs), format!("{FAMILY}\nba"));
+    }
+
+    #[test]
+    fn the_canvas_holds_whole_graphemes() {
+        let mut canvas = Canvas::new((6, 1));
+
+        canvas.put_str(0, 0, &format!("{FAMILY}{ACCENTED}{THUMB}"), None, None);
+        assert_eq!(canvas.row(0), format!("{FAMILY}{ACCENTED}{THUMB} "));
+
+        // Half of the family is overwritten, so all of it goes
+        canvas.put_str(1, 0, "x", None, None);
+        assert_eq!(canvas.row(0), format!(" x{ACCENTED}{THUMB} "));
+    }
+}
+
+#[test]
+fn editor_backups_and_hidden_files_arent_frames() {
+    use a_duk::pet::Animation;
+    use std::fs;
+
+    let path = std::env::temp_dir().join(format!("a_duk-test-{}-backups", std::process::id()));
+    let _ = fs::remove_dir_all(&path);
+    fs::create_dir_all(&path).unwrap();
+    for (file, contents) in [("0.txt", "a"), ("1.txt", "b"), ("0.txt~", "old"), (".#1.txt", "lock"),
+        (".2.txt", "hidden"), ("2.txt.swp", "swap"), ("3.txt.bak", "backup"), ("+4.txt", "signed")] {
+        fs::write(path.join(file), contents).unwrap();
+    }
+
+    let anim = Animation::load(&path).unwrap();
+    assert_eq!(anim.frames, ["a", "b"]);
+
+    fs::remove_dir_all(path).unwrap();
+}
+
+#[test]
+fn gaps_in_frame_numbers_fail_only_when_strict() {
+    use a_duk::pet::{Animation, Error, LoadOptions};
+    use std::fs;
+
+    let path = std::env::temp_dir().join(format!("a_duk-test-{}-gaps", std::process::id()));
+    let _ = fs::remove_dir_all(&path);
+    fs::create_dir_all(&path).unwrap();
+    for (file, contents) in [("1.txt", "b"), ("2.txt", "c"), ("5.txt", "f")] {
+        fs::write(path.join(file), contents).unwrap();
+    }
+
+    let anim = Animation::load(&path).unwrap();
+    assert_eq!(anim.frames, ["b", "c", "f"]);
+
+    let strict = LoadOptions { strict: true, ..Default::default() };
+    match Animation::load_with_options(&path, strict) {
+        Err(Error::FrameGap(_, missing)) => assert_eq!(missing, [0, 3, 4]),
+        other => panic!("expected a frame gap error, got {other:?}"),
+    }
+
+    fs::remove_dir_all(path).unwrap();
+}
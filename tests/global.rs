@@ -0,0 +1,28 @@
+use std::{path::Path, time::{Duration, Instant}};
+
+use a_duk::{pet::Pet, PetRuntime};
+use mlua::Lua;
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/global");
+
+#[test]
+fn global_helpers_are_shared_with_states() {
+    let lua = Lua::new();
+    let _runtime = PetRuntime::new(Pet::load(&lua, Path::new(FIXTURE)).unwrap()).unwrap();
+
+    assert_eq!(lua.globals().get::<_, String>("greeting").unwrap(), "hello duk");
+    assert!(lua.globals().get::<_, mlua::Value>("Tick").unwrap().is_nil());
+}
+
+#[test]
+fn tick_runs_every_global_tick_delay() {
+    let lua = Lua::new();
+    let mut runtime = PetRuntime::new(Pet::load(&lua, Path::new(FIXTURE)).unwrap()).unwrap();
+    let start = Instant::now();
+
+    for ms in [0, 20, 50, 60, 100] {
+        runtime.tick(start + Duration::from_millis(ms)).unwrap();
+    }
+
+    assert_eq!(lua.globals().get::<_, i64>("ticks").unwrap(), 3);
+}
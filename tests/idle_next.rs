@@ -0,0 +1,49 @@
+use std::{path::Path, time::{Duration, Instant}};
+
+use a_duk::{pet::Pet, PetRuntime};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use mlua::Lua;
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/restless");
+
+#[test]
+fn an_idle_pet_moves_on_to_a_weighted_state() {
+    let lua = Lua::new();
+    let mut runtime = PetRuntime::new(Pet::load(&lua, Path::new(FIXTURE)).unwrap()).unwrap();
+    let start = Instant::now();
+    runtime.tick(start).unwrap();
+
+    runtime.tick(start + Duration::from_millis(400)).unwrap();
+    assert_eq!(runtime.current_state(), "home");
+
+    runtime.tick(start + Duration::from_millis(500)).unwrap();
+    assert!(["nap", "stretch"].contains(&runtime.current_state().as_str()), "{}", runtime.current_state());
+}
+
+#[test]
+fn a_key_press_starts_the_wait_over() {
+    let lua = Lua::new();
+    let mut runtime = PetRuntime::new(Pet::load(&lua, Path::new(FIXTURE)).unwrap()).unwrap();
+    let start = Instant::now();
+    runtime.tick(start).unwrap();
+
+    runtime.tick(start + Duration::from_millis(400)).unwrap();
+    runtime.handle_key(KeyEvent::new(KeyCode::Char('a'), KeyModifiers::NONE)).unwrap();
+
+    runtime.tick(start + Duration::from_millis(800)).unwrap();
+    assert_eq!(runtime.current_state(), "home");
+
+    runtime.tick(start + Duration::from_millis(900)).unwrap();
+    assert_ne!(runtime.current_state(), "home");
+}
+
+#[test]
+fn the_states_have_to_exist() {
+    let lua = Lua::new();
+    let mut pet = Pet::load(&lua, Path::new(FIXTURE)).unwrap();
+
+    pet.states.get_mut("home").unwrap().metadata.idle_next.push(("wander".to_string(), 1.0));
+
+    let err = pet.validate().unwrap_err();
+    assert!(err.to_string().contains("idle_next"), "{err}");
+}
@@ -0,0 +1,48 @@
+use std::path::Path;
+
+use a_duk::index::{indexed_pets, PetIndex};
+
+const FAMILY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/family");
+
+fn descriptions(index: Option<&PetIndex>) -> Vec<(String, String)> {
+    indexed_pets(Path::new(FAMILY), index).into_iter()
+        .filter_map(|(dir_name, summary)| Some((dir_name, summary.ok()?.description)))
+        .collect()
+}
+
+#[test]
+fn the_index_survives_a_round_trip() {
+    let path = std::env::temp_dir().join(format!("a_duk-test-{}-index.toml", std::process::id()));
+    let index = PetIndex::build(Path::new(FAMILY));
+    assert_eq!(index.pets.len(), 4);
+
+    index.write(&path).unwrap();
+    assert_eq!(PetIndex::load(&path).unwrap(), Some(index));
+
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(PetIndex::load(&path).unwrap(), None);
+}
+
+#[test]
+fn up_to_date_entries_are_used_instead_of_the_metadata() {
+    let mut index = PetIndex::build(Path::new(FAMILY));
+    index.pets.get_mut("child").unwrap().summary.description = "From the index".to_string();
+
+    let listed = descriptions(Some(&index));
+    assert!(listed.contains(&("child".to_string(), "From the index".to_string())));
+    assert_eq!(listed.len(), descriptions(None).len());
+}
+
+#[test]
+fn stale_entries_and_other_directories_are_read_again() {
+    let mut index = PetIndex::build(Path::new(FAMILY));
+    let child = index.pets.get_mut("child").unwrap();
+    child.summary.description = "From the index".to_string();
+    child.modified -= 1;
+    assert_eq!(descriptions(Some(&index)), descriptions(None));
+
+    let mut elsewhere = PetIndex::build(Path::new(FAMILY));
+    elsewhere.pets.get_mut("child").unwrap().summary.description = "From the index".to_string();
+    elsewhere.pets_dir = Path::new(FAMILY).join("nowhere");
+    assert_eq!(descriptions(Some(&elsewhere)), descriptions(None));
+}
@@ -0,0 +1,30 @@
+use std::path::Path;
+
+use a_duk::{pet::{Error, Pet}, PetRuntime};
+use mlua::Lua;
+
+const FAMILY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/family");
+
+#[test]
+fn children_inherit_and_override_the_base() {
+    let lua = Lua::new();
+    let pet = Pet::load(&lua, &Path::new(FAMILY).join("child")).unwrap();
+
+    assert_eq!(pet.metadata.name, "Child");
+    assert_eq!(pet.animations["idle"].frames[0], " O\n");
+    assert_eq!(pet.animations["walk"].frames[0], " >\n");
+    assert_eq!(pet.states.len(), 2);
+
+    // The idle state comes from the base, along with the module it requires
+    PetRuntime::new(pet).unwrap();
+    assert_eq!(lua.globals().get::<_, String>("idle_from").unwrap(), "base");
+}
+
+#[test]
+fn inheritance_cycles_are_rejected() {
+    let lua = Lua::new();
+
+    let result = Pet::load(&lua, &Path::new(FAMILY).join("loop_a"));
+
+    assert!(matches!(result, Err(Error::InvalidObject(msg)) if msg.contains("cycle")));
+}
@@ -0,0 +1,52 @@
+use std::{path::Path, time::Instant};
+
+use a_duk::{pet::Pet, PetRuntime};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use mlua::Lua;
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/demo");
+
+fn press(runtime: &mut PetRuntime, c: char) {
+    runtime.handle_key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE)).unwrap();
+    runtime.tick(Instant::now()).unwrap();
+}
+
+#[test]
+fn keys_switch_states_without_scripts() {
+    let lua = Lua::new();
+    let mut runtime = PetRuntime::new(Pet::load(&lua, Path::new(FIXTURE)).unwrap()).unwrap();
+
+    press(&mut runtime, 'q');
+    assert_eq!(runtime.current_state(), "walking");
+
+    press(&mut runtime, 's');
+    assert_eq!(runtime.current_state(), "sitting");
+
+    press(&mut runtime, 'w');
+    assert_eq!(runtime.current_state(), "walking");
+}
+
+#[test]
+fn key_down_overrides_the_table() {
+    let lua = Lua::new();
+    let mut runtime = PetRuntime::new(Pet::load(&lua, Path::new(FIXTURE)).unwrap()).unwrap();
+    runtime.set_state("sitting").unwrap();
+
+    press(&mut runtime, 'x');
+    assert_eq!(runtime.current_state(), "walking");
+}
+
+#[test]
+fn key_down_of_the_state_left_gets_the_key() {
+    let lua = Lua::new();
+    let mut runtime = PetRuntime::new(Pet::load(&lua, Path::new(FIXTURE)).unwrap()).unwrap();
+    runtime.set_state("sitting").unwrap();
+
+    runtime.handle_key(KeyEvent::new(KeyCode::Char('w'), KeyModifiers::NONE)).unwrap();
+    // Sitting's handler ran, already seeing where the key leads
+    assert_eq!(lua.globals().get::<_, String>("last_key").unwrap(), "walking:w");
+    assert_eq!(runtime.current_state(), "sitting");
+
+    runtime.tick(Instant::now()).unwrap();
+    assert_eq!(runtime.current_state(), "walking");
+}
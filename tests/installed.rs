@@ -0,0 +1,27 @@
+use std::path::Path;
+
+use a_duk::pet::installed_pets;
+
+const FAMILY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/family");
+
+#[test]
+fn installed_pets_are_sorted_with_their_metadata() {
+    let pets = installed_pets(Path::new(FAMILY));
+
+    let names: Vec<_> = pets.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(names, ["base", "child", "loop_a", "loop_b"]);
+    assert_eq!(pets[1].1.as_ref().unwrap().name, "Child");
+}
+
+#[test]
+fn pets_without_metadata_are_listed_as_invalid() {
+    let pets = installed_pets(Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures")));
+
+    let (_, anims) = pets.iter().find(|(name, _)| name == "anims").unwrap();
+    assert!(anims.is_err());
+}
+
+#[test]
+fn a_missing_directory_has_no_pets() {
+    assert!(installed_pets(&Path::new(FAMILY).join("nowhere")).is_empty());
+}
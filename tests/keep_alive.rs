@@ -0,0 +1,42 @@
+use std::{cell::Cell, path::Path, rc::Rc, time::{Duration, Instant}};
+
+use a_duk::{pet::Pet, PetRuntime};
+use mlua::Lua;
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/buggy");
+
+fn load<'lua>(lua: &'lua Lua, calls: &Rc<Cell<usize>>) -> PetRuntime<'lua> {
+    let calls = calls.clone();
+    lua.globals().set("record", lua.create_function(move |_, _: String| {
+        calls.set(calls.get() + 1);
+        Ok(())
+    }).unwrap()).unwrap();
+
+    PetRuntime::new(Pet::load(lua, Path::new(FIXTURE)).unwrap()).unwrap()
+}
+
+#[test]
+fn handler_errors_fail_the_tick_by_default() {
+    let lua = Lua::new();
+    let mut runtime = load(&lua, &Rc::new(Cell::new(0)));
+
+    assert!(runtime.tick(Instant::now()).is_err());
+}
+
+#[test]
+fn keep_alive_disables_the_failing_handler() {
+    let lua = Lua::new();
+    let calls = Rc::new(Cell::new(0));
+    let mut runtime = load(&lua, &calls);
+    runtime.set_keep_alive(true);
+    let start = Instant::now();
+
+    runtime.tick(start).unwrap();
+    runtime.tick(start + Duration::from_millis(100)).unwrap();
+    runtime.tick(start + Duration::from_millis(200)).unwrap();
+    assert_eq!(calls.get(), 1);
+
+    runtime.enable_handlers();
+    runtime.tick(start + Duration::from_millis(300)).unwrap();
+    assert_eq!(calls.get(), 2);
+}
@@ -0,0 +1,35 @@
+use std::path::Path;
+
+use a_duk::{lint::lint_pet, pet::Pet};
+use mlua::Lua;
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+fn warnings(pet: &str) -> Vec<String> {
+    let lua = Lua::new();
+    let pet = Pet::load(&lua, &Path::new(FIXTURES).join(pet)).unwrap();
+
+    lint_pet(&pet).iter()
+        .map(|warning| {
+            let file = warning.file.strip_prefix(&pet.path).unwrap().display();
+            match warning.line {
+                Some(line) => format!("{file}:{line}: {}", warning.message),
+                None => format!("{file}: {}", warning.message),
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn unknown_literal_names_are_reported_with_their_line() {
+    assert_eq!(warnings("sloppy"), [
+        "state/main/state.lua:9: set_current_state: unknown state 'nowhere'",
+        "state/main/state.lua:11: set_current_anim: unknown animation 'ghost'",
+        "state/quiet/meta.toml: update_delay is set but the state has no Update function",
+    ]);
+}
+
+#[test]
+fn known_names_arent_reported() {
+    assert!(warnings("demo").iter().all(|warning| !warning.contains("unknown")));
+}
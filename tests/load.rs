@@ -0,0 +1,121 @@
+use std::{fs, path::{Path, PathBuf}};
+
+use a_duk::pet::{Error, Pet};
+use mlua::Lua;
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/complete");
+
+#[test]
+fn the_whole_pet_is_loaded() {
+    let lua = Lua::new();
+    let pet = Pet::load(&lua, Path::new(FIXTURE)).unwrap();
+
+    assert_eq!(pet.metadata.name, "Complete");
+    assert_eq!(pet.metadata.description, "Two animations and two states, each with its own script.");
+    assert_eq!(pet.metadata.default_state, "sitting");
+    assert_eq!(pet.metadata.global_tick_delay, 100);
+
+    let mut animations: Vec<_> = pet.animations.keys().map(String::as_str).collect();
+    animations.sort();
+    assert_eq!(animations, ["hop", "sit"]);
+    assert_eq!(pet.animations["sit"].frames, [" _\n(o)\n", " _\n(-)\n"]);
+    assert_eq!(pet.animations["sit"].metadata.delay, 200);
+    assert_eq!(pet.animations["hop"].frames.len(), 1);
+
+    let mut states: Vec<_> = pet.states.keys().map(String::as_str).collect();
+    states.sort();
+    assert_eq!(states, ["hopping", "sitting"]);
+    assert_eq!(pet.states["sitting"].metadata.animation, "sit");
+    assert_eq!(pet.states["sitting"].metadata.update_delay, 500);
+}
+
+#[test]
+fn each_state_has_the_handlers_its_script_defines() {
+    let lua = Lua::new();
+    let pet = Pet::load(&lua, Path::new(FIXTURE)).unwrap();
+
+    let sitting = &pet.states["sitting"].event_handlers;
+    assert!(sitting.init.is_some() && sitting.update.is_some() && sitting.key_down.is_some());
+    assert!(sitting.main.is_none() && sitting.key_up.is_none());
+
+    let hopping = &pet.states["hopping"].event_handlers;
+    assert!(hopping.main.is_some());
+    assert!(hopping.init.is_none() && hopping.update.is_none() && hopping.key_down.is_none());
+}
+
+// A copy of the fixture to break, removed again when dropped
+struct Copy(PathBuf);
+
+impl Copy {
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("a_duk-test-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        copy_dir(Path::new(FIXTURE), &path);
+        Self(path)
+    }
+}
+
+impl Drop for Copy {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn copy_dir(from: &Path, to: &Path) {
+    fs::create_dir_all(to).unwrap();
+    for entry in fs::read_dir(from).unwrap() {
+        let entry = entry.unwrap();
+        if entry.file_type().unwrap().is_dir() {
+            copy_dir(&entry.path(), &to.join(entry.file_name()));
+        } else {
+            fs::copy(entry.path(), to.join(entry.file_name())).unwrap();
+        }
+    }
+}
+
+#[test]
+fn the_default_state_has_to_exist() {
+    let pet = Copy::new("no-default-state");
+    let meta = fs::read_to_string(pet.0.join("meta.toml")).unwrap();
+    fs::write(pet.0.join("meta.toml"), meta.replace("\"sitting\"", "\"lying\"")).unwrap();
+
+    let lua = Lua::new();
+    let error = Pet::load(&lua, &pet.0).unwrap_err();
+    assert!(matches!(error, Error::InvalidObject("The default state doesn't exist")), "{error}");
+}
+
+#[test]
+fn a_states_animation_has_to_exist() {
+    let pet = Copy::new("no-animation");
+    fs::remove_dir_all(pet.0.join("anim/hop")).unwrap();
+
+    let lua = Lua::new();
+    let error = Pet::load(&lua, &pet.0).unwrap_err();
+    assert!(matches!(error, Error::InvalidObject("A state's animation doesn't exist")), "{error}");
+}
+
+#[test]
+fn empty_animations_fail_to_load() {
+    let pet = Copy::new("empty-animation");
+    fs::remove_file(pet.0.join("anim/hop/0.txt")).unwrap();
+
+    let lua = Lua::new();
+    let error = Pet::load(&lua, &pet.0).unwrap_err();
+    assert!(matches!(&error, Error::NoFrames(name, _) if name == "hop"), "{error}");
+}
+
+#[test]
+fn pets_over_the_frame_byte_limit_are_refused() {
+    use a_duk::pet::LoadOptions;
+
+    // The frames of the fixture are 21 bytes together, sit's are 14
+    let lua = Lua::new();
+    let options = |max_frame_bytes| LoadOptions { max_frame_bytes, ..LoadOptions::default() };
+    assert!(Pet::load_with_options(&lua, Path::new(FIXTURE), options(21), |_, _| {}).is_ok());
+
+    let error = Pet::load_with_options(&lua, Path::new(FIXTURE), options(20), |_, _| {}).unwrap_err();
+    assert!(matches!(error, Error::TooManyFrameBytes(21, 20)), "{error}");
+
+    let error = a_duk::pet::Animation::load_with_options(&Path::new(FIXTURE).join("anim/sit"), options(10)).unwrap_err();
+    assert!(matches!(error, Error::TooManyFrameBytes(14, 10)), "{error}");
+}
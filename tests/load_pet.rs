@@ -0,0 +1,77 @@
+use std::path::Path;
+
+use a_duk::{pet::{LoadOptions, Pet}, PetRuntime};
+use mlua::Lua;
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+fn load(lua: &Lua) -> PetRuntime<'_> {
+    let mut runtime = PetRuntime::new(Pet::load(lua, &Path::new(FIXTURES).join("modular")).unwrap()).unwrap();
+    runtime.set_pets_dir(Some(FIXTURES.into()));
+    runtime
+}
+
+#[test]
+fn load_pet_hands_the_new_pet_to_the_driver() {
+    let lua = Lua::new();
+    let mut runtime = load(&lua);
+
+    lua.load("load_pet('experimental')").exec().unwrap();
+
+    let pet = runtime.take_loaded_pet().unwrap();
+    assert!(pet.pet().path.ends_with("experimental"));
+    assert!(runtime.take_loaded_pet().is_none());
+}
+
+#[test]
+fn failing_to_load_keeps_the_current_pet() {
+    let lua = Lua::new();
+    let mut runtime = load(&lua);
+
+    let err = lua.load("load_pet('nonexistent')").exec().unwrap_err();
+    assert!(err.to_string().contains("doesn't exist"), "{err}");
+    // Not a pet at all
+    assert!(lua.load("load_pet('anims')").exec().is_err());
+    assert!(lua.load("load_pet('../fixtures')").exec().is_err());
+
+    assert!(runtime.take_loaded_pet().is_none());
+}
+
+#[test]
+fn load_pet_needs_a_pets_directory() {
+    let lua = Lua::new();
+    let mut runtime = PetRuntime::new(Pet::load(&lua, &Path::new(FIXTURES).join("modular")).unwrap()).unwrap();
+
+    assert!(lua.load("load_pet('experimental')").exec().is_err());
+    assert!(runtime.take_loaded_pet().is_none());
+}
+
+#[test]
+fn load_pet_uses_the_runs_load_options() {
+    let lua = Lua::new();
+    let mut runtime = load(&lua);
+    runtime.set_load_options(LoadOptions { max_frame_bytes: 1, ..LoadOptions::default() });
+
+    let err = lua.load("load_pet('experimental')").exec().unwrap_err();
+    assert!(err.to_string().contains("loading 'experimental' failed"), "{err}");
+    assert!(runtime.take_loaded_pet().is_none());
+}
+
+#[cfg(unix)]
+#[test]
+fn load_pet_stays_in_the_pets_directory() {
+    let pets_dir = std::env::temp_dir().join(format!("a_duk-test-{}-load-pet-symlink", std::process::id()));
+    let _ = std::fs::remove_dir_all(&pets_dir);
+    std::fs::create_dir_all(&pets_dir).unwrap();
+    std::os::unix::fs::symlink(Path::new(FIXTURES).join("experimental"), pets_dir.join("elsewhere")).unwrap();
+
+    let lua = Lua::new();
+    let mut runtime = load(&lua);
+    runtime.set_pets_dir(Some(pets_dir.clone()));
+
+    let err = lua.load("load_pet('elsewhere')").exec().unwrap_err();
+    assert!(err.to_string().contains("outside of the pets directory"), "{err}");
+    assert!(runtime.take_loaded_pet().is_none());
+
+    std::fs::remove_dir_all(pets_dir).unwrap();
+}
@@ -0,0 +1,31 @@
+use std::path::Path;
+
+use a_duk::pet::Pet;
+use mlua::Lua;
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+#[test]
+fn progress_counts_every_frame() {
+    let lua = Lua::new();
+    let mut calls = Vec::new();
+
+    let pet = Pet::load_with_progress(&lua, &Path::new(FIXTURES).join("two_states"),
+        |done, total| calls.push((done, total))).unwrap();
+
+    let frames: usize = pet.animations.values().map(|a| a.frames.len()).sum();
+    assert_eq!(calls.first(), Some(&(0, frames)));
+    assert_eq!(calls.last(), Some(&(frames, frames)));
+    assert!(calls.windows(2).all(|w| w[0].0 <= w[1].0));
+}
+
+#[test]
+fn loading_is_timed() {
+    let lua = Lua::new();
+
+    let pet = Pet::load(&lua, &Path::new(FIXTURES).join("scripted")).unwrap();
+    let timings = pet.load_timings;
+
+    assert_eq!(timings.total(), timings.animations + timings.states + timings.lua);
+    assert!(timings.lua > std::time::Duration::ZERO);
+}
@@ -0,0 +1,76 @@
+use std::path::Path;
+
+use a_duk::pet::{Animation, Error, Pet, State, DEFAULT_DELAY};
+use mlua::Lua;
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/json");
+
+#[test]
+fn metadata_can_be_json() {
+    let lua = Lua::new();
+    let pet = Pet::load(&lua, Path::new(FIXTURE)).unwrap();
+
+    assert_eq!(pet.metadata.name, "Json");
+    assert_eq!(pet.animations["idle"].metadata.delay, 100);
+}
+
+#[test]
+fn toml_is_preferred_over_json() {
+    let lua = Lua::new();
+    let pet = Pet::load(&lua, Path::new(FIXTURE)).unwrap();
+
+    // The idle state has both, they only differ in update_delay
+    assert_eq!(pet.states["idle"].metadata.update_delay, 250);
+}
+
+const MISSING: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/missing_meta");
+
+#[test]
+fn animations_without_metadata_use_the_default_delay() {
+    let animation = Animation::load(&Path::new(MISSING).join("anim/walk")).unwrap();
+
+    assert_eq!(animation.metadata.delay, DEFAULT_DELAY);
+    assert_eq!(animation.metadata.transparent, ' ');
+}
+
+#[test]
+fn states_without_metadata_are_named_in_the_error() {
+    let lua = Lua::new();
+
+    let error = State::load(&lua, &Path::new(MISSING).join("state/lost")).unwrap_err();
+
+    assert!(matches!(&error, Error::MissingMetadata("state", name) if name == "lost"));
+    assert!(error.to_string().contains("state 'lost' is missing meta.toml"));
+}
+
+#[test]
+fn animations_without_frames_list_what_they_have() {
+    let error = Animation::load(&Path::new(MISSING).join("anim/misnamed")).unwrap_err();
+
+    assert!(matches!(&error, Error::NoFrames(name, files)
+        if name == "misnamed" && files == &["01a.txt", "frame1.txt", "notes.md"]));
+    assert!(error.to_string().contains("animation 'misnamed' contains no frames"));
+    assert!(error.to_string().ends_with("but it has 01a.txt, frame1.txt, notes.md"));
+}
+
+#[test]
+fn fps_is_turned_into_a_delay() {
+    use a_duk::pet::AnimationMetadata;
+    use std::fs;
+
+    let dir = std::env::temp_dir().join(format!("a_duk-test-{}-fps", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("meta.toml");
+    let load = |contents: &str| {
+        fs::write(&path, contents).unwrap();
+        AnimationMetadata::load(&path)
+    };
+
+    assert_eq!(load("fps = 12").unwrap().delay, 83);
+    assert_eq!(load("fps = 2.5").unwrap().delay, 400);
+    assert_eq!(load("delay = 50").unwrap().delay, 50);
+    assert!(matches!(load("delay = 50\nfps = 20"), Err(Error::InvalidObject(message)) if message.contains("both")));
+    assert!(load("fps = 0").is_err());
+
+    fs::remove_dir_all(dir).unwrap();
+}
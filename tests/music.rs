@@ -0,0 +1,48 @@
+use a_duk::sound::{Music, Sounds};
+
+#[test]
+fn music_switches_tracks_and_remembers_failures() {
+    let dir = std::env::temp_dir().join(format!("a_duk-test-{}-music", std::process::id()));
+    std::fs::create_dir_all(dir.join("sound")).unwrap();
+    std::fs::write(dir.join("sound/song.wav"), "").unwrap();
+
+    let sounds = Sounds::new(&dir);
+    let mut music = Music::default();
+
+    music.switch(&sounds, Some("song")).unwrap();
+    assert_eq!(music.playing(), Some("song"));
+
+    assert!(music.switch(&sounds, Some("missing")).is_err());
+    assert_eq!(music.playing(), None);
+    // Not retried
+    music.switch(&sounds, Some("missing")).unwrap();
+
+    music.switch(&sounds, Some("song")).unwrap();
+    music.switch(&sounds, None).unwrap();
+    assert_eq!(music.playing(), None);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn sounds_are_looked_up_when_the_pet_loads() {
+    let dir = std::env::temp_dir().join(format!("a_duk-test-{}-sounds", std::process::id()));
+    std::fs::create_dir_all(dir.join("sound")).unwrap();
+    std::fs::write(dir.join("sound/quack.wav"), "").unwrap();
+
+    let sounds = Sounds::new(&dir);
+    std::fs::write(dir.join("sound/honk.wav"), "").unwrap();
+
+    assert!(sounds.resolve("quack").unwrap().ends_with("sound/quack.wav"));
+    assert!(sounds.resolve("honk").is_err());
+    assert!(sounds.resolve("../quack").is_err());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn pets_without_sounds_load() {
+    let sounds = Sounds::new(std::path::Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/two_states")));
+    assert!(sounds.resolve("quack").is_err());
+    sounds.reap();
+}
@@ -0,0 +1,50 @@
+use std::{path::Path, rc::Rc, time::{Duration, Instant}};
+
+use a_duk::{pet::Pet, PetRuntime};
+use mlua::Lua;
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/waking");
+
+#[test]
+fn finishing_the_animation_goes_to_its_state() {
+    let lua = Lua::new();
+    let mut runtime = PetRuntime::new(Pet::load(&lua, Path::new(FIXTURE)).unwrap()).unwrap();
+    let start = Instant::now();
+    runtime.tick(start).unwrap();
+
+    lua.load("set_current_anim('yawn')").exec().unwrap();
+    runtime.tick(start + Duration::from_millis(100)).unwrap();
+    assert_eq!((runtime.current_state().as_str(), runtime.current_frame()), ("asleep", 1));
+
+    runtime.tick(start + Duration::from_millis(200)).unwrap();
+    assert_eq!(runtime.current_state(), "awake");
+}
+
+#[test]
+fn a_states_own_animation_keeps_looping() {
+    let lua = Lua::new();
+    let mut pet = Pet::load(&lua, Path::new(FIXTURE)).unwrap();
+    Rc::get_mut(&mut pet.animations).unwrap()
+        .get_mut("snooze").unwrap()
+        .metadata.on_complete_state = Some("awake".to_string());
+
+    let mut runtime = PetRuntime::new(pet).unwrap();
+    let start = Instant::now();
+    runtime.tick(start).unwrap();
+    runtime.tick(start + Duration::from_millis(100)).unwrap();
+    runtime.tick(start + Duration::from_millis(200)).unwrap();
+    assert_eq!(runtime.current_state(), "asleep");
+}
+
+#[test]
+fn the_state_has_to_exist() {
+    let lua = Lua::new();
+    let mut pet = Pet::load(&lua, Path::new(FIXTURE)).unwrap();
+
+    Rc::get_mut(&mut pet.animations).unwrap()
+        .get_mut("yawn").unwrap()
+        .metadata.on_complete_state = Some("dreaming".to_string());
+
+    let err = pet.validate().unwrap_err();
+    assert!(err.to_string().contains("on_complete_state"), "{err}");
+}
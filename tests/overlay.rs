@@ -0,0 +1,84 @@
+use std::{path::Path, time::{Duration, Instant}};
+
+use a_duk::{canvas::Canvas, overlay::Overlay, pet::Pet, PetRuntime};
+use mlua::Lua;
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/two_states");
+
+fn rows(overlay: &Overlay, size: (u16, u16)) -> Vec<String> {
+    (0..size.1)
+        .map(|y| (0..size.0).map(|x| overlay.get(x, y).unwrap_or('.')).collect())
+        .collect()
+}
+
+#[test]
+fn lines_follow_bresenham() {
+    let mut overlay = Overlay::default();
+
+    overlay.line((0, 0), (4, 2), '*');
+    assert_eq!(rows(&overlay, (5, 3)), ["*....", ".**..", "...**"]);
+
+    let mut steep = Overlay::default();
+    steep.line((1, 2), (0, 0), '*');
+    assert_eq!(rows(&steep, (2, 3)), ["*.", "*.", ".*"]);
+}
+
+#[test]
+fn rects_are_outlines_clipped_to_the_bounds() {
+    let mut overlay = Overlay::default();
+    overlay.set_bounds(Some((4, 3)));
+
+    overlay.rect(-1, 0, 4, 3, '#');
+    assert_eq!(rows(&overlay, (4, 3)), ["###.", "..#.", "###."]);
+
+    overlay.hline(0, 9, 3, '-');
+    overlay.vline(0, 0, 0, '|');
+    assert_eq!(overlay.cells().count(), 7);
+}
+
+#[test]
+fn scripts_draw_over_the_frame() {
+    let lua = Lua::new();
+    lua.globals().set("record", lua.create_function(|_, _: String| Ok(())).unwrap()).unwrap();
+    let mut runtime = PetRuntime::new(Pet::load(&lua, Path::new(FIXTURE)).unwrap()).unwrap();
+    let start = Instant::now();
+    runtime.tick(start).unwrap();
+
+    lua.load("draw_hline(0, 0, 3, '=')").exec().unwrap();
+    assert!(runtime.tick(start + Duration::from_millis(10)).unwrap());
+
+    let mut canvas = Canvas::new((4, 2));
+    runtime.render_into(&mut canvas, (0, 0));
+    assert_eq!(canvas.row(0), "=== ");
+
+    lua.load("clear_overlay()").exec().unwrap();
+    canvas.clear();
+    runtime.render_into(&mut canvas, (0, 0));
+    assert_eq!(canvas.row(0), " o  ");
+
+    let error = lua.load("draw_rect(0, 0, 2, 2, '')").exec().unwrap_err();
+    assert!(error.to_string().contains("draw_rect: the character can't be empty"));
+}
+
+#[test]
+fn render_keys_change_only_with_what_is_drawn() {
+    let lua = Lua::new();
+    lua.globals().set("record", lua.create_function(|_, _: String| Ok(())).unwrap()).unwrap();
+    let mut runtime = PetRuntime::new(Pet::load(&lua, Path::new(FIXTURE)).unwrap()).unwrap();
+    runtime.tick(Instant::now()).unwrap();
+
+    let key = runtime.render_key((0, 0));
+    assert_eq!(runtime.render_key((0, 0)), key);
+    assert_ne!(runtime.render_key((1, 0)), key);
+
+    lua.load("draw_hline(0, 0, 2, '=')").exec().unwrap();
+    let drawn = runtime.render_key((0, 0));
+    assert_ne!(drawn, key);
+
+    // Drawing the same thing again doesn't count as a change
+    lua.load("draw_hline(0, 0, 2, '=')").exec().unwrap();
+    assert_eq!(runtime.render_key((0, 0)), drawn);
+
+    lua.load("set_flip(true)").exec().unwrap();
+    assert_ne!(runtime.render_key((0, 0)), drawn);
+}
@@ -0,0 +1,14 @@
+use a_duk::pet::is_valid_pet_name;
+
+#[test]
+fn plain_names_are_valid() {
+    assert!(is_valid_pet_name("duk"));
+    assert!(is_valid_pet_name("my pet.v2"));
+}
+
+#[test]
+fn names_that_are_paths_are_rejected() {
+    for name in ["", ".", "..", "../../etc", "/etc", "a/b", "a\\b", "..\\etc"] {
+        assert!(!is_valid_pet_name(name), "{name:?} should be invalid");
+    }
+}
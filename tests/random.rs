@@ -0,0 +1,57 @@
+use std::path::Path;
+
+use a_duk::{pet::Pet, PetRuntime};
+use mlua::{Lua, Value};
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/demo");
+
+fn load(lua: &Lua) -> PetRuntime<'_> {
+    PetRuntime::new(Pet::load(lua, Path::new(FIXTURE)).unwrap()).unwrap()
+}
+
+#[test]
+fn random_choice_picks_from_the_list() {
+    let lua = Lua::new();
+    let _runtime = load(&lua);
+
+    assert_eq!(lua.load("return random_choice({})").eval::<Value>().unwrap(), Value::Nil);
+    assert_eq!(lua.load("return random_choice({'only'})").eval::<String>().unwrap(), "only");
+
+    for _ in 0..20 {
+        let picked: String = lua.load("return random_choice({'never', 'always'}, {0, 1})").eval().unwrap();
+        assert_eq!(picked, "always");
+    }
+
+    let error = lua.load("random_choice({'a', 'b'}, {1})").exec().unwrap_err();
+    assert!(error.to_string().contains("as many weights as elements"));
+}
+
+#[test]
+fn random_normal_is_centered_on_the_mean() {
+    let lua = Lua::new();
+    let _runtime = load(&lua);
+
+    assert_eq!(lua.load("return random_normal(5, 0)").eval::<f64>().unwrap(), 5.0);
+
+    let mean: f64 = lua.load("
+        local sum = 0
+        for _ = 1, 1000 do sum = sum + random_normal(10, 2) end
+        return sum / 1000
+    ").eval().unwrap();
+    assert!((mean - 10.0).abs() < 0.5);
+
+    assert!(lua.load("random_normal(0, -1)").exec().unwrap_err().to_string().contains("random_normal"));
+}
+
+#[test]
+fn seeds_make_the_distributions_repeatable() {
+    let draw = || {
+        let lua = Lua::new();
+        let mut runtime = load(&lua);
+        runtime.set_seed(7).unwrap();
+
+        lua.load("return random_normal() .. random_choice({1, 2, 3, 4, 5})").eval::<String>().unwrap()
+    };
+
+    assert_eq!(draw(), draw());
+}
@@ -0,0 +1,27 @@
+use std::path::Path;
+
+use a_duk::pet::Pet;
+use mlua::Lua;
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/modular");
+
+#[test]
+fn states_require_modules_from_the_pet_directory() {
+    let lua = Lua::new();
+    // The state script requires lib.greet at load time
+    Pet::load(&lua, Path::new(FIXTURE)).unwrap();
+
+    let hello: String = lua.load(r#"return require("lib.greet").hello("duk")"#).eval().unwrap();
+    assert_eq!(hello, "hello duk");
+}
+
+#[test]
+fn require_rejects_paths_outside_the_pet() {
+    let lua = Lua::new();
+    let _pet = Pet::load(&lua, Path::new(FIXTURE)).unwrap();
+
+    for name in ["..lib.greet", "/etc/passwd", "../modular/lib/greet", "lib\\\\greet", "lib..greet", "missing"] {
+        let result = lua.load(format!("return require({name:?})")).exec();
+        assert!(result.is_err(), "require({name:?}) should fail");
+    }
+}
@@ -0,0 +1,394 @@
+use std::{path::Path, time::{Duration, Instant}};
+
+use a_duk::{bus::Payload, frame_writer::LineEnding, pet::Pet, PetRuntime};
+use mlua::Lua;
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/two_states");
+
+fn load(lua: &Lua) -> PetRuntime<'_> {
+    lua.globals().set("record", lua.create_function(|_, _: String| Ok(())).unwrap()).unwrap();
+
+    PetRuntime::new(Pet::load(lua, Path::new(FIXTURE)).unwrap()).unwrap()
+}
+
+fn rendered(runtime: &PetRuntime) -> String {
+    let mut buf = Vec::new();
+    runtime.render_to(&mut buf).unwrap();
+    String::from_utf8(buf).unwrap()
+}
+
+#[test]
+fn ticks_advance_frames_by_delay() {
+    let lua = Lua::new();
+    let mut runtime = load(&lua);
+    let start = Instant::now();
+
+    assert!(runtime.tick(start).unwrap());
+    assert_eq!(runtime.current_frame(), 0);
+    assert!(rendered(&runtime).starts_with(" o"));
+
+    assert!(!runtime.tick(start + Duration::from_millis(50)).unwrap());
+    assert_eq!(runtime.current_frame(), 0);
+
+    assert!(runtime.tick(start + Duration::from_millis(100)).unwrap());
+    assert_eq!(runtime.current_frame(), 1);
+    assert!(rendered(&runtime).starts_with(" O"));
+
+    assert!(runtime.tick(start + Duration::from_millis(200)).unwrap());
+    assert_eq!(runtime.current_frame(), 0);
+}
+
+#[test]
+fn rendering_ends_lines_as_asked() {
+    let lua = Lua::new();
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/functional");
+    let runtime = PetRuntime::new(Pet::load(&lua, &path).unwrap()).unwrap();
+
+    let mut buf = Vec::new();
+    runtime.render_to(&mut buf).unwrap();
+    assert_eq!(String::from_utf8(buf).unwrap(), " o\n/|\\");
+
+    let mut buf = Vec::new();
+    runtime.render_to_with(&mut buf, LineEnding::CrLf).unwrap();
+    assert_eq!(String::from_utf8(buf).unwrap(), " o\r\n/|\\");
+}
+
+#[test]
+fn render_at_draws_like_render_into() {
+    let lua = Lua::new();
+    let runtime = load(&lua);
+
+    let mut canvas = a_duk::canvas::Canvas::new((6, 2));
+    runtime.render_into(&mut canvas, (2, 1));
+    assert_eq!(canvas.row(1), "   o  ");
+
+    let mut buf = Vec::new();
+    runtime.render_at(&mut buf, (2, 1), (6, 2)).unwrap();
+    let mut expected = Vec::new();
+    canvas.write_to(&mut expected).unwrap();
+    assert_eq!(buf, expected);
+}
+
+#[test]
+fn animation_handles_describe_animations() {
+    let lua = Lua::new();
+    let mut runtime = load(&lua);
+    let start = Instant::now();
+    runtime.tick(start).unwrap();
+    runtime.tick(start + Duration::from_millis(100)).unwrap();
+
+    let (name, frames, delay, frame): (String, usize, u64, usize) = lua.load(r#"
+        local anim = get_animation()
+        return anim:name(), anim:frame_count(), anim:delay(), anim:current_frame()
+    "#).eval().unwrap();
+
+    assert_eq!((name.as_str(), frames, delay, frame), ("idle", 2, 100, 1));
+    assert!(lua.load("return get_animation('missing')").eval::<mlua::Value>().unwrap().is_nil());
+}
+
+#[test]
+fn frame_tags_follow_the_current_frame() {
+    let lua = Lua::new();
+    let mut runtime = load(&lua);
+    let start = Instant::now();
+    let has_tag = || lua.load("return current_frame_has_tag('big')").eval::<bool>().unwrap();
+
+    runtime.tick(start).unwrap();
+    assert!(!has_tag());
+
+    runtime.tick(start + Duration::from_millis(100)).unwrap();
+    assert!(has_tag());
+}
+
+#[test]
+fn speed_scales_frame_delays() {
+    let lua = Lua::new();
+    let mut runtime = load(&lua);
+    let start = Instant::now();
+
+    runtime.set_speed(2.0);
+    runtime.tick(start).unwrap();
+    assert!(runtime.tick(start + Duration::from_millis(50)).unwrap());
+    assert_eq!(runtime.current_frame(), 1);
+
+    lua.load("set_speed(100)").exec().unwrap();
+    assert_eq!(runtime.speed(), 10.0);
+}
+
+#[test]
+fn key_names_include_modifiers() {
+    use a_duk::runtime::key_name;
+    use crossterm::event::{KeyCode, KeyModifiers};
+
+    assert_eq!(key_name(KeyCode::Char('r'), KeyModifiers::NONE), "r");
+    assert_eq!(key_name(KeyCode::Char('r'), KeyModifiers::CONTROL), "ctrl+r");
+    assert_eq!(key_name(KeyCode::Char('R'), KeyModifiers::SHIFT), "R");
+    assert_eq!(key_name(KeyCode::Left, KeyModifiers::SHIFT | KeyModifiers::ALT), "alt+shift+left");
+    assert_eq!(key_name(KeyCode::Char(' '), KeyModifiers::NONE), "space");
+}
+
+#[test]
+fn pressed_keys_are_buffered_for_polling() {
+    use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+
+    let lua = Lua::new();
+    let mut runtime = load(&lua);
+
+    for c in ['a', 'b', 'c'] {
+        runtime.handle_key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE)).unwrap();
+    }
+    runtime.handle_key(KeyEvent::new_with_kind(KeyCode::Char('d'), KeyModifiers::NONE, KeyEventKind::Release)).unwrap();
+
+    let keys: Vec<String> = lua.load("return get_keys()").eval().unwrap();
+    assert_eq!(keys, ["a", "b", "c"]);
+
+    runtime.handle_key(KeyEvent::new(KeyCode::Up, KeyModifiers::NONE)).unwrap();
+    runtime.handle_key(KeyEvent::new(KeyCode::Down, KeyModifiers::NONE)).unwrap();
+    let (peeked, last, after): (String, String, Option<String>) = lua.load(
+        "return peek_last_key(), get_last_key(), get_last_key()").eval().unwrap();
+    assert_eq!((peeked.as_str(), last.as_str(), after), ("down", "down", None));
+}
+
+#[test]
+fn held_keys_time_out_without_release_events() {
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    let lua = Lua::new();
+    let mut runtime = load(&lua);
+    let start = Instant::now();
+    runtime.tick(start).unwrap();
+
+    runtime.handle_key(KeyEvent::new(KeyCode::Right, KeyModifiers::NONE)).unwrap();
+    runtime.tick(start + Duration::from_millis(300)).unwrap();
+    assert!(lua.load("return is_key_down('right')").eval::<bool>().unwrap());
+    assert!(!lua.load("return is_key_down('left')").eval::<bool>().unwrap());
+
+    runtime.tick(start + Duration::from_millis(700)).unwrap();
+    assert!(!lua.load("return is_key_down('right')").eval::<bool>().unwrap());
+}
+
+#[test]
+fn held_keys_stay_down_until_released() {
+    use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+
+    let lua = Lua::new();
+    let mut runtime = load(&lua);
+    let start = Instant::now();
+    runtime.tick(start).unwrap();
+
+    let key = |kind| KeyEvent::new_with_kind(KeyCode::Char('w'), KeyModifiers::NONE, kind);
+    runtime.handle_key(key(KeyEventKind::Release)).unwrap();
+    runtime.handle_key(key(KeyEventKind::Press)).unwrap();
+    runtime.tick(start + Duration::from_secs(5)).unwrap();
+    assert!(lua.load("return is_key_down('w')").eval::<bool>().unwrap());
+
+    runtime.handle_key(key(KeyEventKind::Release)).unwrap();
+    assert!(!lua.load("return is_key_down('w')").eval::<bool>().unwrap());
+}
+
+#[test]
+fn center_anchored_frames_are_centered_in_their_area() {
+    use a_duk::pet::Anchor;
+
+    let lua = Lua::new();
+    let mut runtime = load(&lua);
+    assert_eq!(runtime.anchored_origin((5, 0), (10, 10)), (5, 0));
+
+    // The frames are 2 columns wide and 1 line tall
+    runtime.pet.metadata.anchor = Anchor::Center;
+    assert_eq!(runtime.anchored_origin((5, 0), (10, 10)), (9, 4));
+    assert_eq!(runtime.anchored_origin((0, 0), (1, 1)), (0, 0));
+}
+
+#[test]
+fn wrong_argument_types_name_the_function() {
+    let lua = Lua::new();
+    let _runtime = load(&lua);
+    let error = |code: &str| lua.load(code).exec().unwrap_err().to_string();
+
+    assert!(error("set_current_anim(5)").contains("set_current_anim expected a string animation name, got number"));
+    assert!(error("adjust_stat('hunger')").contains("adjust_stat expected a number delta as argument 2, got nil"));
+    assert!(error("coroutine.wrap(function() wait('soon') end)()").contains("wait expected a number of milliseconds, got string"));
+
+    // Optional arguments can be left out
+    lua.load("get_animation()").exec().unwrap();
+}
+
+#[test]
+fn flipped_pets_render_mirrored() {
+    let lua = Lua::new();
+    let mut runtime = load(&lua);
+    runtime.tick(Instant::now()).unwrap();
+
+    lua.load("set_flip(true)").exec().unwrap();
+    assert!(rendered(&runtime).starts_with("o "));
+    assert!(lua.load("return get_flip()").eval::<bool>().unwrap());
+
+    lua.load("set_flip(false)").exec().unwrap();
+    assert!(rendered(&runtime).starts_with(" o"));
+}
+
+#[test]
+fn state_changes_are_kept_in_the_history() {
+    use a_duk::runtime::TransitionKind;
+
+    let lua = Lua::new();
+    let mut runtime = load(&lua);
+    let start = Instant::now();
+    runtime.tick(start).unwrap();
+
+    lua.load("set_current_state('second')").exec().unwrap();
+    runtime.tick(start + Duration::from_millis(1500)).unwrap();
+
+    let history: Vec<_> = runtime.history().collect();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].kind, TransitionKind::State);
+    assert_eq!(history[0].to_string(), "1.5s state first -> second");
+}
+
+#[test]
+fn transitions_can_wait_for_the_animation_to_finish() {
+    let lua = Lua::new();
+    let mut runtime = load(&lua);
+    let start = Instant::now();
+    runtime.tick(start).unwrap();
+
+    lua.load("transition_after_anim('second')").exec().unwrap();
+    runtime.tick(start + Duration::from_millis(100)).unwrap();
+    assert_eq!(runtime.current_state(), "first");
+    assert_eq!(runtime.current_frame(), 1);
+
+    // After the last frame has been shown for its delay
+    runtime.tick(start + Duration::from_millis(200)).unwrap();
+    assert_eq!(runtime.current_state(), "second");
+    assert_eq!(runtime.current_frame(), 0);
+
+    let error = lua.load("transition_after_anim('nowhere')").exec().unwrap_err();
+    assert!(error.to_string().contains("unknown state 'nowhere'"));
+}
+
+#[test]
+fn terminal_caps_follow_the_driver() {
+    use a_duk::color::ColorDepth;
+
+    let lua = Lua::new();
+    let mut runtime = load(&lua);
+    runtime.set_color_depth(ColorDepth::Ansi256);
+    runtime.set_output_is_tty(false);
+
+    let (colors, is_tty, mouse): (String, bool, bool) = lua.load(r#"
+        local caps = get_terminal_caps()
+        return caps.colors, caps.is_tty, caps.supports_mouse
+    "#).eval().unwrap();
+    assert_eq!((colors.as_str(), is_tty, mouse), ("256", false, false));
+    assert_eq!(lua.load("return get_color_support()").eval::<String>().unwrap(), "256");
+}
+
+#[test]
+fn scripts_see_the_viewport_as_the_terminal() {
+    let lua = Lua::new();
+    let mut runtime = load(&lua);
+    runtime.set_viewport(Some((4, 2)));
+
+    // Smaller than any terminal the tests run in, and used as is without one
+    let (cols, rows): (u16, u16) = lua.load(r#"
+        local caps = get_terminal_caps()
+        return caps.cols, caps.rows
+    "#).eval().unwrap();
+    assert_eq!((cols, rows), (4, 2));
+}
+
+#[test]
+fn hidden_pets_keep_ticking_but_draw_nothing() {
+    let lua = Lua::new();
+    let mut runtime = load(&lua);
+    let start = Instant::now();
+    runtime.tick(start).unwrap();
+
+    lua.load("set_visible(false)").exec().unwrap();
+    assert!(!lua.load("return is_visible()").eval::<bool>().unwrap());
+    assert_eq!(rendered(&runtime), "");
+
+    runtime.tick(start + Duration::from_millis(100)).unwrap();
+    assert_eq!(runtime.current_frame(), 1);
+
+    lua.load("set_visible(true)").exec().unwrap();
+    assert!(rendered(&runtime).starts_with(" O"));
+}
+
+#[test]
+fn seeded_runs_on_virtual_time_repeat_exactly() {
+    let run = || {
+        let lua = Lua::new();
+        let mut runtime = load(&lua);
+        runtime.set_seed(7).unwrap();
+        runtime.use_virtual_time().unwrap();
+
+        let start = Instant::now();
+        runtime.tick(start).unwrap();
+        runtime.tick(start + Duration::from_millis(2500)).unwrap();
+
+        lua.load("return math.random(1000000), get_time(), os.clock(), os.time()")
+            .eval::<(i64, u64, f64, i64)>()
+            .unwrap()
+    };
+
+    let (_, time, clock, os_time) = run();
+    assert_eq!((time, clock, os_time), (2500, 2.5, 2));
+    assert_eq!(run(), run());
+}
+
+#[test]
+fn get_args_shows_the_launch_arguments_read_only() {
+    let lua = Lua::new();
+    let mut runtime = load(&lua);
+    runtime.set_launch_args(vec![
+        ("pet".to_string(), Payload::String("two_states".to_string())),
+        ("debug".to_string(), Payload::Bool(true)),
+    ]);
+
+    let (pet, debug): (String, bool) = lua.load("local args = get_args() return args.pet, args.debug").eval().unwrap();
+    assert_eq!((pet.as_str(), debug), ("two_states", true));
+
+    let count: i64 = lua.load("local n = 0 for _ in pairs(get_args()) do n = n + 1 end return n").eval().unwrap();
+    assert_eq!(count, 2);
+
+    assert!(lua.load("get_args().debug = false").exec().is_err());
+    assert!(lua.load("get_args().verbose = true").exec().is_err());
+    assert!(lua.load("return get_args().debug").eval::<bool>().unwrap());
+}
+
+#[test]
+fn get_metadata_mirrors_the_pet_metadata_read_only() {
+    let lua = Lua::new();
+    let _runtime = load(&lua);
+
+    let (name, default_state, delay): (String, String, u64) = lua
+        .load("local meta = get_metadata() return meta.name, meta.default_state, meta.global_tick_delay")
+        .eval().unwrap();
+    assert_eq!((name.as_str(), default_state.as_str(), delay), ("Two states", "first", 50));
+
+    let (anchor, extends, pairs): (String, Option<String>, i64) = lua
+        .load("local meta = get_metadata() return meta.anchor, meta.extends, #meta.mirror_pairs")
+        .eval().unwrap();
+    assert_eq!((anchor.as_str(), extends, pairs), ("top_left", None, 5));
+
+    assert!(lua.load("get_metadata().default_state = 'second'").exec().is_err());
+    assert_eq!(lua.load("return get_metadata().default_state").eval::<String>().unwrap(), "first");
+}
+
+#[test]
+fn the_clipboard_is_read_without_failing() {
+    let lua = Lua::new();
+    let _runtime = load(&lua);
+
+    // A string, or nil without a clipboard
+    let text: Option<String> = lua.load("return get_clipboard()").eval().unwrap();
+    if cfg!(not(feature = "clipboard")) {
+        assert_eq!(text, None);
+        let error = lua.load("set_clipboard('quack')").exec().unwrap_err();
+        assert!(error.to_string().contains("without the clipboard feature"), "{error}");
+    }
+
+    assert!(lua.load("set_clipboard(5)").exec().is_err());
+}
@@ -0,0 +1,170 @@
+use std::{path::Path, time::Instant};
+
+use a_duk::{pet::Pet, save::SaveFile, PetRuntime};
+use mlua::Lua;
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/evolving");
+
+fn save_path(name: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("a_duk-test-{}-{name}.toml", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+#[test]
+fn set_current_state_switches_after_the_handler() {
+    let lua = Lua::new();
+    let mut runtime = PetRuntime::new(Pet::load(&lua, Path::new(FIXTURE)).unwrap()).unwrap();
+    assert_eq!(runtime.current_state(), "egg");
+
+    runtime.tick(Instant::now()).unwrap();
+    assert_eq!(runtime.current_state(), "duck");
+    assert!(lua.globals().get::<_, bool>("hatched").unwrap());
+}
+
+#[test]
+fn unknown_states_are_rejected() {
+    let lua = Lua::new();
+    let _runtime = PetRuntime::new(Pet::load(&lua, Path::new(FIXTURE)).unwrap()).unwrap();
+
+    assert!(lua.load("set_current_state('nope')").exec().is_err());
+}
+
+#[test]
+fn the_state_is_restored_from_the_save() {
+    let path = save_path("restore");
+
+    {
+        let lua = Lua::new();
+        let save = SaveFile::load(&path).unwrap();
+        let mut runtime = PetRuntime::with_save(Pet::load(&lua, Path::new(FIXTURE)).unwrap(), Some(save)).unwrap();
+        runtime.tick(Instant::now()).unwrap();
+        runtime.save().unwrap();
+    }
+
+    let lua = Lua::new();
+    let save = SaveFile::load(&path).unwrap();
+    assert_eq!(save.data.state.as_deref(), Some("duck"));
+
+    let runtime = PetRuntime::with_save(Pet::load(&lua, Path::new(FIXTURE)).unwrap(), Some(save)).unwrap();
+    assert_eq!(runtime.current_state(), "duck");
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn missing_saved_states_fall_back_to_the_default() {
+    let path = save_path("missing");
+    std::fs::write(&path, "state = \"gone\"\n").unwrap();
+
+    let lua = Lua::new();
+    let save = SaveFile::load(&path).unwrap();
+    let runtime = PetRuntime::with_save(Pet::load(&lua, Path::new(FIXTURE)).unwrap(), Some(save)).unwrap();
+    assert_eq!(runtime.current_state(), "egg");
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn the_store_survives_a_restart() {
+    let path = save_path("store");
+
+    {
+        let lua = Lua::new();
+        let save = SaveFile::load(&path).unwrap();
+        let mut runtime = PetRuntime::with_save(Pet::load(&lua, Path::new(FIXTURE)).unwrap(), Some(save)).unwrap();
+        lua.load(r#"
+            store_set("name", "Quackers")
+            store_set("feedings", 3)
+            store_set("weight", 1.5)
+            store_set("fed", true)
+            store_set("gone", 1)
+            store_set("gone", nil)
+        "#).exec().unwrap();
+        runtime.save().unwrap();
+    }
+
+    let lua = Lua::new();
+    let save = SaveFile::load(&path).unwrap();
+    let _runtime = PetRuntime::with_save(Pet::load(&lua, Path::new(FIXTURE)).unwrap(), Some(save)).unwrap();
+
+    let (name, feedings, weight, fed, gone): (String, i64, f64, bool, mlua::Value) = lua.load(r#"
+        return store_get("name"), store_get("feedings"), store_get("weight"), store_get("fed"), store_get("gone")
+    "#).eval().unwrap();
+    assert_eq!((name.as_str(), feedings, weight, fed), ("Quackers", 3, 1.5, true));
+    assert!(gone.is_nil());
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn the_store_rejects_tables() {
+    let lua = Lua::new();
+    let _runtime = PetRuntime::new(Pet::load(&lua, Path::new(FIXTURE)).unwrap()).unwrap();
+
+    assert!(lua.load("store_set('t', {})").exec().is_err());
+}
+
+#[test]
+fn counters_survive_a_restart() {
+    let path = save_path("counters");
+
+    {
+        let lua = Lua::new();
+        let save = SaveFile::load(&path).unwrap();
+        let mut runtime = PetRuntime::with_save(Pet::load(&lua, Path::new(FIXTURE)).unwrap(), Some(save)).unwrap();
+        let (fed, treats): (i64, i64) = lua.load(r#"
+            increment_counter("feeds")
+            increment_counter("treats", 5)
+            return increment_counter("feeds"), get_counter("treats")
+        "#).eval().unwrap();
+        assert_eq!((fed, treats), (2, 5));
+        runtime.save().unwrap();
+    }
+
+    let lua = Lua::new();
+    let save = SaveFile::load(&path).unwrap();
+    assert!(save.data.first_run.is_some());
+    let _runtime = PetRuntime::with_save(Pet::load(&lua, Path::new(FIXTURE)).unwrap(), Some(save)).unwrap();
+
+    let (feeds, missing, days): (i64, i64, i64) = lua.load(r#"
+        return get_counter("feeds"), get_counter("missing"), days_alive()
+    "#).eval().unwrap();
+    assert_eq!((feeds, missing, days), (2, 0, 0));
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn days_alive_counts_from_the_first_run() {
+    let path = save_path("days");
+    let three_days_ago = a_duk::runtime::unix_time() - 3 * 24 * 60 * 60 - 60;
+    std::fs::write(&path, format!("first_run = {three_days_ago}\n")).unwrap();
+
+    let lua = Lua::new();
+    let save = SaveFile::load(&path).unwrap();
+    let _runtime = PetRuntime::with_save(Pet::load(&lua, Path::new(FIXTURE)).unwrap(), Some(save)).unwrap();
+    assert_eq!(lua.load("return days_alive()").eval::<i64>().unwrap(), 3);
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn saves_replace_the_old_file_in_one_go() {
+    use a_duk::save::SaveData;
+
+    let path = save_path("atomic");
+    std::fs::write(&path, "state = \"old\"\n").unwrap();
+
+    let mut save = SaveFile::load(&path).unwrap();
+    save.data = SaveData { state: Some("new".to_string()), ..SaveData::default() };
+    save.write().unwrap();
+
+    assert_eq!(SaveFile::load(&path).unwrap().data.state.as_deref(), Some("new"));
+    // Nothing is left next to it
+    let mut temp_name = path.file_name().unwrap().to_os_string();
+    temp_name.push(".tmp");
+    assert!(!path.with_file_name(temp_name).exists());
+
+    std::fs::remove_file(path).unwrap();
+}
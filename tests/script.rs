@@ -0,0 +1,34 @@
+use std::{cell::RefCell, path::Path, rc::Rc, time::{Duration, Instant}};
+
+use a_duk::{pet::Pet, PetRuntime};
+use mlua::Lua;
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/scripted");
+
+#[test]
+fn main_coroutine_waits_between_steps() {
+    let lua = Lua::new();
+    let log = Rc::new(RefCell::new(Vec::<String>::new()));
+    let log_closure = log.clone();
+    lua.globals().set("record", lua.create_function(move |_, msg: String| {
+        log_closure.borrow_mut().push(msg);
+        Ok(())
+    }).unwrap()).unwrap();
+
+    let mut runtime = PetRuntime::new(Pet::load(&lua, Path::new(FIXTURE)).unwrap()).unwrap();
+    let start = Instant::now();
+    let at = |ms| start + Duration::from_millis(ms);
+
+    runtime.tick(at(0)).unwrap();
+    assert_eq!(*log.borrow(), ["left"]);
+
+    runtime.tick(at(99)).unwrap();
+    assert_eq!(*log.borrow(), ["left"]);
+
+    runtime.tick(at(100)).unwrap();
+    assert_eq!(*log.borrow(), ["left", "quack"]);
+
+    runtime.tick(at(150)).unwrap();
+    runtime.tick(at(1000)).unwrap();
+    assert_eq!(*log.borrow(), ["left", "quack", "right"]);
+}
@@ -0,0 +1,29 @@
+use std::{path::Path, time::Instant};
+
+use a_duk::{canvas::Canvas, pet::Pet, text::skip_columns, PetRuntime};
+use mlua::Lua;
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/layered");
+
+#[test]
+fn skipping_columns_goes_by_display_width() {
+    assert_eq!(skip_columns("(o o)", 2), " o)");
+    assert_eq!(skip_columns("(o o)", 9), "");
+    // Half of the wide grapheme is cut off
+    assert_eq!(skip_columns("界x", 1), " x");
+}
+
+#[test]
+fn panned_frames_are_drawn_from_the_pan() {
+    let lua = Lua::new();
+    let mut runtime = PetRuntime::new(Pet::load(&lua, Path::new(FIXTURE)).unwrap()).unwrap();
+    runtime.tick(Instant::now()).unwrap();
+    assert_eq!(runtime.animation_size(), (5, 2));
+
+    assert!(runtime.set_pan((2, 1)));
+    assert!(!runtime.set_pan((2, 1)));
+
+    let mut canvas = Canvas::new((4, 2));
+    runtime.render_into(&mut canvas, (0, 0));
+    assert_eq!([canvas.row(0), canvas.row(1)], ["_ ) ", "    "]);
+}
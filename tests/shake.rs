@@ -0,0 +1,45 @@
+use std::{path::Path, time::{Duration, Instant}};
+
+use a_duk::{canvas::Canvas, pet::Pet, PetRuntime};
+use mlua::Lua;
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/layered");
+
+// Where the top left corner of the body ended up
+fn position(runtime: &PetRuntime) -> (usize, usize) {
+    let mut canvas = Canvas::new((11, 6));
+    runtime.render_into(&mut canvas, (3, 2));
+
+    (0..6).find_map(|y| canvas.row(y).find("(o o)").map(|x| (x, usize::from(y)))).unwrap()
+}
+
+#[test]
+fn shaking_moves_the_pet_and_settles_back() {
+    let lua = Lua::new();
+    let mut runtime = PetRuntime::new(Pet::load(&lua, Path::new(FIXTURE)).unwrap()).unwrap();
+    runtime.set_seed(7).unwrap();
+    let start = Instant::now();
+    runtime.tick(start).unwrap();
+    assert_eq!(position(&runtime), (3, 2));
+
+    lua.load("shake(2, 100)").exec().unwrap();
+    let mut moved = false;
+    for ms in [10, 30, 50, 70, 90] {
+        assert!(runtime.tick(start + Duration::from_millis(ms)).unwrap());
+        let (x, y) = position(&runtime);
+        assert!(x.abs_diff(3) <= 2 && y.abs_diff(2) <= 2, "{ms}ms: ({x}, {y})");
+        moved |= (x, y) != (3, 2);
+    }
+    assert!(moved);
+
+    runtime.tick(start + Duration::from_millis(100)).unwrap();
+    assert_eq!(position(&runtime), (3, 2));
+}
+
+#[test]
+fn the_intensity_cant_be_negative() {
+    let lua = Lua::new();
+    let _runtime = PetRuntime::new(Pet::load(&lua, Path::new(FIXTURE)).unwrap()).unwrap();
+
+    assert!(lua.load("shake(-1, 100)").exec().is_err());
+}
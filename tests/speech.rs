@@ -0,0 +1,91 @@
+use std::{path::Path, time::{Duration, Instant}};
+
+use a_duk::{canvas::Canvas, pet::Pet, PetRuntime};
+use mlua::Lua;
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/layered");
+
+#[test]
+fn lines_are_said_one_after_another() {
+    let lua = Lua::new();
+    let mut runtime = PetRuntime::new(Pet::load(&lua, Path::new(FIXTURE)).unwrap()).unwrap();
+    let start = Instant::now();
+    runtime.tick(start).unwrap();
+
+    lua.load("say('hi', 100) say('bye', 100)").exec().unwrap();
+    assert!(runtime.tick(start + Duration::from_millis(10)).unwrap());
+    assert_eq!(runtime.speech().as_deref(), Some("hi"));
+
+    runtime.tick(start + Duration::from_millis(100)).unwrap();
+    assert_eq!(runtime.speech().as_deref(), Some("hi"));
+
+    assert!(runtime.tick(start + Duration::from_millis(110)).unwrap());
+    assert_eq!(runtime.speech().as_deref(), Some("bye"));
+
+    runtime.tick(start + Duration::from_millis(210)).unwrap();
+    assert_eq!(runtime.speech(), None);
+}
+
+#[test]
+fn interrupting_drops_the_queue() {
+    let lua = Lua::new();
+    let mut runtime = PetRuntime::new(Pet::load(&lua, Path::new(FIXTURE)).unwrap()).unwrap();
+    let start = Instant::now();
+    runtime.tick(start).unwrap();
+
+    lua.load("say('one') say('two') say('now', 100, true)").exec().unwrap();
+    runtime.tick(start + Duration::from_millis(10)).unwrap();
+    assert_eq!(runtime.speech().as_deref(), Some("now"));
+
+    runtime.tick(start + Duration::from_millis(110)).unwrap();
+    assert_eq!(runtime.speech(), None);
+
+    lua.load("say('one') say('two') clear_speech()").exec().unwrap();
+    runtime.tick(start + Duration::from_millis(120)).unwrap();
+    assert_eq!(runtime.speech(), None);
+}
+
+#[test]
+fn speech_goes_above_the_pet_or_below_it() {
+    let lua = Lua::new();
+    let mut runtime = PetRuntime::new(Pet::load(&lua, Path::new(FIXTURE)).unwrap()).unwrap();
+    runtime.tick(Instant::now()).unwrap();
+    lua.load("say('quack')").exec().unwrap();
+
+    let mut canvas = Canvas::new((8, 5));
+    runtime.render_into(&mut canvas, (1, 2));
+    assert_eq!(canvas.row(1), " quack  ");
+    assert_eq!(canvas.row(2), " (o o)  ");
+
+    let mut canvas = Canvas::new((8, 5));
+    runtime.render_into(&mut canvas, (0, 0));
+    assert_eq!(canvas.row(0), "(o o)   ");
+    assert_eq!(canvas.row(2), "quack   ");
+}
+
+#[test]
+fn the_queue_is_bounded() {
+    let lua = Lua::new();
+    let _runtime = PetRuntime::new(Pet::load(&lua, Path::new(FIXTURE)).unwrap()).unwrap();
+
+    let queued: bool = lua.load("for _ = 1, 16 do say('x') end return say('one too many')").eval().unwrap();
+    assert!(!queued);
+}
+
+#[test]
+fn prompts_take_the_place_of_speech() {
+    let lua = Lua::new();
+    let mut runtime = PetRuntime::new(Pet::load(&lua, Path::new(FIXTURE)).unwrap()).unwrap();
+    runtime.tick(Instant::now()).unwrap();
+    lua.load("say('quack')").exec().unwrap();
+
+    runtime.set_prompt(Some("Quit? (y/n)"));
+    let mut canvas = Canvas::new((12, 5));
+    runtime.render_into(&mut canvas, (0, 2));
+    assert_eq!(canvas.row(1), "Quit? (y/n) ");
+
+    runtime.set_prompt(None);
+    let mut canvas = Canvas::new((12, 5));
+    runtime.render_into(&mut canvas, (0, 2));
+    assert_eq!(canvas.row(1), "quack       ");
+}
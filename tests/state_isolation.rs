@@ -0,0 +1,72 @@
+use std::{cell::RefCell, path::Path, rc::Rc};
+
+use a_duk::pet::Pet;
+use mlua::Lua;
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/two_states");
+
+// Loads the fixture with a `record` global that the state scripts call into.
+fn load_recording(lua: &Lua) -> (Pet<'_>, Rc<RefCell<Vec<String>>>) {
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let log_closure = log.clone();
+
+    lua.globals().set(
+        "record",
+        lua.create_function(move |_, msg: String| {
+            log_closure.borrow_mut().push(msg);
+            Ok(())
+        }).unwrap()
+    ).unwrap();
+
+    let pet = Pet::load(lua, Path::new(FIXTURE)).unwrap();
+
+    (pet, log)
+}
+
+#[test]
+fn states_share_animations() {
+    let lua = Lua::new();
+    let (pet, _) = load_recording(&lua);
+
+    assert_eq!(pet.animations.len(), 1);
+    assert_eq!(pet.states.len(), 2);
+
+    for state in pet.states.values() {
+        assert!(pet.animations.contains_key(&state.metadata.animation));
+    }
+}
+
+#[test]
+fn each_state_runs_its_own_update() {
+    let lua = Lua::new();
+    let (pet, log) = load_recording(&lua);
+
+    for name in ["first", "second", "first"] {
+        let update = pet.states[name].event_handlers.update.as_ref().unwrap();
+        update.call::<(), ()>(()).unwrap();
+
+        assert_eq!(log.borrow().last().unwrap(), &format!("{name} update"));
+    }
+}
+
+#[test]
+fn each_state_runs_its_own_init() {
+    let lua = Lua::new();
+    let (pet, log) = load_recording(&lua);
+
+    for name in ["second", "first"] {
+        let init = pet.states[name].event_handlers.init.as_ref().unwrap();
+        init.call::<(), ()>(()).unwrap();
+    }
+
+    assert_eq!(*log.borrow(), ["second init", "first init"]);
+}
+
+#[test]
+fn state_handlers_do_not_leak_into_globals() {
+    let lua = Lua::new();
+    let _ = load_recording(&lua);
+
+    assert!(lua.globals().get::<_, mlua::Value>("Update").unwrap().is_nil());
+    assert!(lua.globals().get::<_, mlua::Value>("Init").unwrap().is_nil());
+}
@@ -0,0 +1,87 @@
+use std::time::Duration;
+
+use a_duk::stats::{Stat, Stats};
+
+#[test]
+fn stats_decay_and_clamp() {
+    let mut stats = Stats::default();
+    stats.register("hunger".to_string(), Stat::new(50.0, 10.0, 0.0, 100.0).unwrap());
+    stats.register("energy".to_string(), Stat::new(5.0, -2.0, 0.0, 100.0).unwrap());
+
+    stats.decay(Duration::from_secs(2));
+    assert_eq!(stats.get("hunger"), Some(70.0));
+    assert_eq!(stats.get("energy"), Some(1.0));
+
+    stats.decay(Duration::from_secs(10));
+    assert_eq!(stats.get("hunger"), Some(100.0));
+    assert_eq!(stats.get("energy"), Some(0.0));
+
+    assert!(stats.adjust("energy", 30.0));
+    assert_eq!(stats.get("energy"), Some(30.0));
+    assert!(!stats.adjust("missing", 1.0));
+}
+
+#[test]
+fn stat_rejects_inverted_bounds() {
+    assert!(Stat::new(0.0, 1.0, 10.0, 0.0).is_err());
+}
+
+#[test]
+fn thresholds_are_reported_once_per_crossing() {
+    use a_duk::stats::{Crossing, Thresholds};
+
+    let mut stat = Stat::new(50.0, 0.0, 0.0, 100.0).unwrap()
+        .with_thresholds(Thresholds { high: Some(80.0), low: Some(10.0), hysteresis: 5.0 })
+        .unwrap();
+
+    assert_eq!(stat.adjust(25.0), None);
+    assert_eq!(stat.adjust(5.0), Some(Crossing::High));
+    // Hovering around the threshold doesn't count
+    assert_eq!(stat.adjust(-3.0), None);
+    assert_eq!(stat.adjust(3.0), None);
+    // Until it has come back far enough
+    assert_eq!(stat.adjust(-10.0), None);
+    assert_eq!(stat.adjust(10.0), Some(Crossing::High));
+
+    assert_eq!(stat.adjust(-75.0), Some(Crossing::Low));
+    assert_eq!(stat.adjust(-5.0), None);
+}
+
+#[test]
+fn thresholds_have_to_make_sense() {
+    use a_duk::stats::Thresholds;
+
+    let stat = Stat::new(50.0, 0.0, 0.0, 100.0).unwrap();
+    assert!(stat.clone().with_thresholds(Thresholds { high: Some(10.0), low: Some(80.0), hysteresis: 0.0 }).is_err());
+    assert!(stat.with_thresholds(Thresholds { high: Some(80.0), low: None, hysteresis: -1.0 }).is_err());
+}
+
+#[test]
+fn scripts_are_called_back_when_a_stat_crosses_a_threshold() {
+    use std::{path::Path, time::Instant};
+
+    use a_duk::{pet::Pet, PetRuntime};
+    use mlua::Lua;
+
+    let lua = Lua::new();
+    lua.globals().set("record", lua.create_function(|_, _: String| Ok(())).unwrap()).unwrap();
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/two_states");
+    let mut runtime = PetRuntime::new(Pet::load(&lua, &path).unwrap()).unwrap();
+    let start = Instant::now();
+    runtime.tick(start).unwrap();
+
+    lua.load(r#"
+        crossed = {}
+        register_stat("hunger", 70, 10, 0, 100, {
+            high = 80,
+            on_high = function(name, value) table.insert(crossed, name .. " high " .. value) end,
+        })
+    "#).exec().unwrap();
+
+    runtime.tick(start + Duration::from_millis(500)).unwrap();
+    runtime.tick(start + Duration::from_millis(1500)).unwrap();
+    runtime.tick(start + Duration::from_millis(2000)).unwrap();
+
+    let crossed: Vec<String> = lua.load("return crossed").eval().unwrap();
+    assert_eq!(crossed, ["hunger high 85.0"]);
+}
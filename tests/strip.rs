@@ -0,0 +1,34 @@
+use std::path::Path;
+
+use a_duk::{pet::Pet, preview::animation_strip};
+use mlua::Lua;
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/complete");
+
+#[test]
+fn frames_are_laid_out_side_by_side() {
+    let lua = Lua::new();
+    let pet = Pet::load(&lua, Path::new(FIXTURE)).unwrap();
+
+    assert_eq!(animation_strip(&pet.animations["sit"], 80), [
+        "#0  | #1",
+        " _  |  _",
+        "(o) | (-)",
+    ]);
+}
+
+#[test]
+fn frames_wrap_to_the_width() {
+    let lua = Lua::new();
+    let pet = Pet::load(&lua, Path::new(FIXTURE)).unwrap();
+
+    assert_eq!(animation_strip(&pet.animations["sit"], 8), [
+        "#0",
+        " _",
+        "(o)",
+        "",
+        "#1",
+        " _",
+        "(-)",
+    ]);
+}
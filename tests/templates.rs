@@ -0,0 +1,56 @@
+use std::{collections::BTreeMap, path::Path, time::{Duration, Instant}};
+
+use a_duk::{pet::Pet, text::fill_template, PetRuntime};
+use mlua::Lua;
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/templated");
+
+fn vars(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+    pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+}
+
+#[test]
+fn values_fit_the_placeholder() {
+    let vars = vars(&[("score", "42"), ("name", "Quackers the Great")]);
+
+    assert_eq!(fill_template("[{score}]", &vars), "[42     ]");
+    assert_eq!(fill_template("{name}!", &vars), "Quacke!");
+}
+
+#[test]
+fn unknown_placeholders_and_plain_braces_stay() {
+    let vars = vars(&[("score", "42")]);
+
+    assert_eq!(fill_template("{lives} { } {", &vars), "{lives} { } {");
+    assert_eq!(fill_template("no braces", &vars), "no braces");
+}
+
+#[test]
+fn doubled_braces_are_literal() {
+    let vars = vars(&[("score", "42")]);
+
+    assert_eq!(fill_template("{{score}} {score}", &vars), "{score} 42     ");
+}
+
+#[test]
+fn frames_show_the_current_values() {
+    let lua = Lua::new();
+    let mut runtime = PetRuntime::new(Pet::load(&lua, Path::new(FIXTURE)).unwrap()).unwrap();
+    let start = Instant::now();
+    runtime.tick(start).unwrap();
+    assert_eq!(runtime.displayed_frame(), "[{score}]\n");
+
+    lua.load("set_template_var('score', 1200)").exec().unwrap();
+    assert!(runtime.tick(start + Duration::from_millis(10)).unwrap());
+    assert_eq!(runtime.displayed_frame(), "[1200   ]\n");
+
+    // Setting the same value again doesn't redraw
+    lua.load("set_template_var('score', 1200)").exec().unwrap();
+    assert!(!runtime.tick(start + Duration::from_millis(20)).unwrap());
+
+    lua.load("set_template_var('score', nil)").exec().unwrap();
+    runtime.tick(start + Duration::from_millis(30)).unwrap();
+    assert_eq!(runtime.displayed_frame(), "[{score}]\n");
+
+    assert!(lua.load("set_template_var('no spaces', 1)").exec().is_err());
+}
@@ -0,0 +1,70 @@
+use std::{path::Path, time::{Duration, Instant}};
+
+use a_duk::{canvas::Canvas, pet::Pet, PetRuntime};
+use mlua::Lua;
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/layered");
+
+fn load(lua: &Lua) -> PetRuntime<'_> {
+    PetRuntime::new(Pet::load(lua, Path::new(FIXTURE)).unwrap()).unwrap()
+}
+
+fn rendered(runtime: &PetRuntime) -> Vec<String> {
+    let mut canvas = Canvas::new((5, 2));
+    runtime.render_into(&mut canvas, (0, 0));
+    (0..2).map(|y| canvas.row(y)).collect()
+}
+
+#[test]
+fn tracks_are_drawn_over_the_animation() {
+    let lua = Lua::new();
+    let mut runtime = load(&lua);
+    let start = Instant::now();
+    runtime.tick(start).unwrap();
+    assert_eq!(rendered(&runtime), ["(o o)", "( _ )"]);
+
+    lua.load("play_track('eyes', 'blink')").exec().unwrap();
+    assert!(runtime.tick(start + Duration::from_millis(10)).unwrap());
+    // The transparent ends leave the body's brackets showing
+    assert_eq!(rendered(&runtime), ["(- -)", "( _ )"]);
+}
+
+#[test]
+fn tracks_advance_on_their_own_timer() {
+    let lua = Lua::new();
+    let mut runtime = load(&lua);
+    let start = Instant::now();
+    runtime.tick(start).unwrap();
+    lua.load("play_track('eyes', 'blink')").exec().unwrap();
+    runtime.tick(start).unwrap();
+
+    // The body's frame is due every 100ms, the track's every 50ms
+    assert!(runtime.tick(start + Duration::from_millis(50)).unwrap());
+    assert_eq!(rendered(&runtime), ["(o o)", "( _ )"]);
+    runtime.tick(start + Duration::from_millis(100)).unwrap();
+    assert_eq!(rendered(&runtime), ["(- -)", "( _ )"]);
+}
+
+#[test]
+fn stopped_tracks_disappear() {
+    let lua = Lua::new();
+    let mut runtime = load(&lua);
+    let start = Instant::now();
+    lua.load("play_track('eyes', 'blink')").exec().unwrap();
+    runtime.tick(start).unwrap();
+
+    lua.load("stop_track('eyes')").exec().unwrap();
+    assert!(runtime.tick(start + Duration::from_millis(10)).unwrap());
+    assert_eq!(rendered(&runtime), ["(o o)", "( _ )"]);
+    // Stopping it again is harmless
+    lua.load("stop_track('eyes')").exec().unwrap();
+}
+
+#[test]
+fn unknown_animations_cant_be_played() {
+    let lua = Lua::new();
+    let _runtime = load(&lua);
+
+    let err = lua.load("play_track('eyes', 'wink')").exec().unwrap_err();
+    assert!(err.to_string().contains("unknown animation 'wink'"), "{err}");
+}
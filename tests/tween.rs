@@ -0,0 +1,60 @@
+use std::{path::Path, time::{Duration, Instant}};
+
+use a_duk::{pet::Pet, tween::{Easing, Tween}, PetRuntime};
+use mlua::Lua;
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/demo");
+
+#[test]
+fn easings_meet_at_the_ends() {
+    for easing in [Easing::Linear, Easing::EaseIn, Easing::EaseOut] {
+        assert_eq!(easing.apply(0.0), 0.0);
+        assert_eq!(easing.apply(1.0), 1.0);
+    }
+
+    assert_eq!(Easing::Linear.apply(0.5), 0.5);
+    assert!(Easing::EaseIn.apply(0.5) < 0.5);
+    assert!(Easing::EaseOut.apply(0.5) > 0.5);
+    assert!("bounce".parse::<Easing>().is_err());
+}
+
+#[test]
+fn tweens_move_with_the_clock() {
+    let tween = Tween {
+        from: 10.0,
+        to: 20.0,
+        start: Duration::from_secs(1),
+        duration: Duration::from_secs(2),
+        easing: Easing::Linear,
+    };
+
+    assert_eq!(tween.value(Duration::ZERO), 10.0);
+    assert_eq!(tween.value(Duration::from_secs(2)), 15.0);
+    assert!(!tween.is_done(Duration::from_secs(2)));
+    assert_eq!(tween.value(Duration::from_secs(5)), 20.0);
+    assert!(tween.is_done(Duration::from_secs(3)));
+}
+
+#[test]
+fn scripts_read_tweens_each_tick() {
+    let lua = Lua::new();
+    let mut runtime = PetRuntime::new(Pet::load(&lua, Path::new(FIXTURE)).unwrap()).unwrap();
+    let start = Instant::now();
+    runtime.tick(start).unwrap();
+
+    lua.load("T = tween(0, 100, 1000, 'ease_out')").exec().unwrap();
+    assert_eq!(lua.load("return tween_value(T)").eval::<f64>().unwrap(), 0.0);
+
+    runtime.tick(start + Duration::from_millis(500)).unwrap();
+    assert_eq!(lua.load("return T:value()").eval::<f64>().unwrap(), 75.0);
+    assert!(!lua.load("return tween_done(T)").eval::<bool>().unwrap());
+
+    runtime.tick(start + Duration::from_millis(1000)).unwrap();
+    assert_eq!(lua.load("return tween_value(T)").eval::<f64>().unwrap(), 100.0);
+    assert!(lua.load("return T:is_done()").eval::<bool>().unwrap());
+
+    let error = lua.load("tween(0, 1, 100, 'bounce')").exec().unwrap_err();
+    assert!(error.to_string().contains("tween: unknown easing 'bounce'"));
+    let error = lua.load("tween_value(get_animation())").exec().unwrap_err();
+    assert!(error.to_string().contains("tween_value expected a tween"));
+}
@@ -0,0 +1,50 @@
+use std::{path::Path, time::{Duration, Instant}};
+
+use a_duk::{pet::Pet, PetRuntime};
+use mlua::Lua;
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/functional");
+
+fn walking(lua: &Lua) -> (PetRuntime<'_>, Instant) {
+    let mut runtime = PetRuntime::new(Pet::load(lua, Path::new(FIXTURE)).unwrap()).unwrap();
+    let start = Instant::now();
+    runtime.tick(start).unwrap();
+    runtime.tick(start + Duration::from_millis(50)).unwrap();
+    (runtime, start + Duration::from_millis(50))
+}
+
+#[test]
+fn update_returns_the_next_state() {
+    let lua = Lua::new();
+    let mut runtime = PetRuntime::new(Pet::load(&lua, Path::new(FIXTURE)).unwrap()).unwrap();
+    let start = Instant::now();
+
+    runtime.tick(start).unwrap();
+    assert_eq!(runtime.current_state(), "idle");
+
+    runtime.tick(start + Duration::from_millis(50)).unwrap();
+    assert_eq!(runtime.current_state(), "walking");
+}
+
+#[test]
+fn returning_nothing_stays() {
+    let lua = Lua::new();
+    let (mut runtime, now) = walking(&lua);
+
+    runtime.tick(now + Duration::from_millis(50)).unwrap();
+    assert_eq!(runtime.current_state(), "walking");
+}
+
+#[test]
+fn the_returned_state_has_to_exist() {
+    let lua = Lua::new();
+    let (mut runtime, now) = walking(&lua);
+
+    lua.globals().set("next_state", "flying").unwrap();
+    let error = runtime.tick(now + Duration::from_millis(50)).unwrap_err();
+    assert!(error.to_string().contains("Update returned an unknown state 'flying'"), "{error}");
+
+    lua.globals().set("next_state", true).unwrap();
+    let error = runtime.tick(now + Duration::from_millis(100)).unwrap_err();
+    assert!(error.to_string().contains("Update returned a boolean"), "{error}");
+}